@@ -0,0 +1,4 @@
+fn main() -> std::io::Result<()> {
+    prost_build::compile_protos(&["proto/marketdata/v1/market_data.proto"], &["proto/"])?;
+    Ok(())
+}
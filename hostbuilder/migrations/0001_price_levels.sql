@@ -0,0 +1,11 @@
+-- Level 2 books: the aggregate quantity resting at each price of each side.
+CREATE TABLE price_levels (
+    exchange TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    quantity NUMERIC NOT NULL,
+    order_book_id UUID NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, symbol, side, price)
+);
@@ -0,0 +1,20 @@
+-- Venue tickers: best bid and offer with rolling 24 hour statistics, one row
+-- per update.
+CREATE TABLE tickers (
+    exchange TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    bid NUMERIC NOT NULL,
+    bid_qty NUMERIC NOT NULL,
+    ask NUMERIC NOT NULL,
+    ask_qty NUMERIC NOT NULL,
+    last NUMERIC NOT NULL,
+    volume NUMERIC NOT NULL,
+    vwap NUMERIC NOT NULL,
+    high NUMERIC NOT NULL,
+    low NUMERIC NOT NULL,
+    change NUMERIC NOT NULL,
+    change_pct NUMERIC NOT NULL,
+    security_id UUID NOT NULL,
+    PRIMARY KEY (exchange, symbol, timestamp)
+);
@@ -0,0 +1,18 @@
+-- Candles, one row per interval, updated in place while the interval runs.
+CREATE TABLE candles (
+    exchange TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    interval INTEGER NOT NULL,
+    interval_begin TIMESTAMPTZ NOT NULL,
+    open NUMERIC NOT NULL,
+    high NUMERIC NOT NULL,
+    low NUMERIC NOT NULL,
+    close NUMERIC NOT NULL,
+    volume NUMERIC NOT NULL,
+    vwap NUMERIC NOT NULL,
+    trades BIGINT NOT NULL,
+    closed BOOLEAN NOT NULL,
+    security_id UUID NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, symbol, interval, interval_begin)
+);
@@ -0,0 +1,19 @@
+-- Trading rules of each symbol, one row per symbol, updated in place whenever
+-- the venue changes them.
+CREATE TABLE instruments (
+    exchange TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    base TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    status TEXT NOT NULL,
+    price_precision INTEGER NOT NULL,
+    quantity_precision INTEGER NOT NULL,
+    tick_size NUMERIC NOT NULL,
+    lot_size NUMERIC NOT NULL,
+    min_order_quantity NUMERIC NOT NULL,
+    min_order_cost NUMERIC,
+    marginable BOOLEAN NOT NULL,
+    security_id UUID NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, symbol)
+);
@@ -0,0 +1,61 @@
+-- Activity on our own account, kept apart from the market data tables.
+
+-- Our orders, one row per order, updated in place until it is final.
+CREATE TABLE account_orders (
+    exchange TEXT NOT NULL,
+    order_id TEXT NOT NULL,
+    client_order_id TEXT,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    order_type TEXT NOT NULL,
+    status TEXT NOT NULL,
+    quantity NUMERIC NOT NULL,
+    limit_price NUMERIC,
+    filled_quantity NUMERIC NOT NULL,
+    average_price NUMERIC,
+    timestamp TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, order_id)
+);
+
+-- Trades of our orders. Fills repeated after a reconnect are ignored.
+CREATE TABLE account_fills (
+    exchange TEXT NOT NULL,
+    execution_id TEXT NOT NULL,
+    trade_id BIGINT,
+    order_id TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    quantity NUMERIC NOT NULL,
+    cost NUMERIC NOT NULL,
+    fee NUMERIC NOT NULL,
+    fee_asset TEXT,
+    liquidity TEXT,
+    timestamp TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, execution_id)
+);
+
+CREATE INDEX account_fills_order ON account_fills (exchange, order_id);
+
+-- The latest balance of every asset.
+CREATE TABLE account_balances (
+    exchange TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    balance NUMERIC NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, asset)
+);
+
+-- Every ledger entry that changed a balance, with the balance it left.
+CREATE TABLE account_ledger (
+    exchange TEXT NOT NULL,
+    ledger_id TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    entry_type TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    fee NUMERIC NOT NULL,
+    balance NUMERIC NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (exchange, ledger_id)
+);
@@ -0,0 +1,137 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::{api::ApiState, config::Websocket, feeds::FeedError};
+
+type AdminResult = Result<StatusCode, (StatusCode, String)>;
+
+/// Routes changing what the engine subscribes to while it runs. Requests must
+/// carry `token` as a bearer token.
+pub fn routes(token: &str) -> Router<ApiState> {
+    Router::new()
+        .route("/admin/symbols", post(add_symbol).delete(remove_symbol))
+        .route("/admin/channels", post(add_channel).delete(remove_channel))
+        .route_layer(middleware::from_fn_with_state(Arc::from(token), authorize))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SymbolRequest {
+    exchange: String,
+    symbol: String,
+}
+
+/// The channel is nested rather than flattened into the request, since serde
+/// cannot reject unknown fields of a flattened struct.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddChannelRequest {
+    exchange: String,
+    websocket: Websocket,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RemoveChannelRequest {
+    exchange: String,
+    channel: String,
+}
+
+async fn authorize(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|presented| presented == &*token);
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+async fn add_symbol(
+    State(state): State<ApiState>,
+    Json(request): Json<SymbolRequest>,
+) -> AdminResult {
+    state
+        .feeds
+        .add_symbol(&request.exchange, &request.symbol)
+        .await
+        .map_err(rejection)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_symbol(
+    State(state): State<ApiState>,
+    Json(request): Json<SymbolRequest>,
+) -> AdminResult {
+    state
+        .feeds
+        .remove_symbol(&request.exchange, &request.symbol)
+        .await
+        .map_err(rejection)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn add_channel(
+    State(state): State<ApiState>,
+    Json(request): Json<AddChannelRequest>,
+) -> AdminResult {
+    state
+        .feeds
+        .add_channel(&request.exchange, &request.websocket)
+        .await
+        .map_err(rejection)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_channel(
+    State(state): State<ApiState>,
+    Json(request): Json<RemoveChannelRequest>,
+) -> AdminResult {
+    state
+        .feeds
+        .remove_channel(&request.exchange, &request.channel)
+        .await
+        .map_err(rejection)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn rejection(error: FeedError) -> (StatusCode, String) {
+    let status = match error {
+        FeedError::UnknownExchange(_) | FeedError::NotSubscribed(..) => StatusCode::NOT_FOUND,
+        FeedError::AlreadySubscribed(..) => StatusCode::CONFLICT,
+        FeedError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_channel_requests_reject_unknown_fields() {
+        let request: AddChannelRequest = serde_json::from_str(
+            r#"{"exchange":"kraken","websocket":{"channel":"book","endpoint":"wss://ws.kraken.com/v2","depth":25}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.websocket.depth, Some(25));
+
+        for body in [
+            r#"{"exchange":"kraken","websocket":{"channel":"book","endpoint":"wss://ws.kraken.com/v2","dpeth":25}}"#,
+            r#"{"exchange":"kraken","websocket":{"channel":"book","endpoint":"wss://ws.kraken.com/v2"},"depth":25}"#,
+        ] {
+            assert!(serde_json::from_str::<AddChannelRequest>(body).is_err());
+        }
+    }
+}
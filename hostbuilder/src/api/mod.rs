@@ -0,0 +1,59 @@
+pub mod admin;
+pub mod query;
+pub mod stream;
+
+use anyhow::Result;
+use axum::Router;
+use std::sync::Arc;
+use tokio::{net::TcpListener, sync::broadcast::Receiver};
+use tracing::{info, warn};
+
+use crate::{
+    book::BookRegistry, config::ServerSettings, connections::ConnectionRegistry, fanout::FanoutHub,
+    feeds::FeedManager, metrics::Metrics, sinks::trade_history::TradeHistory,
+};
+
+/// Everything the HTTP API reads from.
+#[derive(Clone)]
+pub struct ApiState {
+    pub feeds: Arc<FeedManager>,
+    pub books: Arc<BookRegistry>,
+    pub trades: Arc<TradeHistory>,
+    pub connections: Arc<ConnectionRegistry>,
+    pub metrics: Arc<Metrics>,
+    pub fanout: Arc<FanoutHub>,
+    /// Bearer token required by the admin routes, which are only served when
+    /// one is set.
+    pub admin_token: Option<String>,
+}
+
+pub fn router(state: ApiState) -> Router {
+    let mut router = query::routes().merge(stream::routes());
+    match state
+        .admin_token
+        .as_deref()
+        .filter(|token| !token.is_empty())
+    {
+        Some(token) => router = router.merge(admin::routes(token)),
+        None => warn!("No admin token is configured, so the admin routes are not served"),
+    }
+    router.with_state(state)
+}
+
+/// Serves the API on `settings` until a shutdown signal is received.
+pub async fn serve(
+    settings: &ServerSettings,
+    state: ApiState,
+    mut shutdown_rx: Receiver<()>,
+) -> Result<()> {
+    let address = format!("{}:{}", settings.address, settings.port);
+    let listener = TcpListener::bind(&address).await?;
+    info!("Serving HTTP API on {}", address);
+
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async move {
+            let _ = shutdown_rx.recv().await;
+        })
+        .await?;
+    Ok(())
+}
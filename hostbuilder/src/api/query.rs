@@ -0,0 +1,358 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::ApiState,
+    book::{
+        level2::Level2Book,
+        level3::{Level, Level3Book, RestingOrder},
+        BookRegistry,
+    },
+    connections::ConnectionStatus,
+    events::{BookLevel, Side, Trade},
+};
+
+/// Price levels per side returned when no depth is requested.
+const DEFAULT_DEPTH: usize = 10;
+/// Trades returned when no limit is requested.
+const DEFAULT_TRADES: usize = 100;
+
+type ApiResult<T> = Result<Json<T>, (StatusCode, String)>;
+
+/// Read-only routes over the engine's in-memory state.
+pub fn routes() -> Router<ApiState> {
+    Router::new()
+        .route("/exchanges", get(exchanges))
+        .route("/books/l2", get(level2_book))
+        .route("/books/l3", get(level3_book))
+        .route("/books/bbo", get(bbo))
+        .route("/trades", get(trades))
+        .route("/connections", get(connections))
+        .route("/metrics", get(metrics))
+}
+
+#[derive(Deserialize)]
+struct SymbolQuery {
+    exchange: String,
+    symbol: String,
+    depth: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ExchangeView {
+    exchange: String,
+    symbols: Vec<String>,
+    channels: Vec<ChannelView>,
+}
+
+#[derive(Serialize)]
+struct ChannelView {
+    channel: String,
+    endpoint: String,
+}
+
+#[derive(Serialize)]
+struct BookView<T> {
+    exchange: String,
+    symbol: String,
+    synced: bool,
+    updated_at: Option<DateTime<Utc>>,
+    bids: Vec<T>,
+    asks: Vec<T>,
+}
+
+#[derive(Serialize)]
+struct BboView {
+    exchange: String,
+    symbol: String,
+    synced: bool,
+    updated_at: Option<DateTime<Utc>>,
+    bid: Option<LevelView>,
+    ask: Option<LevelView>,
+}
+
+/// A price level of either kind of book. Only a Level 3 book knows how many
+/// orders make up a level.
+#[derive(Serialize)]
+struct LevelView {
+    price: BigDecimal,
+    quantity: BigDecimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_count: Option<usize>,
+}
+
+impl From<Level> for LevelView {
+    fn from(level: Level) -> Self {
+        Self {
+            price: level.price,
+            quantity: level.quantity,
+            order_count: Some(level.order_count),
+        }
+    }
+}
+
+impl From<BookLevel> for LevelView {
+    fn from(level: BookLevel) -> Self {
+        Self {
+            price: level.price,
+            quantity: level.quantity,
+            order_count: None,
+        }
+    }
+}
+
+async fn exchanges(State(state): State<ApiState>) -> Json<Vec<ExchangeView>> {
+    let exchanges = state
+        .feeds
+        .exchanges()
+        .iter()
+        .map(|exchange| ExchangeView {
+            exchange: exchange.exchange.clone(),
+            symbols: exchange.symbols.clone(),
+            channels: exchange
+                .apis
+                .iter()
+                .flat_map(|api| api.websockets.iter())
+                .map(|websocket| ChannelView {
+                    channel: websocket.channel.clone(),
+                    endpoint: websocket.endpoint.clone(),
+                })
+                .collect(),
+        })
+        .collect();
+    Json(exchanges)
+}
+
+async fn level2_book(
+    State(state): State<ApiState>,
+    Query(query): Query<SymbolQuery>,
+) -> ApiResult<BookView<LevelView>> {
+    let depth = query.depth.unwrap_or(DEFAULT_DEPTH);
+    level2_view(&state.books, &query, depth)
+        .map(Json)
+        .ok_or_else(|| not_found(&query))
+}
+
+async fn level3_book(
+    State(state): State<ApiState>,
+    Query(query): Query<SymbolQuery>,
+) -> ApiResult<BookView<RestingOrder>> {
+    with_book(&state, &query, |book| match query.depth {
+        Some(depth) => book_view(
+            book,
+            book.top_orders(Side::Buy, depth)
+                .into_iter()
+                .cloned()
+                .collect(),
+            book.top_orders(Side::Sell, depth)
+                .into_iter()
+                .cloned()
+                .collect(),
+        ),
+        None => book_view(book, book.orders(Side::Buy), book.orders(Side::Sell)),
+    })
+}
+
+async fn bbo(
+    State(state): State<ApiState>,
+    Query(query): Query<SymbolQuery>,
+) -> ApiResult<BboView> {
+    bbo_view(&state.books, &query)
+        .map(Json)
+        .ok_or_else(|| not_found(&query))
+}
+
+async fn trades(
+    State(state): State<ApiState>,
+    Query(query): Query<SymbolQuery>,
+) -> Json<Vec<Trade>> {
+    Json(state.trades.recent(
+        &query.exchange,
+        &query.symbol,
+        query.limit.unwrap_or(DEFAULT_TRADES),
+    ))
+}
+
+async fn connections(State(state): State<ApiState>) -> Json<Vec<ConnectionStatus>> {
+    Json(state.connections.all())
+}
+
+async fn metrics(State(state): State<ApiState>) -> String {
+    state.metrics.render()
+}
+
+/// The best `depth` price levels of each side, aggregated from the symbol's
+/// Level 3 book if it has one and read from its Level 2 book otherwise.
+fn level2_view(
+    books: &BookRegistry,
+    query: &SymbolQuery,
+    depth: usize,
+) -> Option<BookView<LevelView>> {
+    books
+        .with_book(&query.exchange, &query.symbol, |book| {
+            book_view(
+                book,
+                level_views(book.depth(Side::Buy, depth)),
+                level_views(book.depth(Side::Sell, depth)),
+            )
+        })
+        .or_else(|| {
+            books.with_level_book(&query.exchange, &query.symbol, |book| {
+                level_book_view(
+                    book,
+                    level_views(book.depth(Side::Buy, depth)),
+                    level_views(book.depth(Side::Sell, depth)),
+                )
+            })
+        })
+}
+
+/// The best bid and ask, from the same book as [`level2_view`].
+fn bbo_view(books: &BookRegistry, query: &SymbolQuery) -> Option<BboView> {
+    books
+        .with_book(&query.exchange, &query.symbol, |book| BboView {
+            exchange: book.exchange().to_string(),
+            symbol: book.symbol().to_string(),
+            synced: book.is_synced(),
+            updated_at: book.updated_at(),
+            bid: book.best_bid().map(LevelView::from),
+            ask: book.best_ask().map(LevelView::from),
+        })
+        .or_else(|| {
+            books.with_level_book(&query.exchange, &query.symbol, |book| BboView {
+                exchange: book.exchange().to_string(),
+                symbol: book.symbol().to_string(),
+                synced: book.is_synced(),
+                updated_at: book.updated_at(),
+                bid: book.best_bid().map(LevelView::from),
+                ask: book.best_ask().map(LevelView::from),
+            })
+        })
+}
+
+fn with_book<T>(
+    state: &ApiState,
+    query: &SymbolQuery,
+    f: impl FnOnce(&Level3Book) -> T,
+) -> ApiResult<T> {
+    state
+        .books
+        .with_book(&query.exchange, &query.symbol, f)
+        .map(Json)
+        .ok_or_else(|| not_found(query))
+}
+
+fn not_found(query: &SymbolQuery) -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        format!("No book for {} {}", query.exchange, query.symbol),
+    )
+}
+
+fn level_views<L: Into<LevelView>>(levels: Vec<L>) -> Vec<LevelView> {
+    levels.into_iter().map(Into::into).collect()
+}
+
+fn book_view<T>(book: &Level3Book, bids: Vec<T>, asks: Vec<T>) -> BookView<T> {
+    BookView {
+        exchange: book.exchange().to_string(),
+        symbol: book.symbol().to_string(),
+        synced: book.is_synced(),
+        updated_at: book.updated_at(),
+        bids,
+        asks,
+    }
+}
+
+fn level_book_view<T>(book: &Level2Book, bids: Vec<T>, asks: Vec<T>) -> BookView<T> {
+    BookView {
+        exchange: book.exchange().to_string(),
+        symbol: book.symbol().to_string(),
+        synced: book.is_synced(),
+        updated_at: book.updated_at(),
+        bids,
+        asks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{LevelSnapshot, MarketEvent},
+        metrics::Metrics,
+    };
+    use std::sync::Arc;
+
+    fn level(price: &str, quantity: &str) -> BookLevel {
+        BookLevel {
+            price: price.parse().unwrap(),
+            quantity: quantity.parse().unwrap(),
+        }
+    }
+
+    fn query(symbol: &str) -> SymbolQuery {
+        SymbolQuery {
+            exchange: "kraken".to_string(),
+            symbol: symbol.to_string(),
+            depth: None,
+            limit: None,
+        }
+    }
+
+    /// A registry holding only a Level 2 book for ETH/USD.
+    fn books() -> BookRegistry {
+        let books = BookRegistry::new(Arc::new(Metrics::new()));
+        let snapshot = MarketEvent::LevelSnapshot(LevelSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "ETH/USD".to_string(),
+            bids: vec![level("3000.1", "2"), level("3000.2", "1")],
+            asks: vec![level("3000.5", "4"), level("3000.9", "3")],
+            depth: None,
+            sequence: None,
+            checksum: None,
+        });
+        books.apply(vec![snapshot], &|_| None, &|_| None).unwrap();
+        books
+    }
+
+    #[test]
+    fn level2_view_falls_back_to_the_level2_book() {
+        let books = books();
+
+        let view = level2_view(&books, &query("ETH/USD"), 1).unwrap();
+
+        assert!(view.synced);
+        assert_eq!(view.bids.len(), 1);
+        assert_eq!(view.bids[0].price, "3000.2".parse::<BigDecimal>().unwrap());
+        assert_eq!(view.bids[0].order_count, None);
+        assert_eq!(view.asks.len(), 1);
+        assert_eq!(view.asks[0].price, "3000.5".parse::<BigDecimal>().unwrap());
+        assert!(level2_view(&books, &query("BTC/USD"), 1).is_none());
+    }
+
+    #[test]
+    fn bbo_view_falls_back_to_the_level2_book() {
+        let books = books();
+
+        let view = bbo_view(&books, &query("ETH/USD")).unwrap();
+
+        assert_eq!(
+            view.bid.map(|level| level.quantity),
+            Some("1".parse().unwrap())
+        );
+        assert_eq!(
+            view.ask.map(|level| level.quantity),
+            Some("4".parse().unwrap())
+        );
+        assert!(bbo_view(&books, &query("BTC/USD")).is_none());
+    }
+}
@@ -0,0 +1,278 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+    routing::get,
+    Router,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc},
+    task::JoinHandle,
+};
+use tracing::warn;
+
+use crate::{
+    api::ApiState,
+    events::Side,
+    fanout::{StreamKey, BOOK_CHANNEL, DEPTH_CHANNEL, INSTRUMENT_CHANNEL},
+};
+
+/// Messages queued for a client before its subscriptions start lagging.
+const CLIENT_BUFFER: usize = 1024;
+
+/// Downstream websocket. Clients subscribe with Kraken-style requests:
+///
+/// `{"method": "subscribe", "params": {"exchange": "Kraken", "symbol": "BTC/USD", "channel": "book"}}`
+///
+/// where `channel` is `book` for the Level 3 book, `depth` for the Level 2 book,
+/// `trade`, `ticker`, `candle` or `instrument`, and receive a `snapshot`
+/// followed by `update`s numbered by `sequence`. A client that cannot keep up
+/// is sent a fresh snapshot instead of the updates it missed.
+pub fn routes() -> Router<ApiState> {
+    Router::new().route("/ws", get(upgrade))
+}
+
+#[derive(Deserialize)]
+struct Request {
+    method: String,
+    params: StreamParams,
+}
+
+#[derive(Clone, Deserialize)]
+struct StreamParams {
+    exchange: String,
+    symbol: String,
+    channel: String,
+}
+
+impl StreamParams {
+    fn key(&self) -> StreamKey {
+        (
+            self.exchange.clone(),
+            self.symbol.clone(),
+            self.channel.clone(),
+        )
+    }
+}
+
+async fn upgrade(ws: WebSocketUpgrade, State(state): State<ApiState>) -> Response {
+    ws.on_upgrade(move |socket| session(socket, state))
+}
+
+async fn session(socket: WebSocket, state: ApiState) {
+    let (mut writer, mut reader) = socket.split();
+    let (tx, mut rx) = mpsc::channel::<String>(CLIENT_BUFFER);
+
+    let write_task = tokio::spawn(async move {
+        while let Some(text) = rx.recv().await {
+            if writer.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions: HashMap<StreamKey, JoinHandle<()>> = HashMap::new();
+
+    while let Some(Ok(message)) = reader.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let reply = match serde_json::from_str::<Request>(&text) {
+            Ok(request) => handle_request(&state, &tx, &mut subscriptions, request),
+            Err(e) => json!({ "success": false, "error": format!("Invalid request: {}", e) }),
+        };
+        if tx.send(reply.to_string()).await.is_err() {
+            break;
+        }
+    }
+
+    for (_, subscription) in subscriptions.drain() {
+        subscription.abort();
+    }
+    write_task.abort();
+}
+
+fn handle_request(
+    state: &ApiState,
+    tx: &mpsc::Sender<String>,
+    subscriptions: &mut HashMap<StreamKey, JoinHandle<()>>,
+    request: Request,
+) -> Value {
+    let params = request.params;
+    let result = json!({
+        "exchange": &params.exchange,
+        "symbol": &params.symbol,
+        "channel": &params.channel,
+    });
+
+    let outcome = match request.method.as_str() {
+        "subscribe" => subscribe(state, tx, subscriptions, &params),
+        "unsubscribe" => match subscriptions.remove(&params.key()) {
+            Some(subscription) => {
+                subscription.abort();
+                Ok(())
+            }
+            None => Err("Not subscribed".to_string()),
+        },
+        method => Err(format!("Unknown method: {}", method)),
+    };
+
+    match outcome {
+        Ok(()) => json!({ "method": request.method, "success": true, "result": result }),
+        Err(e) => {
+            json!({ "method": request.method, "success": false, "result": result, "error": e })
+        }
+    }
+}
+
+fn subscribe(
+    state: &ApiState,
+    tx: &mpsc::Sender<String>,
+    subscriptions: &mut HashMap<StreamKey, JoinHandle<()>>,
+    params: &StreamParams,
+) -> Result<(), String> {
+    if subscriptions.contains_key(&params.key()) {
+        return Err("Already subscribed".to_string());
+    }
+
+    // Fail fast on an unknown channel or symbol rather than inside the
+    // forwarding task.
+    state
+        .fanout
+        .subscribe(
+            &state.feeds,
+            &params.exchange,
+            &params.symbol,
+            &params.channel,
+        )
+        .map_err(|e| e.to_string())?;
+
+    let forward = tokio::spawn(forward(state.clone(), params.clone(), tx.clone()));
+    subscriptions.insert(params.key(), forward);
+    Ok(())
+}
+
+/// Sends a snapshot, then every update after it. If the client falls behind the
+/// stream, the missed updates are dropped and it starts over from a new snapshot.
+async fn forward(state: ApiState, params: StreamParams, tx: mpsc::Sender<String>) {
+    let labels = [
+        ("exchange", params.exchange.as_str()),
+        ("symbol", params.symbol.as_str()),
+        ("channel", params.channel.as_str()),
+    ];
+
+    loop {
+        // Subscribe before taking the snapshot so no update falls in between.
+        let mut receiver = match state.fanout.subscribe(
+            &state.feeds,
+            &params.exchange,
+            &params.symbol,
+            &params.channel,
+        ) {
+            Ok(receiver) => receiver,
+            Err(_) => return,
+        };
+
+        let (snapshot, snapshot_sequence) = snapshot(&state, &params);
+        if tx.send(snapshot.to_string()).await.is_err() {
+            return;
+        }
+
+        loop {
+            match receiver.recv().await {
+                Ok(update) => {
+                    if update.sequence <= snapshot_sequence {
+                        continue;
+                    }
+                    let message = json!({
+                        "channel": &params.channel,
+                        "type": "update",
+                        "exchange": &params.exchange,
+                        "symbol": &params.symbol,
+                        "sequence": update.sequence,
+                        "data": &update.event,
+                    });
+                    if tx.send(message.to_string()).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        "Downstream client lagged {} updates on {} {} {}, resnapshotting",
+                        missed, params.exchange, params.symbol, params.channel
+                    );
+                    state
+                        .metrics
+                        .increment("fanout_slow_consumers_total", &labels);
+                    break;
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+/// The snapshot message for a subscription and the sequence number it covers.
+/// Trade, ticker and candle streams have no state, so their snapshot is empty
+/// and covers nothing. The instrument stream's snapshot carries the symbol's
+/// current instrument, if known.
+fn snapshot(state: &ApiState, params: &StreamParams) -> (Value, u64) {
+    if params.channel == INSTRUMENT_CHANNEL {
+        let instrument = state.books.instrument(&params.exchange, &params.symbol);
+        let message = json!({
+            "channel": &params.channel,
+            "type": "snapshot",
+            "exchange": &params.exchange,
+            "symbol": &params.symbol,
+            "sequence": 0,
+            "synced": instrument.is_some(),
+            "data": instrument,
+        });
+        return (message, 0);
+    }
+
+    let book = match params.channel.as_str() {
+        BOOK_CHANNEL => state
+            .books
+            .with_book(&params.exchange, &params.symbol, |book| {
+                (
+                    book.sequence(),
+                    book.is_synced(),
+                    json!(book.orders(Side::Buy)),
+                    json!(book.orders(Side::Sell)),
+                )
+            }),
+        DEPTH_CHANNEL => state
+            .books
+            .with_level_book(&params.exchange, &params.symbol, |book| {
+                (
+                    book.sequence(),
+                    book.is_synced(),
+                    json!(book.price_levels(Side::Buy)),
+                    json!(book.price_levels(Side::Sell)),
+                )
+            }),
+        _ => None,
+    };
+    let (sequence, synced, bids, asks) = book.unwrap_or_else(|| (0, false, json!([]), json!([])));
+
+    let message = json!({
+        "channel": &params.channel,
+        "type": "snapshot",
+        "exchange": &params.exchange,
+        "symbol": &params.symbol,
+        "sequence": sequence,
+        "synced": synced,
+        "data": { "bids": bids, "asks": asks },
+    });
+    (message, sequence)
+}
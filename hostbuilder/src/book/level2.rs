@@ -0,0 +1,352 @@
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::events::{BookLevel, LevelEvent, LevelSnapshot, MarketEvent, Side};
+
+/// In-memory Level 2 order book: the aggregate quantity at each price of each
+/// side, as published by venues that do not expose individual orders.
+pub struct Level2Book {
+    exchange: String,
+    symbol: String,
+    bids: BTreeMap<BigDecimal, BigDecimal>,
+    asks: BTreeMap<BigDecimal, BigDecimal>,
+    depth: Option<usize>,
+    updated_at: Option<DateTime<Utc>>,
+    synced: bool,
+    sequence: u64,
+}
+
+impl Level2Book {
+    pub fn new(exchange: &str, symbol: &str) -> Self {
+        Self {
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            depth: None,
+            updated_at: None,
+            synced: false,
+            sequence: 0,
+        }
+    }
+
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Applies a Level 2 event. Every other event is ignored. Removing a level
+    /// the book does not hold is not an error: the checksum catches a book that
+    /// drifted from the venue's.
+    pub fn apply(&mut self, event: &MarketEvent) {
+        match event {
+            MarketEvent::LevelSnapshot(snapshot) => self.apply_snapshot(snapshot),
+            MarketEvent::LevelUpdated(level) => self.update(level),
+            _ => {}
+        }
+    }
+
+    /// Replaces the whole book with `snapshot`.
+    pub fn apply_snapshot(&mut self, snapshot: &LevelSnapshot) {
+        self.clear();
+        for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            for level in levels {
+                if !level.quantity.is_zero() {
+                    self.levels_mut(side)
+                        .insert(level.price.clone(), level.quantity.clone());
+                }
+            }
+        }
+        self.depth = snapshot.depth;
+        self.updated_at = Some(Utc::now());
+        self.synced = true;
+    }
+
+    /// Drops every level, leaving an empty book that waits for a new snapshot.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.updated_at = None;
+        self.synced = false;
+    }
+
+    /// Marks the book as out of date without dropping its levels, so they can be
+    /// reconciled against the next snapshot.
+    pub fn invalidate(&mut self) {
+        self.synced = false;
+    }
+
+    /// Whether the book has been built from a snapshot since it was last cleared
+    /// or invalidated.
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// The book as a snapshot, numbered with its current sequence number.
+    pub fn snapshot(&self) -> LevelSnapshot {
+        LevelSnapshot {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            bids: self.price_levels(Side::Buy),
+            asks: self.price_levels(Side::Sell),
+            depth: self.depth,
+            sequence: Some(self.sequence),
+            checksum: None,
+        }
+    }
+
+    /// Levels in the book that `snapshot` no longer contains.
+    pub fn stale_levels(&self, snapshot: &LevelSnapshot) -> Vec<(Side, BookLevel)> {
+        let mut stale = vec![];
+        for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            let live: HashSet<&BigDecimal> = levels.iter().map(|level| &level.price).collect();
+            stale.extend(
+                self.levels(side)
+                    .iter()
+                    .filter(|(price, _)| !live.contains(price))
+                    .map(|(price, quantity)| (side, book_level(price, quantity))),
+            );
+        }
+        stale
+    }
+
+    /// Drops the levels beyond the depth the venue keeps current, which it
+    /// stops sending updates for, and returns them.
+    pub fn truncate(&mut self) -> Vec<(Side, BookLevel)> {
+        let Some(depth) = self.depth else {
+            return vec![];
+        };
+
+        let mut dropped = vec![];
+        for side in [Side::Buy, Side::Sell] {
+            let beyond: Vec<BigDecimal> = self
+                .ordered_levels(side)
+                .skip(depth)
+                .map(|(price, _)| price.clone())
+                .collect();
+            for price in beyond {
+                if let Some(quantity) = self.levels_mut(side).remove(&price) {
+                    dropped.push((side, BookLevel { price, quantity }));
+                }
+            }
+        }
+        dropped
+    }
+
+    fn update(&mut self, event: &LevelEvent) {
+        let levels = self.levels_mut(event.side);
+        if event.quantity.is_zero() {
+            levels.remove(&event.price);
+        } else {
+            levels.insert(event.price.clone(), event.quantity.clone());
+        }
+        self.updated_at = Some(event.timestamp);
+    }
+
+    fn levels(&self, side: Side) -> &BTreeMap<BigDecimal, BigDecimal> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<BigDecimal, BigDecimal> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    /// Price levels of `side` ordered from the best price outwards.
+    fn ordered_levels(
+        &self,
+        side: Side,
+    ) -> Box<dyn Iterator<Item = (&BigDecimal, &BigDecimal)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.levels(side).iter().rev()),
+            Side::Sell => Box::new(self.levels(side).iter()),
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<BookLevel> {
+        self.depth(Side::Buy, 1).pop()
+    }
+
+    pub fn best_ask(&self) -> Option<BookLevel> {
+        self.depth(Side::Sell, 1).pop()
+    }
+
+    /// The best `levels` price levels of `side`.
+    pub fn depth(&self, side: Side, levels: usize) -> Vec<BookLevel> {
+        self.ordered_levels(side)
+            .take(levels)
+            .map(|(price, quantity)| book_level(price, quantity))
+            .collect()
+    }
+
+    /// Every price level of `side`, best price first.
+    pub fn price_levels(&self, side: Side) -> Vec<BookLevel> {
+        self.depth(side, usize::MAX)
+    }
+
+    /// Quantity across both sides.
+    pub fn total_volume(&self) -> BigDecimal {
+        self.bids.values().chain(self.asks.values()).sum()
+    }
+
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at
+    }
+
+    /// Sequence number of the last event applied to the book, numbered like
+    /// `Level3Book::sequence`.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub(crate) fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+}
+
+fn book_level(price: &BigDecimal, quantity: &BigDecimal) -> BookLevel {
+    BookLevel {
+        price: price.clone(),
+        quantity: quantity.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    fn level(price: &str, quantity: &str) -> BookLevel {
+        BookLevel {
+            price: decimal(price),
+            quantity: decimal(quantity),
+        }
+    }
+
+    fn update(side: Side, price: &str, quantity: &str) -> MarketEvent {
+        MarketEvent::LevelUpdated(LevelEvent {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            side,
+            price: decimal(price),
+            quantity: decimal(quantity),
+            timestamp: Utc::now(),
+            sequence: None,
+            checksum: None,
+        })
+    }
+
+    fn synced_book(depth: Option<usize>) -> Level2Book {
+        let mut book = Level2Book::new("kraken", "BTC/USD");
+        book.apply(&MarketEvent::LevelSnapshot(LevelSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: vec![level("100.0", "1"), level("99.5", "2")],
+            asks: vec![level("100.5", "3"), level("101.0", "4")],
+            depth,
+            sequence: None,
+            checksum: None,
+        }));
+        book
+    }
+
+    #[test]
+    fn snapshot_replaces_the_book() {
+        let book = synced_book(Some(2));
+
+        assert!(book.is_synced());
+        assert_eq!(book.best_bid(), Some(level("100.0", "1")));
+        assert_eq!(book.best_ask(), Some(level("100.5", "3")));
+        assert_eq!(book.total_volume(), decimal("10"));
+    }
+
+    #[test]
+    fn updates_set_and_delete_levels() {
+        let mut book = synced_book(None);
+
+        book.apply(&update(Side::Buy, "99.5", "5"));
+        book.apply(&update(Side::Sell, "100.5", "0"));
+        book.apply(&update(Side::Sell, "100.8", "1"));
+
+        assert_eq!(
+            book.price_levels(Side::Buy),
+            vec![level("100.0", "1"), level("99.5", "5")]
+        );
+        assert_eq!(
+            book.price_levels(Side::Sell),
+            vec![level("100.8", "1"), level("101.0", "4")]
+        );
+    }
+
+    #[test]
+    fn deleting_an_unknown_level_is_ignored() {
+        let mut book = synced_book(None);
+
+        book.apply(&update(Side::Buy, "42.0", "0"));
+
+        assert_eq!(book.price_levels(Side::Buy).len(), 2);
+    }
+
+    #[test]
+    fn truncate_drops_levels_beyond_the_depth() {
+        let mut book = synced_book(Some(2));
+
+        book.apply(&update(Side::Buy, "100.2", "1"));
+        book.apply(&update(Side::Sell, "102.0", "1"));
+
+        assert_eq!(
+            book.truncate(),
+            vec![
+                (Side::Buy, level("99.5", "2")),
+                (Side::Sell, level("102.0", "1"))
+            ]
+        );
+        assert_eq!(
+            book.price_levels(Side::Buy),
+            vec![level("100.2", "1"), level("100.0", "1")]
+        );
+        assert!(book.truncate().is_empty());
+    }
+
+    #[test]
+    fn truncate_without_a_depth_keeps_every_level() {
+        let mut book = synced_book(None);
+
+        book.apply(&update(Side::Buy, "98.0", "1"));
+
+        assert!(book.truncate().is_empty());
+        assert_eq!(book.price_levels(Side::Buy).len(), 3);
+    }
+
+    #[test]
+    fn stale_levels_lists_levels_missing_from_a_snapshot() {
+        let book = synced_book(None);
+        let snapshot = LevelSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: vec![level("100.0", "1")],
+            asks: vec![level("100.5", "3"), level("101.0", "4")],
+            depth: None,
+            sequence: None,
+            checksum: None,
+        };
+
+        assert_eq!(
+            book.stale_levels(&snapshot),
+            vec![(Side::Buy, level("99.5", "2"))]
+        );
+    }
+}
@@ -0,0 +1,634 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::events::{BookOrder, BookSnapshot, MarketEvent, OrderEvent, Side};
+
+/// An order resting in the book.
+#[derive(Clone, Debug, Serialize)]
+pub struct RestingOrder {
+    pub order_id: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Aggregated view of one price level.
+#[derive(Clone, Debug, Serialize)]
+pub struct Level {
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub order_count: usize,
+}
+
+/// Where an order sits in its price level's time-priority queue.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueuePosition {
+    /// Number of orders ahead of this one at the same price.
+    pub orders_ahead: usize,
+    /// Quantity resting ahead of this one at the same price.
+    pub quantity_ahead: BigDecimal,
+}
+
+#[derive(Default)]
+struct PriceLevel {
+    orders: VecDeque<RestingOrder>,
+    quantity: BigDecimal,
+}
+
+impl PriceLevel {
+    fn push(&mut self, order: RestingOrder) {
+        self.quantity += &order.quantity;
+        self.orders.push_back(order);
+    }
+
+    fn remove(&mut self, order_id: &str) -> Option<RestingOrder> {
+        let index = self.orders.iter().position(|o| o.order_id == order_id)?;
+        let order = self.orders.remove(index)?;
+        self.quantity -= &order.quantity;
+        Some(order)
+    }
+
+    fn level(&self, price: &BigDecimal) -> Level {
+        Level {
+            price: price.clone(),
+            quantity: self.quantity.clone(),
+            order_count: self.orders.len(),
+        }
+    }
+}
+
+/// In-memory Level 3 order book: an order-id index plus per-side price levels,
+/// each holding its orders in time priority.
+pub struct Level3Book {
+    exchange: String,
+    symbol: String,
+    bids: BTreeMap<BigDecimal, PriceLevel>,
+    asks: BTreeMap<BigDecimal, PriceLevel>,
+    orders: HashMap<String, (Side, BigDecimal)>,
+    total_volume: BigDecimal,
+    updated_at: Option<DateTime<Utc>>,
+    synced: bool,
+    sequence: u64,
+}
+
+impl Level3Book {
+    pub fn new(exchange: &str, symbol: &str) -> Self {
+        Self {
+            exchange: exchange.to_string(),
+            symbol: symbol.to_string(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            orders: HashMap::new(),
+            total_volume: BigDecimal::zero(),
+            updated_at: None,
+            synced: false,
+            sequence: 0,
+        }
+    }
+
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Applies a book event. Level 2, trade and connection events are ignored.
+    pub fn apply(&mut self, event: &MarketEvent) -> Result<()> {
+        match event {
+            MarketEvent::BookSnapshot(snapshot) => {
+                self.apply_snapshot(snapshot);
+                Ok(())
+            }
+            MarketEvent::OrderAdded(order) => self.add(order),
+            MarketEvent::OrderModified(order) => self.modify(order),
+            MarketEvent::OrderDeleted(order) => self.delete(order),
+            MarketEvent::LevelUpdated(_)
+            | MarketEvent::LevelSnapshot(_)
+            | MarketEvent::Trade(_)
+            | MarketEvent::Ticker(_)
+            | MarketEvent::Candle(_)
+            | MarketEvent::CandleClosed(_)
+            | MarketEvent::Instrument(_)
+            | MarketEvent::Account(_)
+            | MarketEvent::Heartbeat(_)
+            | MarketEvent::Status(_) => Ok(()),
+        }
+    }
+
+    /// Replaces the whole book with `snapshot`.
+    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) {
+        self.clear();
+        for (side, orders) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            for order in orders {
+                self.insert(resting_order(side, order));
+            }
+        }
+        self.synced = true;
+    }
+
+    /// Drops every order, leaving an empty book that waits for a new snapshot.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.orders.clear();
+        self.total_volume = BigDecimal::zero();
+        self.updated_at = None;
+        self.synced = false;
+    }
+
+    /// Marks the book as out of date without dropping its orders, so they can be
+    /// reconciled against the next snapshot.
+    pub fn invalidate(&mut self) {
+        self.synced = false;
+    }
+
+    /// Whether the book has been built from a snapshot since it was last cleared
+    /// or invalidated. Incremental events are only meaningful once it has.
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// The book as a snapshot, numbered with its current sequence number.
+    pub fn snapshot(&self) -> BookSnapshot {
+        let orders = |side| {
+            self.orders(side)
+                .into_iter()
+                .map(|order| BookOrder {
+                    order_id: order.order_id,
+                    price: order.price,
+                    quantity: order.quantity,
+                    timestamp: order.timestamp,
+                })
+                .collect()
+        };
+
+        BookSnapshot {
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            bids: orders(Side::Buy),
+            asks: orders(Side::Sell),
+            sequence: Some(self.sequence),
+            checksum: None,
+        }
+    }
+
+    /// Orders resting in the book that `snapshot` no longer contains.
+    pub fn stale_orders(&self, snapshot: &BookSnapshot) -> Vec<RestingOrder> {
+        let live: HashSet<&str> = snapshot
+            .bids
+            .iter()
+            .chain(snapshot.asks.iter())
+            .map(|order| order.order_id.as_str())
+            .collect();
+
+        [Side::Buy, Side::Sell]
+            .into_iter()
+            .flat_map(|side| self.orders(side))
+            .filter(|order| !live.contains(order.order_id.as_str()))
+            .collect()
+    }
+
+    fn add(&mut self, event: &OrderEvent) -> Result<()> {
+        if self.orders.contains_key(&event.order_id) {
+            return Err(anyhow!(
+                "Order {} already exists in {} book",
+                event.order_id,
+                self.symbol
+            ));
+        }
+        self.insert(RestingOrder {
+            order_id: event.order_id.clone(),
+            side: event.side,
+            price: event.price.clone(),
+            quantity: event.quantity.clone(),
+            timestamp: event.timestamp,
+        });
+        Ok(())
+    }
+
+    /// A modify that only reduces quantity at the same price keeps the order's
+    /// queue position; anything else sends it to the back of its new level.
+    fn modify(&mut self, event: &OrderEvent) -> Result<()> {
+        let (side, price) =
+            self.orders.get(&event.order_id).cloned().ok_or_else(|| {
+                anyhow!("Order {} not found in {} book", event.order_id, self.symbol)
+            })?;
+
+        if side == event.side && price == event.price {
+            let level = self
+                .levels_mut(side)
+                .get_mut(&price)
+                .ok_or_else(|| anyhow!("Price level {} missing", price))?;
+            if let Some(order) = level
+                .orders
+                .iter_mut()
+                .find(|o| o.order_id == event.order_id)
+            {
+                if event.quantity <= order.quantity {
+                    let delta = &order.quantity - &event.quantity;
+                    order.quantity = event.quantity.clone();
+                    order.timestamp = event.timestamp;
+                    level.quantity -= &delta;
+                    self.total_volume -= &delta;
+                    self.updated_at = Some(event.timestamp);
+                    return Ok(());
+                }
+            }
+        }
+
+        self.remove(&event.order_id);
+        self.insert(RestingOrder {
+            order_id: event.order_id.clone(),
+            side: event.side,
+            price: event.price.clone(),
+            quantity: event.quantity.clone(),
+            timestamp: event.timestamp,
+        });
+        Ok(())
+    }
+
+    fn delete(&mut self, event: &OrderEvent) -> Result<()> {
+        match self.remove(&event.order_id) {
+            Some(_) => {
+                self.updated_at = Some(event.timestamp);
+                Ok(())
+            }
+            None => Err(anyhow!(
+                "Order {} not found in {} book",
+                event.order_id,
+                self.symbol
+            )),
+        }
+    }
+
+    fn insert(&mut self, order: RestingOrder) {
+        self.total_volume += &order.quantity;
+        self.updated_at = Some(order.timestamp);
+        self.orders
+            .insert(order.order_id.clone(), (order.side, order.price.clone()));
+        self.levels_mut(order.side)
+            .entry(order.price.clone())
+            .or_default()
+            .push(order);
+    }
+
+    fn remove(&mut self, order_id: &str) -> Option<RestingOrder> {
+        let (side, price) = self.orders.remove(order_id)?;
+        let levels = self.levels_mut(side);
+        let level = levels.get_mut(&price)?;
+        let order = level.remove(order_id)?;
+        if level.orders.is_empty() {
+            levels.remove(&price);
+        }
+        self.total_volume -= &order.quantity;
+        Some(order)
+    }
+
+    fn levels(&self, side: Side) -> &BTreeMap<BigDecimal, PriceLevel> {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<BigDecimal, PriceLevel> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    /// Price levels of `side` ordered from the best price outwards.
+    fn ordered_levels(
+        &self,
+        side: Side,
+    ) -> Box<dyn Iterator<Item = (&BigDecimal, &PriceLevel)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.levels(side).iter().rev()),
+            Side::Sell => Box::new(self.levels(side).iter()),
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.depth(Side::Buy, 1).pop()
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.depth(Side::Sell, 1).pop()
+    }
+
+    /// The best `levels` price levels of `side`, aggregated.
+    pub fn depth(&self, side: Side, levels: usize) -> Vec<Level> {
+        self.ordered_levels(side)
+            .take(levels)
+            .map(|(price, level)| level.level(price))
+            .collect()
+    }
+
+    /// Every resting order of `side`, best price first and in time priority
+    /// within a level.
+    pub fn orders(&self, side: Side) -> Vec<RestingOrder> {
+        self.ordered_levels(side)
+            .flat_map(|(_, level)| level.orders.iter().cloned())
+            .collect()
+    }
+
+    /// Orders in the best `levels` price levels of `side`, best price first and in
+    /// time priority within a level.
+    pub fn top_orders(&self, side: Side, levels: usize) -> Vec<&RestingOrder> {
+        self.ordered_levels(side)
+            .take(levels)
+            .flat_map(|(_, level)| level.orders.iter())
+            .collect()
+    }
+
+    pub fn order(&self, order_id: &str) -> Option<&RestingOrder> {
+        let (side, price) = self.orders.get(order_id)?;
+        self.levels(*side)
+            .get(price)?
+            .orders
+            .iter()
+            .find(|o| o.order_id == order_id)
+    }
+
+    pub fn queue_position(&self, order_id: &str) -> Option<QueuePosition> {
+        let (side, price) = self.orders.get(order_id)?;
+        let level = self.levels(*side).get(price)?;
+        let orders_ahead = level.orders.iter().position(|o| o.order_id == order_id)?;
+        let quantity_ahead = level
+            .orders
+            .iter()
+            .take(orders_ahead)
+            .map(|o| &o.quantity)
+            .sum();
+        Some(QueuePosition {
+            orders_ahead,
+            quantity_ahead,
+        })
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// Open quantity across both sides.
+    pub fn total_volume(&self) -> &BigDecimal {
+        &self.total_volume
+    }
+
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at
+    }
+
+    /// Sequence number of the last event applied to the book. Numbers increase by
+    /// one per event for the lifetime of the book, across snapshots.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub(crate) fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+}
+
+fn resting_order(side: Side, order: &BookOrder) -> RestingOrder {
+    RestingOrder {
+        order_id: order.order_id.clone(),
+        side,
+        price: order.price.clone(),
+        quantity: order.quantity.clone(),
+        timestamp: order.timestamp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    fn order(order_id: &str, side: Side, price: &str, quantity: &str) -> OrderEvent {
+        OrderEvent {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            side,
+            order_id: order_id.to_string(),
+            price: decimal(price),
+            quantity: decimal(quantity),
+            timestamp: Utc::now(),
+            sequence: None,
+            checksum: None,
+        }
+    }
+
+    fn book_order(order_id: &str, price: &str, quantity: &str) -> BookOrder {
+        BookOrder {
+            order_id: order_id.to_string(),
+            price: decimal(price),
+            quantity: decimal(quantity),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn snapshot(bids: Vec<BookOrder>, asks: Vec<BookOrder>) -> BookSnapshot {
+        BookSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids,
+            asks,
+            sequence: None,
+            checksum: None,
+        }
+    }
+
+    /// A book with two bids queued at 100 and one ask at 101.
+    fn book() -> Level3Book {
+        let mut book = Level3Book::new("kraken", "BTC/USD");
+        book.apply_snapshot(&snapshot(
+            vec![book_order("b1", "100", "1"), book_order("b2", "100", "2")],
+            vec![book_order("a1", "101", "3")],
+        ));
+        book
+    }
+
+    fn order_ids(book: &Level3Book, side: Side) -> Vec<String> {
+        book.orders(side)
+            .into_iter()
+            .map(|order| order.order_id)
+            .collect()
+    }
+
+    #[test]
+    fn adds_orders_to_the_back_of_their_level() {
+        let mut book = book();
+
+        book.apply(&MarketEvent::OrderAdded(order("b3", Side::Buy, "100", "4")))
+            .unwrap();
+        book.apply(&MarketEvent::OrderAdded(order(
+            "b4",
+            Side::Buy,
+            "100.5",
+            "1",
+        )))
+        .unwrap();
+
+        assert_eq!(order_ids(&book, Side::Buy), ["b4", "b1", "b2", "b3"]);
+        let best = book.best_bid().unwrap();
+        assert_eq!(best.price, decimal("100.5"));
+        assert_eq!(best.order_count, 1);
+        assert_eq!(book.depth(Side::Buy, 2)[1].quantity, decimal("7"));
+        assert_eq!(book.order_count(), 5);
+    }
+
+    #[test]
+    fn rejects_an_order_that_already_exists() {
+        let mut book = book();
+
+        let result = book.apply(&MarketEvent::OrderAdded(order("b1", Side::Buy, "99", "1")));
+
+        assert!(result.is_err());
+        assert_eq!(book.order("b1").unwrap().price, decimal("100"));
+    }
+
+    #[test]
+    fn reducing_an_order_keeps_its_queue_position() {
+        let mut book = book();
+
+        book.apply(&MarketEvent::OrderModified(order(
+            "b1",
+            Side::Buy,
+            "100",
+            "0.5",
+        )))
+        .unwrap();
+
+        assert_eq!(order_ids(&book, Side::Buy), ["b1", "b2"]);
+        let position = book.queue_position("b2").unwrap();
+        assert_eq!(position.orders_ahead, 1);
+        assert_eq!(position.quantity_ahead, decimal("0.5"));
+        assert_eq!(book.best_bid().unwrap().quantity, decimal("2.5"));
+    }
+
+    #[test]
+    fn increasing_an_order_sends_it_to_the_back() {
+        let mut book = book();
+
+        book.apply(&MarketEvent::OrderModified(order(
+            "b1",
+            Side::Buy,
+            "100",
+            "5",
+        )))
+        .unwrap();
+
+        assert_eq!(order_ids(&book, Side::Buy), ["b2", "b1"]);
+        assert_eq!(book.queue_position("b1").unwrap().orders_ahead, 1);
+        assert_eq!(book.best_bid().unwrap().quantity, decimal("7"));
+    }
+
+    #[test]
+    fn repricing_an_order_moves_it_to_its_new_level() {
+        let mut book = book();
+
+        book.apply(&MarketEvent::OrderModified(order(
+            "b1",
+            Side::Buy,
+            "99",
+            "1",
+        )))
+        .unwrap();
+
+        assert_eq!(order_ids(&book, Side::Buy), ["b2", "b1"]);
+        assert_eq!(book.depth(Side::Buy, 2).len(), 2);
+        assert_eq!(book.order("b1").unwrap().price, decimal("99"));
+    }
+
+    #[test]
+    fn deletes_orders_and_empty_levels() {
+        let mut book = book();
+
+        book.apply(&MarketEvent::OrderDeleted(order(
+            "a1",
+            Side::Sell,
+            "101",
+            "3",
+        )))
+        .unwrap();
+
+        assert!(book.best_ask().is_none());
+        assert!(book.order("a1").is_none());
+        assert!(book
+            .apply(&MarketEvent::OrderDeleted(order(
+                "a1",
+                Side::Sell,
+                "101",
+                "3"
+            )))
+            .is_err());
+    }
+
+    #[test]
+    fn total_volume_follows_every_change() {
+        let mut book = book();
+        assert_eq!(book.total_volume(), &decimal("6"));
+
+        book.apply(&MarketEvent::OrderAdded(order(
+            "a2",
+            Side::Sell,
+            "102",
+            "1.5",
+        )))
+        .unwrap();
+        book.apply(&MarketEvent::OrderModified(order(
+            "b2",
+            Side::Buy,
+            "100",
+            "0.5",
+        )))
+        .unwrap();
+        book.apply(&MarketEvent::OrderModified(order(
+            "b1",
+            Side::Buy,
+            "99",
+            "4",
+        )))
+        .unwrap();
+        book.apply(&MarketEvent::OrderDeleted(order(
+            "a1",
+            Side::Sell,
+            "101",
+            "3",
+        )))
+        .unwrap();
+
+        assert_eq!(book.total_volume(), &decimal("6"));
+        book.clear();
+        assert!(book.total_volume().is_zero());
+    }
+
+    #[test]
+    fn stale_orders_lists_orders_missing_from_a_snapshot() {
+        let book = book();
+        let next = snapshot(
+            vec![book_order("b2", "100", "2")],
+            vec![book_order("a1", "101", "3"), book_order("a2", "102", "1")],
+        );
+
+        let stale: Vec<String> = book
+            .stale_orders(&next)
+            .into_iter()
+            .map(|order| order.order_id)
+            .collect();
+
+        assert_eq!(stale, ["b1"]);
+    }
+}
@@ -0,0 +1,617 @@
+pub mod level2;
+pub mod level3;
+
+use anyhow::{anyhow, Result};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, RwLock},
+};
+use tracing::{debug, error, warn};
+
+use crate::{
+    events::{BookLevel, Instrument, LevelEvent, MarketEvent, OrderEvent, Side, Ticker},
+    metrics::Metrics,
+};
+use level2::Level2Book;
+use level3::{Level3Book, RestingOrder};
+
+/// Number of book incidents kept for inspection.
+const MAX_INCIDENTS: usize = 100;
+
+/// The kinds of book the registry keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookKind {
+    /// Individual orders, from a Level 3 feed.
+    Level3,
+    /// Aggregate quantity per price, from a Level 2 feed.
+    Level2,
+}
+
+/// A book that was found to be inconsistent with the feed and invalidated.
+#[derive(Clone, Debug, Serialize)]
+pub struct BookIncident {
+    pub exchange: String,
+    pub symbol: String,
+    pub reason: String,
+    pub expected_checksum: Option<u32>,
+    pub computed_checksum: Option<u32>,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Result of applying a batch of events.
+#[derive(Default)]
+pub struct ApplyOutcome {
+    /// Events that were applied, plus those that do not touch a book, in order.
+    /// Events for books waiting on a snapshot are dropped.
+    pub events: Vec<MarketEvent>,
+    /// Symbols whose book was invalidated and must be rebuilt from a fresh snapshot.
+    pub resync: Vec<String>,
+}
+
+/// In-process Level 3 and Level 2 books for every (exchange, symbol) the engine
+/// tracks. This is the source of truth; Redis and Postgres are derived from it.
+/// A symbol fed by both kinds of channel has one book of each kind.
+pub struct BookRegistry {
+    books: RwLock<HashMap<(String, String), Level3Book>>,
+    level_books: RwLock<HashMap<(String, String), Level2Book>>,
+    /// Reference data of every symbol the venue has described.
+    instruments: RwLock<HashMap<(String, String), Instrument>>,
+    incidents: Mutex<VecDeque<BookIncident>>,
+    metrics: Arc<Metrics>,
+}
+
+impl BookRegistry {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            books: RwLock::new(HashMap::new()),
+            level_books: RwLock::new(HashMap::new()),
+            instruments: RwLock::new(HashMap::new()),
+            incidents: Mutex::new(VecDeque::new()),
+            metrics,
+        }
+    }
+
+    /// Applies the book events in `events` to their books, creating books on first
+    /// use. After any event carrying a checksum, the book is checked against
+    /// `checksum`; a mismatch, or an event that cannot be applied, invalidates the
+    /// book until a new snapshot arrives. A snapshot is preceded in the outcome by
+    /// deletes for every order it no longer contains, so sinks that mirror the
+    /// book see a clean resync boundary. Every book event in the outcome carries
+    /// the book's sequence number after it was applied.
+    ///
+    /// Level 2 events are applied the same way to Level 2 books, checked against
+    /// `level_checksum`. A Level 2 book is cut back to its depth once per venue
+    /// message, at the event carrying the message's checksum, and the levels it
+    /// drops are followed in the outcome by updates removing them. Tickers are
+    /// passed through after being cross-checked against the books.
+    ///
+    /// Once a symbol's instrument has been seen, the prices and quantities of
+    /// its events are rounded to the instrument's precision before anything
+    /// else, so books and every sink hold them as the venue quotes them.
+    pub fn apply(
+        &self,
+        events: Vec<MarketEvent>,
+        checksum: &(dyn Fn(&Level3Book) -> Option<u32> + Send + Sync),
+        level_checksum: &(dyn Fn(&Level2Book) -> Option<u32> + Send + Sync),
+    ) -> Result<ApplyOutcome> {
+        let mut books = self
+            .books
+            .write()
+            .map_err(|_| anyhow!("Book registry lock poisoned"))?;
+        let mut level_books = self
+            .level_books
+            .write()
+            .map_err(|_| anyhow!("Book registry lock poisoned"))?;
+        let mut instruments = self
+            .instruments
+            .write()
+            .map_err(|_| anyhow!("Book registry lock poisoned"))?;
+        let mut outcome = ApplyOutcome::default();
+
+        for mut event in events {
+            if let MarketEvent::Instrument(instrument) = &event {
+                instruments.insert(
+                    (instrument.exchange.clone(), instrument.symbol.clone()),
+                    instrument.as_ref().clone(),
+                );
+            } else if let Some(symbol) = event.symbol() {
+                let key = (event.exchange().to_string(), symbol.to_string());
+                if let Some(instrument) = instruments.get(&key) {
+                    event.round(instrument.price_precision, instrument.quantity_precision);
+                }
+            }
+
+            let symbol = match event.symbol() {
+                Some(symbol) if event.is_book_event() => symbol.to_string(),
+                Some(_) if event.is_level_event() => {
+                    self.apply_level(&mut level_books, &mut outcome, event, level_checksum);
+                    continue;
+                }
+                _ => {
+                    if let MarketEvent::Ticker(ticker) = &event {
+                        self.check_ticker(&books, &level_books, ticker);
+                    }
+                    outcome.events.push(event);
+                    continue;
+                }
+            };
+            let exchange = event.exchange().to_string();
+
+            let book = books
+                .entry((exchange.clone(), symbol.clone()))
+                .or_insert_with(|| Level3Book::new(&exchange, &symbol));
+
+            match &event {
+                MarketEvent::BookSnapshot(snapshot) => {
+                    for order in book.stale_orders(snapshot) {
+                        let mut stale = stale_order_event(&exchange, &symbol, order);
+                        set_sequence(&mut stale, book.next_sequence());
+                        outcome.events.push(stale);
+                    }
+                }
+                _ if !book.is_synced() => continue,
+                _ => {}
+            }
+
+            if let Err(e) = book.apply(&event) {
+                book.invalidate();
+                self.record(&mut outcome, &exchange, &symbol, e.to_string(), None, None);
+                continue;
+            }
+
+            if let Some(expected) = expected_checksum(&event) {
+                if let Some(computed) = checksum(book) {
+                    if computed != expected {
+                        book.invalidate();
+                        self.metrics.increment(
+                            "book_checksum_mismatches_total",
+                            &[("exchange", &exchange), ("symbol", &symbol)],
+                        );
+                        self.record(
+                            &mut outcome,
+                            &exchange,
+                            &symbol,
+                            "checksum mismatch".to_string(),
+                            Some(expected),
+                            Some(computed),
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            set_sequence(&mut event, book.next_sequence());
+            outcome.events.push(event);
+        }
+
+        Ok(outcome)
+    }
+
+    fn apply_level(
+        &self,
+        books: &mut HashMap<(String, String), Level2Book>,
+        outcome: &mut ApplyOutcome,
+        mut event: MarketEvent,
+        checksum: &(dyn Fn(&Level2Book) -> Option<u32> + Send + Sync),
+    ) {
+        let exchange = event.exchange().to_string();
+        let symbol = event.symbol().unwrap_or_default().to_string();
+        let book = books
+            .entry((exchange.clone(), symbol.clone()))
+            .or_insert_with(|| Level2Book::new(&exchange, &symbol));
+
+        match &event {
+            MarketEvent::LevelSnapshot(snapshot) => {
+                for (side, level) in book.stale_levels(snapshot) {
+                    let mut stale = removed_level_event(&exchange, &symbol, side, level);
+                    set_sequence(&mut stale, book.next_sequence());
+                    outcome.events.push(stale);
+                }
+            }
+            _ if !book.is_synced() => return,
+            _ => {}
+        }
+
+        // A message may insert a level past the subscribed depth and delete
+        // another further up, so the book is only cut back to its depth once the
+        // message is complete: at the event carrying the message's checksum.
+        let expected = expected_checksum(&event);
+        book.apply(&event);
+        let dropped = match &event {
+            MarketEvent::LevelSnapshot(_) => book.truncate(),
+            _ if expected.is_some() => book.truncate(),
+            _ => vec![],
+        };
+
+        if let Some(expected) = expected {
+            if let Some(computed) = checksum(book) {
+                if computed != expected {
+                    book.invalidate();
+                    self.metrics.increment(
+                        "book_checksum_mismatches_total",
+                        &[("exchange", &exchange), ("symbol", &symbol)],
+                    );
+                    self.record(
+                        outcome,
+                        &exchange,
+                        &symbol,
+                        "checksum mismatch".to_string(),
+                        Some(expected),
+                        Some(computed),
+                    );
+                    return;
+                }
+            }
+        }
+
+        set_sequence(&mut event, book.next_sequence());
+        outcome.events.push(event);
+        for (side, level) in dropped {
+            let mut removed = removed_level_event(&exchange, &symbol, side, level);
+            set_sequence(&mut removed, book.next_sequence());
+            outcome.events.push(removed);
+        }
+    }
+
+    /// Cross-checks the best bid and offer the venue reports in `ticker` against
+    /// the symbol's Level 3 book, or its Level 2 book when it has no synced Level
+    /// 3 book. The feeds arrive on separate connections, so brief differences are
+    /// expected; a mismatch rate that stays high points at a drifting book.
+    fn check_ticker(
+        &self,
+        books: &HashMap<(String, String), Level3Book>,
+        level_books: &HashMap<(String, String), Level2Book>,
+        ticker: &Ticker,
+    ) {
+        let key = (ticker.exchange.clone(), ticker.symbol.clone());
+        let (kind, bid, ask) = match (books.get(&key), level_books.get(&key)) {
+            (Some(book), _) if book.is_synced() => (
+                "level3",
+                book.best_bid().map(|level| level.price),
+                book.best_ask().map(|level| level.price),
+            ),
+            (_, Some(book)) if book.is_synced() => (
+                "level2",
+                book.best_bid().map(|level| level.price),
+                book.best_ask().map(|level| level.price),
+            ),
+            _ => return,
+        };
+
+        let labels = [
+            ("exchange", ticker.exchange.as_str()),
+            ("symbol", ticker.symbol.as_str()),
+            ("book", kind),
+        ];
+        self.metrics.increment("ticker_bbo_checks_total", &labels);
+        if bid.as_ref() != Some(&ticker.bid) || ask.as_ref() != Some(&ticker.ask) {
+            self.metrics
+                .increment("ticker_bbo_mismatches_total", &labels);
+            debug!(
+                "{} {} ticker BBO {}/{} differs from the {} book's {:?}/{:?}",
+                ticker.exchange, ticker.symbol, ticker.bid, ticker.ask, kind, bid, ask
+            );
+        }
+    }
+
+    fn record(
+        &self,
+        outcome: &mut ApplyOutcome,
+        exchange: &str,
+        symbol: &str,
+        reason: String,
+        expected_checksum: Option<u32>,
+        computed_checksum: Option<u32>,
+    ) {
+        error!(
+            "Invalidating {} {} book: {} (expected checksum {:?}, computed {:?})",
+            exchange, symbol, reason, expected_checksum, computed_checksum
+        );
+        self.metrics.increment(
+            "book_resyncs_total",
+            &[("exchange", exchange), ("symbol", symbol)],
+        );
+
+        if !outcome.resync.iter().any(|s| s == symbol) {
+            outcome.resync.push(symbol.to_string());
+        }
+
+        match self.incidents.lock() {
+            Ok(mut incidents) => {
+                if incidents.len() == MAX_INCIDENTS {
+                    incidents.pop_front();
+                }
+                incidents.push_back(BookIncident {
+                    exchange: exchange.to_string(),
+                    symbol: symbol.to_string(),
+                    reason,
+                    expected_checksum,
+                    computed_checksum,
+                    detected_at: Utc::now(),
+                });
+            }
+            Err(_) => warn!("Book incident log lock poisoned"),
+        }
+    }
+
+    /// Runs `f` against the book for `exchange`/`symbol`, if one exists.
+    pub fn with_book<R>(
+        &self,
+        exchange: &str,
+        symbol: &str,
+        f: impl FnOnce(&Level3Book) -> R,
+    ) -> Option<R> {
+        let books = self.books.read().ok()?;
+        books
+            .get(&(exchange.to_string(), symbol.to_string()))
+            .map(f)
+    }
+
+    /// Runs `f` against the Level 2 book for `exchange`/`symbol`, if one exists.
+    pub fn with_level_book<R>(
+        &self,
+        exchange: &str,
+        symbol: &str,
+        f: impl FnOnce(&Level2Book) -> R,
+    ) -> Option<R> {
+        let books = self.level_books.read().ok()?;
+        books
+            .get(&(exchange.to_string(), symbol.to_string()))
+            .map(f)
+    }
+
+    /// Events that bring a sink which missed some of the book events of
+    /// `exchange`/`symbol` back in line with the books: deletes for the
+    /// `touched` orders no longer resting, then a snapshot of each of the
+    /// symbol's books. `None` while a book waits on a snapshot from the venue,
+    /// since there is nothing trustworthy to resync from until it arrives.
+    pub fn resync_events(
+        &self,
+        exchange: &str,
+        symbol: &str,
+        touched: &HashMap<String, RestingOrder>,
+    ) -> Option<Vec<MarketEvent>> {
+        let key = (exchange.to_string(), symbol.to_string());
+        let mut events = vec![];
+
+        if let Some(book) = self.books.read().ok()?.get(&key) {
+            if !book.is_synced() {
+                return None;
+            }
+            events.extend(
+                touched
+                    .values()
+                    .filter(|order| book.order(&order.order_id).is_none())
+                    .map(|order| stale_order_event(exchange, symbol, order.clone())),
+            );
+            events.push(MarketEvent::BookSnapshot(book.snapshot()));
+        }
+
+        if let Some(book) = self.level_books.read().ok()?.get(&key) {
+            if !book.is_synced() {
+                return None;
+            }
+            events.push(MarketEvent::LevelSnapshot(book.snapshot()));
+        }
+
+        Some(events)
+    }
+
+    /// The reference data of `exchange`/`symbol`, if the venue has sent it.
+    pub fn instrument(&self, exchange: &str, symbol: &str) -> Option<Instrument> {
+        self.instruments
+            .read()
+            .ok()?
+            .get(&(exchange.to_string(), symbol.to_string()))
+            .cloned()
+    }
+
+    /// Invalidates the `kind` books of `symbols` on `exchange`, e.g. after the
+    /// feed was lost. Their contents are kept until the next snapshot reconciles
+    /// them.
+    pub fn invalidate(&self, exchange: &str, symbols: &[String], kind: BookKind) {
+        match kind {
+            BookKind::Level3 => {
+                if let Ok(mut books) = self.books.write() {
+                    for symbol in symbols {
+                        if let Some(book) = books.get_mut(&(exchange.to_string(), symbol.clone())) {
+                            book.invalidate();
+                        }
+                    }
+                }
+            }
+            BookKind::Level2 => {
+                if let Ok(mut books) = self.level_books.write() {
+                    for symbol in symbols {
+                        if let Some(book) = books.get_mut(&(exchange.to_string(), symbol.clone())) {
+                            book.invalidate();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Discards the books and reference data for `exchange`/`symbol`.
+    pub fn remove(&self, exchange: &str, symbol: &str) {
+        let key = (exchange.to_string(), symbol.to_string());
+        if let Ok(mut books) = self.books.write() {
+            books.remove(&key);
+        }
+        if let Ok(mut books) = self.level_books.write() {
+            books.remove(&key);
+        }
+        if let Ok(mut instruments) = self.instruments.write() {
+            instruments.remove(&key);
+        }
+    }
+
+    /// (exchange, symbol) pairs with a book.
+    pub fn keys(&self) -> Vec<(String, String)> {
+        self.books
+            .read()
+            .map(|books| books.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The most recent book incidents, oldest first.
+    pub fn incidents(&self) -> Vec<BookIncident> {
+        self.incidents
+            .lock()
+            .map(|incidents| incidents.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+fn stale_order_event(exchange: &str, symbol: &str, order: RestingOrder) -> MarketEvent {
+    MarketEvent::OrderDeleted(OrderEvent {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        side: order.side,
+        order_id: order.order_id,
+        price: order.price,
+        quantity: order.quantity,
+        timestamp: Utc::now(),
+        sequence: None,
+        checksum: None,
+    })
+}
+
+fn removed_level_event(exchange: &str, symbol: &str, side: Side, level: BookLevel) -> MarketEvent {
+    MarketEvent::LevelUpdated(LevelEvent {
+        exchange: exchange.to_string(),
+        symbol: symbol.to_string(),
+        side,
+        price: level.price,
+        quantity: BigDecimal::zero(),
+        timestamp: Utc::now(),
+        sequence: None,
+        checksum: None,
+    })
+}
+
+fn set_sequence(event: &mut MarketEvent, sequence: u64) {
+    match event {
+        MarketEvent::OrderAdded(order)
+        | MarketEvent::OrderModified(order)
+        | MarketEvent::OrderDeleted(order) => order.sequence = Some(sequence),
+        MarketEvent::BookSnapshot(snapshot) => snapshot.sequence = Some(sequence),
+        MarketEvent::LevelUpdated(level) => level.sequence = Some(sequence),
+        MarketEvent::LevelSnapshot(snapshot) => snapshot.sequence = Some(sequence),
+        _ => {}
+    }
+}
+
+fn expected_checksum(event: &MarketEvent) -> Option<u32> {
+    match event {
+        MarketEvent::OrderAdded(order)
+        | MarketEvent::OrderModified(order)
+        | MarketEvent::OrderDeleted(order) => order.checksum,
+        MarketEvent::BookSnapshot(snapshot) => snapshot.checksum,
+        MarketEvent::LevelUpdated(level) => level.checksum,
+        MarketEvent::LevelSnapshot(snapshot) => snapshot.checksum,
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::LevelSnapshot;
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    fn level(price: &str, quantity: &str) -> BookLevel {
+        BookLevel {
+            price: decimal(price),
+            quantity: decimal(quantity),
+        }
+    }
+
+    fn snapshot() -> MarketEvent {
+        MarketEvent::LevelSnapshot(LevelSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: vec![level("100.0", "1"), level("99.5", "2")],
+            asks: vec![level("100.5", "3")],
+            depth: Some(2),
+            sequence: None,
+            checksum: None,
+        })
+    }
+
+    fn bid(price: &str, quantity: &str, checksum: Option<u32>) -> MarketEvent {
+        MarketEvent::LevelUpdated(LevelEvent {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            side: Side::Buy,
+            price: decimal(price),
+            quantity: decimal(quantity),
+            timestamp: Utc::now(),
+            sequence: None,
+            checksum,
+        })
+    }
+
+    /// Stands in for a venue checksum: the number of bid levels in the book.
+    fn bid_count(book: &Level2Book) -> Option<u32> {
+        Some(book.price_levels(Side::Buy).len() as u32)
+    }
+
+    fn no_checksum(_: &Level3Book) -> Option<u32> {
+        None
+    }
+
+    #[test]
+    fn level_books_are_truncated_once_per_message() {
+        let registry = BookRegistry::new(Arc::new(Metrics::new()));
+        registry
+            .apply(vec![snapshot()], &no_checksum, &bid_count)
+            .unwrap();
+
+        // The insert pushes 99.5 beyond the depth, but the delete in the same
+        // message brings it back within it.
+        let outcome = registry
+            .apply(
+                vec![bid("100.2", "1", None), bid("100.0", "0", Some(2))],
+                &no_checksum,
+                &bid_count,
+            )
+            .unwrap();
+
+        assert!(registry.incidents().is_empty());
+        assert_eq!(outcome.events.len(), 2);
+        let levels = registry
+            .with_level_book("kraken", "BTC/USD", |book| book.price_levels(Side::Buy))
+            .unwrap();
+        assert_eq!(levels, vec![level("100.2", "1"), level("99.5", "2")]);
+    }
+
+    #[test]
+    fn levels_beyond_the_depth_are_removed_after_the_message() {
+        let registry = BookRegistry::new(Arc::new(Metrics::new()));
+        registry
+            .apply(vec![snapshot()], &no_checksum, &bid_count)
+            .unwrap();
+
+        let outcome = registry
+            .apply(vec![bid("100.2", "1", Some(2))], &no_checksum, &bid_count)
+            .unwrap();
+
+        assert!(registry.incidents().is_empty());
+        assert_eq!(outcome.events.len(), 2);
+        match &outcome.events[1] {
+            MarketEvent::LevelUpdated(removed) => {
+                assert_eq!(removed.price, decimal("99.5"));
+                assert!(removed.quantity.is_zero());
+                assert_eq!(removed.sequence, Some(3));
+            }
+            other => panic!("expected a level removal, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+pub mod tcp_publisher;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// A message bound for a broker topic.
+#[derive(Clone, Debug)]
+pub struct BrokerMessage {
+    pub topic: String,
+    /// Partitioning key; messages with the same key keep their relative order.
+    pub key: String,
+    /// Position of the message among those sent with its topic and key.
+    pub sequence: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Transport to the MessageBrokerEngine.
+#[async_trait]
+pub trait BrokerPublisher: Send + Sync {
+    /// Publishes `messages`, all bound for `topic`, in order. An error means the
+    /// batch may not have been delivered and may be sent again; what success
+    /// guarantees depends on the transport.
+    async fn publish(&self, topic: &str, messages: &[BrokerMessage]) -> Result<()>;
+}
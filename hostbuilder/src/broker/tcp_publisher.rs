@@ -0,0 +1,122 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use prost::Message;
+use tokio::{io::AsyncWriteExt, net::TcpStream, sync::Mutex};
+use tracing::info;
+
+use crate::{
+    broker::{BrokerMessage, BrokerPublisher},
+    config::ServerSettings,
+    proto::v1,
+};
+
+/// Publishes batches to the broker over a persistent TCP connection, reconnecting
+/// on the next batch after a failure.
+///
+/// Each batch is written as a length-delimited `marketdata.v1.BrokerBatch`, so
+/// the broker decodes it with the same schema as the payloads it carries.
+///
+/// The broker sends nothing back, so a batch is only known to have been written
+/// to the socket: an error means the connection failed, not that the broker
+/// refused the batch, and a batch written just before the connection drops can
+/// be lost without an error.
+pub struct TcpBrokerPublisher {
+    address: String,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl TcpBrokerPublisher {
+    pub fn new(settings: &ServerSettings) -> Self {
+        Self {
+            address: format!("{}:{}", settings.address, settings.port),
+            stream: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl BrokerPublisher for TcpBrokerPublisher {
+    async fn publish(&self, topic: &str, messages: &[BrokerMessage]) -> Result<()> {
+        let frame = encode_frame(topic, messages);
+        let mut stream = self.stream.lock().await;
+
+        if stream.is_none() {
+            *stream = Some(TcpStream::connect(&self.address).await?);
+            info!("Connected to message broker at {}", self.address);
+        }
+
+        if let Some(connection) = stream.as_mut() {
+            if let Err(e) = connection.write_all(&frame).await {
+                *stream = None;
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_frame(topic: &str, messages: &[BrokerMessage]) -> Vec<u8> {
+    v1::BrokerBatch {
+        topic: topic.to_string(),
+        messages: messages
+            .iter()
+            .map(|message| v1::BrokerMessage {
+                key: message.key.clone(),
+                payload: message.payload.clone(),
+                sequence: message.sequence,
+            })
+            .collect(),
+    }
+    .encode_length_delimited_to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(key: &str, payload: &[u8]) -> BrokerMessage {
+        BrokerMessage {
+            topic: "order-data".to_string(),
+            key: key.to_string(),
+            payload: payload.to_vec(),
+            sequence: 1,
+        }
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let messages = vec![
+            message("kraken:BTC/USD", &[1, 2, 3]),
+            message("kraken:ETH/USD", &[]),
+            message("", &[0; 300]),
+        ];
+
+        let frame = encode_frame("order-data", &messages);
+        let batch = v1::BrokerBatch::decode_length_delimited(frame.as_slice()).unwrap();
+
+        assert_eq!(batch.topic, "order-data");
+        assert_eq!(batch.messages.len(), messages.len());
+        for (decoded, message) in batch.messages.iter().zip(&messages) {
+            assert_eq!(decoded.key, message.key);
+            assert_eq!(decoded.payload, message.payload);
+            assert_eq!(decoded.sequence, message.sequence);
+        }
+    }
+
+    #[test]
+    fn consecutive_frames_can_be_split_apart() {
+        let mut stream = encode_frame("order-data", &[message("a", &[1])]);
+        stream.extend(encode_frame("book-data", &[message("b", &[2; 200])]));
+
+        let mut cursor = stream.as_slice();
+        let first = v1::BrokerBatch::decode_length_delimited(&mut cursor).unwrap();
+        let second = v1::BrokerBatch::decode_length_delimited(&mut cursor).unwrap();
+
+        assert!(cursor.is_empty());
+        assert_eq!(first.topic, "order-data");
+        assert_eq!(first.messages[0].key, "a");
+        assert_eq!(second.topic, "book-data");
+        assert_eq!(second.messages[0].payload, vec![2; 200]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::events::{Candle, MarketEvent};
+
+/// (exchange, symbol, interval)
+type CandleKey = (String, String, u32);
+
+/// Follows the candle each (exchange, symbol, interval) is building, to tell
+/// when its interval has ended. Venues only say so implicitly, by starting to
+/// send the next interval's candle.
+#[derive(Default)]
+pub struct CandleTracker {
+    open: Mutex<HashMap<CandleKey, Candle>>,
+}
+
+impl CandleTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Passes `events` through, inserting a `CandleClosed` carrying the final
+    /// state of the previous candle ahead of the first candle of a later
+    /// interval. Candles of intervals that were already closed, such as those
+    /// repeated in the snapshot sent on resubscribing, are dropped so a closed
+    /// candle is never reopened.
+    pub fn track(&self, events: Vec<MarketEvent>) -> Vec<MarketEvent> {
+        if !events
+            .iter()
+            .any(|event| matches!(event, MarketEvent::Candle(_)))
+        {
+            return events;
+        }
+        let Ok(mut open) = self.open.lock() else {
+            return events;
+        };
+
+        let mut tracked = Vec::with_capacity(events.len());
+        for event in events {
+            if let MarketEvent::Candle(candle) = &event {
+                let key = (
+                    candle.exchange.clone(),
+                    candle.symbol.clone(),
+                    candle.interval,
+                );
+                match open.get(&key) {
+                    Some(current) if candle.interval_begin < current.interval_begin => continue,
+                    Some(current) if candle.interval_begin > current.interval_begin => {
+                        tracked.push(MarketEvent::CandleClosed(Box::new(current.clone())));
+                        open.insert(key, candle.as_ref().clone());
+                    }
+                    _ => {
+                        open.insert(key, candle.as_ref().clone());
+                    }
+                }
+            }
+            tracked.push(event);
+        }
+        tracked
+    }
+
+    /// Forgets the candles of `exchange`/`symbol`, which is no longer fed.
+    pub fn remove(&self, exchange: &str, symbol: &str) {
+        if let Ok(mut open) = self.open.lock() {
+            open.retain(|(e, s, _), _| e != exchange || s != symbol);
+        }
+    }
+}
@@ -1,9 +1,18 @@
-use serde::{Deserialize, Serialize};
-use serde_yaml;
-use std::fs;
 use anyhow::{Context, Result};
+use config::{Environment, File, FileFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+};
+use thiserror::Error;
+use url::Url;
+
+use crate::{connector::ConnectorRegistry, sinks::broker_sink::TOPICS};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub data_engine_server_configuration: DataEngineServerConfiguration,
     pub message_broker_server_configuration: MessageBrokerServerConfiguration,
@@ -12,22 +21,186 @@ pub struct Config {
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct DataEngineServerConfiguration {
     pub data_engine_server_settings: ServerSettings,
+    #[serde(default)]
+    pub redis_settings: RedisSettings,
+    #[serde(default)]
+    pub pipeline_settings: PipelineSettings,
+    #[serde(default)]
+    pub postgres_settings: PostgresSettings,
+    #[serde(default)]
+    pub shutdown_settings: ShutdownSettings,
+    #[serde(default)]
+    pub reload_settings: ReloadSettings,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisSettings {
+    /// Prefix of every key the engine writes, so engines sharing a Redis do not
+    /// collide and one engine's keys can be found without touching the others.
+    pub namespace: String,
+    /// Entries kept per event stream. Trimming is approximate, so a stream may
+    /// briefly hold a few more.
+    pub stream_max_len: usize,
+}
+
+impl Default for RedisSettings {
+    fn default() -> Self {
+        Self {
+            namespace: "data-engine".to_string(),
+            stream_max_len: 100_000,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineSettings {
+    /// Frames read from a connection that may wait to be parsed and applied.
+    pub frame_capacity: usize,
+    /// Applied batches that may wait for each sink.
+    pub sink_capacity: usize,
+    /// What to do when a queue is full. Waiting slows the reader down; dropping
+    /// a frame invalidates the connection's books and requests fresh snapshots,
+    /// and dropping a sink's batch leaves that sink missing its trades, tickers
+    /// and candles while its books are resent from memory with its next batch.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for PipelineSettings {
+    fn default() -> Self {
+        Self {
+            frame_capacity: 10_000,
+            sink_capacity: 10_000,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostgresSettings {
+    /// Pending writes per table that trigger a flush.
+    pub batch_size: usize,
+    /// Longest a write waits for its batch to fill, in milliseconds.
+    pub linger_ms: u64,
+    /// Writes queued per table before the feed waits for the database.
+    pub queue_capacity: usize,
+    /// Attempts at a transiently failing batch before it is given up on.
+    pub max_retries: usize,
+}
+
+impl Default for PostgresSettings {
+    fn default() -> Self {
+        Self {
+            batch_size: 1000,
+            linger_ms: 100,
+            queue_capacity: 100_000,
+            max_retries: 5,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownSettings {
+    /// Longest shutdown waits for feeds to unsubscribe and pending writes to
+    /// drain, in milliseconds.
+    pub drain_timeout_ms: u64,
+    /// When set, the engine's Redis keys expire this many seconds after shutdown
+    /// instead of being kept until the next run overwrites them.
+    pub expire_keys_after_secs: Option<u64>,
+}
+
+impl Default for ShutdownSettings {
+    fn default() -> Self {
+        Self {
+            drain_timeout_ms: 15_000,
+            expire_keys_after_secs: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReloadSettings {
+    /// Whether changes to the config file are applied while the engine runs.
+    /// Only `topics` and `exchanges` are reloaded; other settings take effect
+    /// on restart.
+    pub enabled: bool,
+    /// How often the config file is checked for changes, in milliseconds.
+    pub poll_interval_ms: u64,
+}
+
+impl Default for ReloadSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            poll_interval_ms: 5_000,
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct MessageBrokerServerConfiguration {
     pub message_broker_server_settings: ServerSettings,
+    #[serde(default)]
+    pub publisher_settings: PublisherSettings,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PublisherSettings {
+    /// Messages per topic sent to the broker in one batch.
+    pub batch_size: usize,
+    /// Longest a message waits for its batch to fill, in milliseconds.
+    pub linger_ms: u64,
+    /// Messages buffered while the broker is slow or unavailable.
+    pub buffer_capacity: usize,
+    /// What to do when the buffer is full.
+    pub overflow: OverflowPolicy,
+    /// Attempts at a batch the broker rejects before it is dropped.
+    pub max_retries: usize,
+}
+
+impl Default for PublisherSettings {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            linger_ms: 50,
+            buffer_capacity: 100_000,
+            overflow: OverflowPolicy::Block,
+            max_retries: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Wait for room, slowing down the feed that produced the events.
+    Block,
+    /// Discard the new events and count them.
+    Drop,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct ServerSettings {
     pub address: String,
     pub port: u16,
+    /// Bearer token required by the admin routes. They are not served when
+    /// unset.
+    #[serde(default)]
+    pub admin_token: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Exchange {
     pub exchange: String,
     pub websocket_token: String,
@@ -36,22 +209,540 @@ pub struct Exchange {
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Api {
     pub websockets: Vec<Websocket>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
 pub struct Websocket {
     pub channel: String,
     pub endpoint: String,
+    /// Most symbols subscribed over one connection; the symbols are spread over
+    /// as many connections as needed. Defaults to the venue's limit for the
+    /// channel, if it has one.
+    #[serde(default)]
+    pub max_symbols_per_connection: Option<usize>,
+    /// Price levels per side to subscribe to, for channels that take a depth.
+    /// Defaults to the venue's default for the channel.
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// Candle intervals in minutes to subscribe to, for channels that take an
+    /// interval. Defaults to the venue's default for the channel.
+    #[serde(default)]
+    pub intervals: Vec<u32>,
+    /// Candle intervals for particular symbols, replacing `intervals` for them.
+    /// A list rather than a map keyed by symbol, since config keys are
+    /// lowercased when loaded.
+    #[serde(default)]
+    pub symbol_intervals: Vec<SymbolIntervals>,
 }
 
+impl Websocket {
+    /// The candle intervals configured for `symbol`, empty if left to the venue.
+    pub fn intervals(&self, symbol: &str) -> &[u32] {
+        self.symbol_intervals
+            .iter()
+            .find(|entry| entry.symbol == symbol)
+            .map_or(&self.intervals, |entry| &entry.intervals)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolIntervals {
+    pub symbol: String,
+    pub intervals: Vec<u32>,
+}
+
+/// Prefix of the environment variables overriding the config file. Nested keys
+/// are joined with `__`, so the API port is overridden by
+///
+/// `DATA_ENGINE__DATA_ENGINE_SERVER_CONFIGURATION__DATA_ENGINE_SERVER_SETTINGS__PORT`
+///
+/// `topics` takes a comma separated list; exchanges can only be set in the file.
+pub const ENV_PREFIX: &str = "DATA_ENGINE";
+
+/// Every problem found in a config, reported together so they can all be fixed
+/// at once.
+#[derive(Debug, Error)]
+#[error("Invalid config:\n{}", .0.iter().map(|problem| format!("  - {}", problem)).collect::<Vec<_>>().join("\n"))]
+pub struct ConfigErrors(pub Vec<String>);
+
 impl Config {
+    /// Loads the config from `file_path`, layered over the defaults and
+    /// overridden by `DATA_ENGINE__*` environment variables.
     pub fn new(file_path: &str) -> Result<Self> {
         let config_data = fs::read_to_string(file_path)
             .with_context(|| format!("Unable to read file: {}", file_path))?;
-        let config: Config = serde_yaml::from_str(&config_data)
-            .context("YAML was not well-formatted")?;
+        Self::parse(&config_data)
+    }
+
+    /// Layers `config_data`, the YAML of the config file, over the defaults and
+    /// under the environment. Unknown keys are rejected wherever they come from,
+    /// and every key or value that does not fit is reported together.
+    pub fn parse(config_data: &str) -> Result<Self> {
+        let layers = config::Config::builder()
+            .add_source(File::from_str(&defaults().to_string(), FileFormat::Json))
+            .add_source(File::from_str(config_data, FileFormat::Yaml))
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .separator("__")
+                    .list_separator(",")
+                    .with_list_parse_key("topics")
+                    .try_parsing(true),
+            )
+            .build()
+            .context("Config was not well-formatted")?;
+
+        let mut problems = uppercase_keys(config_data);
+        match layers.clone().try_deserialize::<Config>() {
+            Ok(config) if problems.is_empty() => return Ok(config),
+            Ok(_) => {}
+            Err(error) => {
+                let layout = layers
+                    .try_deserialize()
+                    .map(layout_problems)
+                    .unwrap_or_default();
+                if problems.is_empty() && layout.is_empty() {
+                    return Err(error).context("Config does not match the expected layout");
+                }
+                problems.extend(layout);
+            }
+        }
+        Err(ConfigErrors(problems).into())
+    }
+
+    /// Loads the config from `file_path` and validates it against the connectors
+    /// in `registry`.
+    pub fn check(file_path: &str, registry: &ConnectorRegistry) -> Result<Self> {
+        let config = Self::new(file_path)?;
+        config.validate(registry)?;
         Ok(config)
     }
-}
\ No newline at end of file
+
+    /// Rejects configs the engine cannot run: unknown exchanges, channels or
+    /// topics, malformed URLs, or exchanges without symbols or listing a symbol
+    /// or channel twice.
+    pub fn validate(&self, registry: &ConnectorRegistry) -> Result<(), ConfigErrors> {
+        let mut problems = vec![];
+
+        for topic in self.topics.iter() {
+            if !TOPICS.contains(&topic.as_str()) {
+                problems.push(format!(
+                    "Unknown topic {}, expected one of {:?}",
+                    topic, TOPICS
+                ));
+            }
+        }
+
+        let mut exchanges = HashSet::new();
+        for exchange in self.exchanges.iter() {
+            let name = &exchange.exchange;
+            if !exchanges.insert(name) {
+                problems.push(format!("Exchange {} is listed more than once", name));
+            }
+            let connector = registry.create(exchange);
+            if connector.is_none() {
+                problems.push(format!("Unknown exchange: {}", name));
+            }
+            if let Err(problem) = check_url(&exchange.websocket_token, &["http", "https"]) {
+                problems.push(format!("{} websocket_token {}", name, problem));
+            }
+
+            if exchange.symbols.is_empty() {
+                problems.push(format!("{} lists no symbols", name));
+            }
+            let mut symbols = HashSet::new();
+            for symbol in exchange.symbols.iter() {
+                if symbol.trim().is_empty() {
+                    problems.push(format!("{} lists an empty symbol", name));
+                } else if !symbols.insert(symbol) {
+                    problems.push(format!("{} lists symbol {} more than once", name, symbol));
+                }
+            }
+
+            let mut channels = HashSet::new();
+            for websocket in exchange.websockets() {
+                let channel = &websocket.channel;
+                if !channels.insert(channel) {
+                    problems.push(format!("{} lists channel {} more than once", name, channel));
+                }
+                if let Some(connector) = &connector {
+                    if !connector.channels().contains(&channel.as_str()) {
+                        problems.push(format!(
+                            "{} does not support channel {}, expected one of {:?}",
+                            name,
+                            channel,
+                            connector.channels()
+                        ));
+                    }
+                }
+                if let Err(problem) = check_url(&websocket.endpoint, &["ws", "wss"]) {
+                    problems.push(format!("{} {} endpoint {}", name, channel, problem));
+                }
+                if websocket.max_symbols_per_connection == Some(0) {
+                    problems.push(format!(
+                        "{} {} max_symbols_per_connection must be at least 1",
+                        name, channel
+                    ));
+                }
+                if let (Some(depth), Some(connector)) = (websocket.depth, &connector) {
+                    let depths = connector.depths(channel);
+                    if depths.is_empty() {
+                        problems.push(format!("{} {} does not take a depth", name, channel));
+                    } else if !depths.contains(&depth) {
+                        problems.push(format!(
+                            "{} {} depth {} is not one of {:?}",
+                            name, channel, depth, depths
+                        ));
+                    }
+                }
+                let mut interval_symbols = HashSet::new();
+                for entry in websocket.symbol_intervals.iter() {
+                    let symbol = &entry.symbol;
+                    if !exchange.symbols.contains(symbol) {
+                        problems.push(format!(
+                            "{} {} sets intervals for unlisted symbol {}",
+                            name, channel, symbol
+                        ));
+                    } else if !interval_symbols.insert(symbol) {
+                        problems.push(format!(
+                            "{} {} sets intervals for symbol {} more than once",
+                            name, channel, symbol
+                        ));
+                    }
+                }
+                if let Some(connector) = &connector {
+                    let supported = connector.intervals(channel);
+                    let unsupported: BTreeSet<u32> = websocket
+                        .intervals
+                        .iter()
+                        .chain(
+                            websocket
+                                .symbol_intervals
+                                .iter()
+                                .flat_map(|entry| entry.intervals.iter()),
+                        )
+                        .filter(|interval| !supported.contains(interval))
+                        .copied()
+                        .collect();
+                    if supported.is_empty() && !unsupported.is_empty() {
+                        problems.push(format!("{} {} does not take an interval", name, channel));
+                    } else if !unsupported.is_empty() {
+                        problems.push(format!(
+                            "{} {} intervals {:?} are not among {:?}",
+                            name, channel, unsupported, supported
+                        ));
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(problems))
+        }
+    }
+}
+
+/// Settings the config file may leave out, as the first layer under it.
+fn defaults() -> serde_json::Value {
+    json!({
+        "data_engine_server_configuration": {
+            "redis_settings": RedisSettings::default(),
+            "pipeline_settings": PipelineSettings::default(),
+            "postgres_settings": PostgresSettings::default(),
+            "shutdown_settings": ShutdownSettings::default(),
+            "reload_settings": ReloadSettings::default(),
+        },
+        "message_broker_server_configuration": {
+            "publisher_settings": PublisherSettings::default(),
+        },
+    })
+}
+
+fn check_url(value: &str, schemes: &[&str]) -> Result<(), String> {
+    let url = Url::parse(value).map_err(|e| format!("{} is not a valid URL: {}", value, e))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("{} should use one of {:?}", value, schemes));
+    }
+    Ok(())
+}
+
+/// Most fixes [`layout_problems`] tries, in case they never let deserialization
+/// get further.
+const MAX_LAYOUT_FIXES: usize = 100;
+
+/// Values tried in place of one that does not fit, until one lets
+/// deserialization get past it.
+fn placeholders() -> [serde_json::Value; 5] {
+    [json!(0), json!(""), json!(false), json!([]), json!({})]
+}
+
+/// Step into a config value, leaving out the enum variants serde also tracks.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Every key or value of `value` that does not fit [`Config`]. Serde stops at
+/// the first, so each one is reported, then removed or replaced by a
+/// placeholder until the rest deserializes. Errors the fix causes, such as a
+/// removed field now missing, are not reported.
+fn layout_problems(mut value: serde_json::Value) -> Vec<String> {
+    let mut problems = vec![];
+    let mut patched: HashSet<Vec<Step>> = HashSet::new();
+
+    for _ in 0..MAX_LAYOUT_FIXES {
+        let Some((path, message)) = first_error(&value) else {
+            break;
+        };
+        let location = error_path(&path);
+        // Serde names an unknown or missing field only in its message.
+        let field = ["unknown field `", "missing field `"]
+            .iter()
+            .find_map(|prefix| message.strip_prefix(prefix))
+            .and_then(|rest| rest.split('`').next())
+            .map(|field| [location.clone(), vec![Step::Key(field.to_string())]].concat());
+        let target = field.unwrap_or_else(|| location.clone());
+
+        let caused = patched
+            .iter()
+            .any(|fix| target.starts_with(fix) || location.starts_with(fix));
+        if !caused {
+            problems.push(match path.to_string().as_str() {
+                "." => message.clone(),
+                path => format!("{}: {}", path, message),
+            });
+        }
+
+        if message.starts_with("unknown field") {
+            remove(&mut value, &target);
+        } else if !patch(&mut value, &target, &path.to_string(), &message) {
+            break;
+        }
+        patched.insert(target);
+    }
+    problems
+}
+
+/// Where and why `value` first fails to deserialize, if it does.
+fn first_error(value: &serde_json::Value) -> Option<(serde_path_to_error::Path, String)> {
+    let error = serde_path_to_error::deserialize::<_, Config>(value).err()?;
+    let message = error.inner().to_string();
+    Some((error.path().clone(), message))
+}
+
+fn error_path(path: &serde_path_to_error::Path) -> Vec<Step> {
+    path.iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Map { key } => Some(Step::Key(key.clone())),
+            serde_path_to_error::Segment::Seq { index } => Some(Step::Index(*index)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Removes the field at `target`, or failing that replaces the value there with
+/// the first placeholder that moves the error elsewhere. Returns whether either
+/// did.
+fn patch(value: &mut serde_json::Value, target: &[Step], path: &str, message: &str) -> bool {
+    // A removed field only helps if it may be left out, while a placeholder
+    // struct may well miss fields of its own.
+    let moved = |candidate: &serde_json::Value, removed: bool| match first_error(candidate) {
+        Some((_, next_message)) if next_message.starts_with("missing field") => {
+            !removed && next_message != message
+        }
+        Some((next_path, _)) => next_path.to_string() != path,
+        None => true,
+    };
+
+    if matches!(target.last(), Some(Step::Key(_))) {
+        let mut candidate = value.clone();
+        if remove(&mut candidate, target) && moved(&candidate, true) {
+            *value = candidate;
+            return true;
+        }
+    }
+    for placeholder in placeholders() {
+        let mut candidate = value.clone();
+        if set(&mut candidate, target, placeholder) && moved(&candidate, false) {
+            *value = candidate;
+            return true;
+        }
+    }
+    false
+}
+
+fn remove(value: &mut serde_json::Value, target: &[Step]) -> bool {
+    let Some((Step::Key(key), parent)) = target.split_last() else {
+        return false;
+    };
+    lookup(value, parent)
+        .and_then(|parent| parent.as_object_mut())
+        .and_then(|parent| parent.remove(key))
+        .is_some()
+}
+
+fn set(value: &mut serde_json::Value, target: &[Step], new: serde_json::Value) -> bool {
+    let Some((last, parent)) = target.split_last() else {
+        return false;
+    };
+    match (lookup(value, parent), last) {
+        (Some(serde_json::Value::Object(map)), Step::Key(key)) => {
+            map.insert(key.clone(), new);
+            true
+        }
+        (Some(serde_json::Value::Array(items)), Step::Index(index)) if *index < items.len() => {
+            items[*index] = new;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn lookup<'a>(
+    value: &'a mut serde_json::Value,
+    target: &[Step],
+) -> Option<&'a mut serde_json::Value> {
+    target.iter().try_fold(value, |value, step| match step {
+        Step::Key(key) => value.get_mut(key.as_str()),
+        Step::Index(index) => value.get_mut(*index),
+    })
+}
+
+/// Keys of the config file that are not lowercase. They are lowercased when
+/// loaded, so they would otherwise be accepted under a different name.
+fn uppercase_keys(config_data: &str) -> Vec<String> {
+    fn walk(value: &serde_yaml::Value, path: &str, problems: &mut Vec<String>) {
+        match value {
+            serde_yaml::Value::Mapping(mapping) => {
+                for (key, value) in mapping {
+                    let key = match key {
+                        serde_yaml::Value::String(key) => key.as_str(),
+                        _ => continue,
+                    };
+                    let path = if path.is_empty() {
+                        key.to_string()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    if key != key.to_lowercase() {
+                        problems.push(format!(
+                            "{}: keys are read in lowercase, write {} as {}",
+                            path,
+                            key,
+                            key.to_lowercase()
+                        ));
+                    }
+                    walk(value, &path, problems);
+                }
+            }
+            serde_yaml::Value::Sequence(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    walk(item, &format!("{}[{}]", path, index), problems);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut problems = vec![];
+    if let Ok(value) = serde_yaml::from_str(config_data) {
+        walk(&value, "", &mut problems);
+    }
+    problems
+}
+
+impl Exchange {
+    /// Every websocket of every API.
+    pub fn websockets(&self) -> impl Iterator<Item = &Websocket> {
+        self.apis.iter().flat_map(|api| api.websockets.iter())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+data_engine_server_configuration:
+  data_engine_server_settings:
+    address: '127.0.0.1'
+    port: 8000
+message_broker_server_configuration:
+  message_broker_server_settings:
+    address: '127.0.0.1'
+    port: 8001
+topics:
+  - 'candle-data'
+exchanges:
+  - exchange: 'Kraken'
+    websocket_token: 'https://api.kraken.com/0/private/GetWebSocketsToken'
+    symbols:
+      - 'BTC/USD'
+      - 'ETH/USD'
+    apis:
+      - websockets:
+          - channel: 'ohlc'
+            endpoint: 'wss://ws.kraken.com/v2'
+            intervals: [5]
+            symbol_intervals:
+              - symbol: 'BTC/USD'
+                intervals: [1, 60]
+";
+
+    fn ohlc(config: &Config) -> &Websocket {
+        config.exchanges[0].websockets().next().unwrap()
+    }
+
+    #[test]
+    fn per_symbol_intervals_keep_the_symbol_case() {
+        let config = Config::parse(CONFIG).unwrap();
+        config.validate(&ConnectorRegistry::default()).unwrap();
+
+        assert_eq!(ohlc(&config).intervals("BTC/USD"), &[1, 60]);
+        assert_eq!(ohlc(&config).intervals("ETH/USD"), &[5]);
+    }
+
+    #[test]
+    fn per_symbol_intervals_must_name_listed_symbols() {
+        let config =
+            Config::parse(&CONFIG.replace("symbol: 'BTC/USD'", "symbol: 'SOL/USD'")).unwrap();
+        let errors = config.validate(&ConnectorRegistry::default()).unwrap_err();
+
+        assert_eq!(
+            errors.0,
+            vec!["Kraken ohlc sets intervals for unlisted symbol SOL/USD"]
+        );
+    }
+
+    #[test]
+    fn every_layout_problem_is_reported() {
+        let config = CONFIG
+            .replace("port: 8000", "port: 'eight thousand'")
+            .replace("    port: 8001", "    Port: 8001\n    timeout: 5")
+            .replace("intervals: [5]", "intervals: [5, 'hourly']")
+            .replace("exchanges:", "exchanges:\n  - 'Binance'");
+        let error = Config::parse(&config).unwrap_err();
+        let errors = error.downcast_ref::<ConfigErrors>().unwrap();
+
+        assert_eq!(
+            errors.0,
+            vec![
+                "message_broker_server_configuration.message_broker_server_settings.Port: \
+                 keys are read in lowercase, write Port as port",
+                "data_engine_server_configuration.data_engine_server_settings.port: \
+                 invalid type: string \"eight thousand\", expected u16",
+                "exchanges[0]: invalid type: string \"Binance\", expected struct Exchange",
+                "exchanges[1].apis[0].websockets[0].intervals[1]: \
+                 invalid type: string \"hourly\", expected u32",
+                "message_broker_server_configuration.message_broker_server_settings.timeout: \
+                 unknown field `timeout`, expected one of `address`, `port`, `admin_token`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::RwLock};
+
+/// State of one websocket connection to a venue.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionStatus {
+    pub exchange: String,
+    pub channel: String,
+    pub endpoint: String,
+    pub symbols: Vec<String>,
+    pub connected: bool,
+    pub connected_since: Option<DateTime<Utc>>,
+    pub disconnected_since: Option<DateTime<Utc>>,
+    pub reconnects: u64,
+    pub last_message_at: Option<DateTime<Utc>>,
+}
+
+/// Live status of every websocket the engine maintains, keyed by connection id.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: RwLock<BTreeMap<String, ConnectionStatus>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking a connection that has not been established yet.
+    pub fn register(
+        &self,
+        id: &str,
+        exchange: &str,
+        channel: &str,
+        endpoint: &str,
+        symbols: &[String],
+    ) {
+        if let Ok(mut connections) = self.connections.write() {
+            connections.insert(
+                id.to_string(),
+                ConnectionStatus {
+                    exchange: exchange.to_string(),
+                    channel: channel.to_string(),
+                    endpoint: endpoint.to_string(),
+                    symbols: symbols.to_vec(),
+                    connected: false,
+                    connected_since: None,
+                    disconnected_since: Some(Utc::now()),
+                    reconnects: 0,
+                    last_message_at: None,
+                },
+            );
+        }
+    }
+
+    pub fn connected(&self, id: &str, reconnect: bool) {
+        self.update(id, |status| {
+            status.connected = true;
+            status.connected_since = Some(Utc::now());
+            status.disconnected_since = None;
+            if reconnect {
+                status.reconnects += 1;
+            }
+        });
+    }
+
+    pub fn disconnected(&self, id: &str) {
+        self.update(id, |status| {
+            status.connected = false;
+            status.connected_since = None;
+            status.disconnected_since = Some(Utc::now());
+        });
+    }
+
+    pub fn message_received(&self, id: &str) {
+        self.update(id, |status| status.last_message_at = Some(Utc::now()));
+    }
+
+    /// Records the symbols now carried by a connection.
+    pub fn set_symbols(&self, id: &str, symbols: &[String]) {
+        self.update(id, |status| status.symbols = symbols.to_vec());
+    }
+
+    /// Stops tracking a connection.
+    pub fn remove(&self, id: &str) {
+        if let Ok(mut connections) = self.connections.write() {
+            connections.remove(id);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<ConnectionStatus> {
+        self.connections.read().ok()?.get(id).cloned()
+    }
+
+    pub fn all(&self) -> Vec<ConnectionStatus> {
+        self.connections
+            .read()
+            .map(|connections| connections.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut ConnectionStatus)) {
+        if let Ok(mut connections) = self.connections.write() {
+            if let Some(status) = connections.get_mut(id) {
+                f(status);
+            }
+        }
+    }
+}
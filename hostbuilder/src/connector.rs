@@ -0,0 +1,148 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use databaseschema::models::OrderBook;
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    book::{level2::Level2Book, level3::Level3Book, BookKind},
+    config::{Exchange, Websocket},
+    endpoint::EndpointHandler,
+    events::{Instrument, MarketEvent},
+    handlers::connectors::kraken_connector::KrakenConnector,
+    processor::MarketDataProcessor,
+};
+
+/// A venue integration. The orchestration loop in `HostedObject::run` only talks to
+/// exchanges through this trait, so a new venue is added by implementing it and
+/// registering a factory under the name used in `Exchange.exchange`.
+#[async_trait]
+pub trait ExchangeConnector: Send + Sync {
+    /// Name of the venue, matching the `exchange` field in the config.
+    fn exchange(&self) -> &str;
+
+    /// Channels the venue supports, as named in `Websocket.channel`.
+    fn channels(&self) -> &[&str];
+
+    /// Values `Websocket.depth` may take for `channel`. Channels that take no
+    /// depth return none.
+    fn depths(&self, _channel: &str) -> &[u32] {
+        &[]
+    }
+
+    /// Candle intervals in minutes `Websocket.intervals` may take for `channel`.
+    /// Channels that take no interval return none.
+    fn intervals(&self, _channel: &str) -> &[u32] {
+        &[]
+    }
+
+    /// Whether `websocket.channel` carries our own account's activity rather
+    /// than market data for its symbols. Such a channel is subscribed once per
+    /// connection, whatever symbols the connection carries.
+    fn is_account_channel(&self, _websocket: &Websocket) -> bool {
+        false
+    }
+
+    /// The kind of book `websocket.channel` feeds, or `None` if it feeds none.
+    fn book_kind(&self, _websocket: &Websocket) -> Option<BookKind> {
+        None
+    }
+
+    /// Performs whatever handshake the venue needs before subscribing.
+    async fn authenticate(&self) -> Result<()>;
+
+    /// Builds the subscribe frames for `websocket.channel`, usually one, but one
+    /// per parameter set where the venue takes a single value per frame. Called
+    /// for every (re)subscription, so credentials embedded in the frames must be
+    /// current. Fails for channels the venue does not support.
+    async fn subscribe_messages(
+        &self,
+        websocket: &Websocket,
+        symbols: &[String],
+    ) -> Result<Vec<Value>>;
+
+    /// Builds the unsubscribe frames for `websocket.channel`.
+    async fn unsubscribe_messages(
+        &self,
+        websocket: &Websocket,
+        symbols: &[String],
+    ) -> Result<Vec<Value>>;
+
+    /// Parses a single text frame received from the venue into normalized events.
+    /// Frames that carry no market data, such as acknowledgements, yield no events.
+    fn parse_frame(&self, text: &str) -> Result<Vec<MarketEvent>>;
+
+    /// The venue's reference data for `symbol`, if it has been loaded.
+    fn instrument(&self, _symbol: &str) -> Option<Instrument> {
+        None
+    }
+
+    /// The venue's checksum of `book`, compared against the checksums carried by
+    /// events to detect a corrupted book. `None` skips verification.
+    fn book_checksum(&self, _book: &Level3Book) -> Option<u32> {
+        None
+    }
+
+    /// The venue's checksum of a Level 2 `book`, used like `book_checksum`.
+    fn level_book_checksum(&self, _book: &Level2Book) -> Option<u32> {
+        None
+    }
+
+    /// Most symbols the venue accepts on one connection to `websocket.channel`,
+    /// or `None` if it does not limit them.
+    fn max_symbols_per_connection(&self, _websocket: &Websocket) -> Option<usize> {
+        None
+    }
+
+    /// Creates the handler for one connection to `websocket.endpoint` carrying
+    /// `order_books`, which connects and subscribes once it listens. `shard`
+    /// tells apart the connections the channel's symbols are spread over. Parsed
+    /// events are routed to their book by symbol and handed to `processor`.
+    async fn connect(
+        self: Arc<Self>,
+        websocket: &Websocket,
+        shard: usize,
+        order_books: Vec<OrderBook>,
+        processor: Arc<MarketDataProcessor>,
+    ) -> Result<Arc<dyn EndpointHandler + Send + Sync>>;
+}
+
+pub type ConnectorFactory = fn(&Exchange) -> Arc<dyn ExchangeConnector>;
+
+/// Maps `Exchange.exchange` names to connector factories.
+#[derive(Clone)]
+pub struct ConnectorRegistry {
+    factories: HashMap<String, ConnectorFactory>,
+}
+
+impl ConnectorRegistry {
+    /// Creates a registry with no connectors registered.
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers `factory` under `exchange`, replacing any previous registration.
+    pub fn register(&mut self, exchange: &str, factory: ConnectorFactory) {
+        self.factories.insert(exchange.to_string(), factory);
+    }
+
+    /// Builds the connector for `exchange`, or `None` if no factory is registered.
+    pub fn create(&self, exchange: &Exchange) -> Option<Arc<dyn ExchangeConnector>> {
+        self.factories
+            .get(&exchange.exchange)
+            .map(|factory| factory(exchange))
+    }
+}
+
+impl Default for ConnectorRegistry {
+    /// Creates a registry with the built-in connectors registered.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("Kraken", |exchange| {
+            Arc::new(KrakenConnector::new(exchange))
+        });
+        registry
+    }
+}
@@ -1,9 +1,23 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use databaseschema::models::OrderBook;
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
 
 #[async_trait]
 pub trait EndpointHandler {
+    /// Streams from the endpoint until `shutdown_rx` fires, then stops reading
+    /// between frames, unsubscribes and returns.
     async fn listen(self: Arc<Self>, mut shutdown_rx: Receiver<()>) -> Result<()>;
+
+    /// Symbols currently carried by the connection.
+    fn symbols(&self) -> Vec<String>;
+
+    /// Starts routing the events of `order_books` to them and subscribes to
+    /// their symbols on the open connection. While disconnected, they are
+    /// subscribed on the next connect.
+    async fn subscribe(&self, order_books: Vec<OrderBook>) -> Result<()>;
+
+    /// Stops routing events for `symbols` and unsubscribes from them.
+    async fn unsubscribe(&self, symbols: &[String]) -> Result<()>;
 }
@@ -0,0 +1,590 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::{BigDecimal, RoundingMode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Venue-neutral market data event. Connectors translate their wire formats into
+/// these and every sink consumes them, so nothing downstream of the connector
+/// depends on an exchange's JSON layout.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MarketEvent {
+    OrderAdded(OrderEvent),
+    OrderModified(OrderEvent),
+    OrderDeleted(OrderEvent),
+    BookSnapshot(BookSnapshot),
+    LevelUpdated(LevelEvent),
+    LevelSnapshot(LevelSnapshot),
+    Trade(Trade),
+    Ticker(Box<Ticker>),
+    /// The latest state of a candle still being built.
+    Candle(Box<Candle>),
+    /// A candle whose interval has ended, in its final state.
+    CandleClosed(Box<Candle>),
+    Instrument(Box<Instrument>),
+    /// Activity on the engine's own account. Connections hand it to
+    /// [`crate::processor::MarketDataProcessor::process_account`] instead of
+    /// routing it to a book.
+    Account(AccountEvent),
+    Heartbeat(Heartbeat),
+    Status(StatusEvent),
+}
+
+impl MarketEvent {
+    pub fn exchange(&self) -> &str {
+        match self {
+            MarketEvent::OrderAdded(order)
+            | MarketEvent::OrderModified(order)
+            | MarketEvent::OrderDeleted(order) => &order.exchange,
+            MarketEvent::BookSnapshot(snapshot) => &snapshot.exchange,
+            MarketEvent::LevelUpdated(level) => &level.exchange,
+            MarketEvent::LevelSnapshot(snapshot) => &snapshot.exchange,
+            MarketEvent::Trade(trade) => &trade.exchange,
+            MarketEvent::Ticker(ticker) => &ticker.exchange,
+            MarketEvent::Candle(candle) | MarketEvent::CandleClosed(candle) => &candle.exchange,
+            MarketEvent::Instrument(instrument) => &instrument.exchange,
+            MarketEvent::Account(account) => account.exchange(),
+            MarketEvent::Heartbeat(heartbeat) => &heartbeat.exchange,
+            MarketEvent::Status(status) => &status.exchange,
+        }
+    }
+
+    /// The symbol the event belongs to, or `None` for connection-level events.
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            MarketEvent::OrderAdded(order)
+            | MarketEvent::OrderModified(order)
+            | MarketEvent::OrderDeleted(order) => Some(&order.symbol),
+            MarketEvent::BookSnapshot(snapshot) => Some(&snapshot.symbol),
+            MarketEvent::LevelUpdated(level) => Some(&level.symbol),
+            MarketEvent::LevelSnapshot(snapshot) => Some(&snapshot.symbol),
+            MarketEvent::Trade(trade) => Some(&trade.symbol),
+            MarketEvent::Ticker(ticker) => Some(&ticker.symbol),
+            MarketEvent::Candle(candle) | MarketEvent::CandleClosed(candle) => Some(&candle.symbol),
+            MarketEvent::Instrument(instrument) => Some(&instrument.symbol),
+            MarketEvent::Account(account) => account.symbol(),
+            MarketEvent::Heartbeat(_) | MarketEvent::Status(_) => None,
+        }
+    }
+
+    /// Name of the event's kind, e.g. `order_added`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MarketEvent::OrderAdded(_) => "order_added",
+            MarketEvent::OrderModified(_) => "order_modified",
+            MarketEvent::OrderDeleted(_) => "order_deleted",
+            MarketEvent::BookSnapshot(_) => "book_snapshot",
+            MarketEvent::LevelUpdated(_) => "level_updated",
+            MarketEvent::LevelSnapshot(_) => "level_snapshot",
+            MarketEvent::Trade(_) => "trade",
+            MarketEvent::Ticker(_) => "ticker",
+            MarketEvent::Candle(_) => "candle",
+            MarketEvent::CandleClosed(_) => "candle_closed",
+            MarketEvent::Instrument(_) => "instrument",
+            MarketEvent::Account(account) => account.kind(),
+            MarketEvent::Heartbeat(_) => "heartbeat",
+            MarketEvent::Status(_) => "status",
+        }
+    }
+
+    /// Rounds the event's prices to `price_decimals` and its quantities to
+    /// `quantity_decimals` places, the precision the venue quotes the symbol
+    /// at. Derived figures such as VWAPs and changes are left as they are.
+    pub fn round(&mut self, price_decimals: u32, quantity_decimals: u32) {
+        let price = |value: &mut BigDecimal| round(value, price_decimals);
+        let quantity = |value: &mut BigDecimal| round(value, quantity_decimals);
+
+        match self {
+            MarketEvent::OrderAdded(order)
+            | MarketEvent::OrderModified(order)
+            | MarketEvent::OrderDeleted(order) => {
+                price(&mut order.price);
+                quantity(&mut order.quantity);
+            }
+            MarketEvent::BookSnapshot(snapshot) => {
+                for order in snapshot.bids.iter_mut().chain(snapshot.asks.iter_mut()) {
+                    price(&mut order.price);
+                    quantity(&mut order.quantity);
+                }
+            }
+            MarketEvent::LevelUpdated(level) => {
+                price(&mut level.price);
+                quantity(&mut level.quantity);
+            }
+            MarketEvent::LevelSnapshot(snapshot) => {
+                for level in snapshot.bids.iter_mut().chain(snapshot.asks.iter_mut()) {
+                    price(&mut level.price);
+                    quantity(&mut level.quantity);
+                }
+            }
+            MarketEvent::Trade(trade) => {
+                price(&mut trade.price);
+                quantity(&mut trade.quantity);
+            }
+            MarketEvent::Ticker(ticker) => {
+                for value in [
+                    &mut ticker.bid,
+                    &mut ticker.ask,
+                    &mut ticker.last,
+                    &mut ticker.high,
+                    &mut ticker.low,
+                ] {
+                    price(value);
+                }
+                for value in [
+                    &mut ticker.bid_quantity,
+                    &mut ticker.ask_quantity,
+                    &mut ticker.volume,
+                ] {
+                    quantity(value);
+                }
+            }
+            MarketEvent::Candle(candle) | MarketEvent::CandleClosed(candle) => {
+                for value in [
+                    &mut candle.open,
+                    &mut candle.high,
+                    &mut candle.low,
+                    &mut candle.close,
+                ] {
+                    price(value);
+                }
+                quantity(&mut candle.volume);
+            }
+            MarketEvent::Instrument(_)
+            | MarketEvent::Account(_)
+            | MarketEvent::Heartbeat(_)
+            | MarketEvent::Status(_) => {}
+        }
+    }
+
+    /// Whether the event changes the state of a Level 3 order book.
+    pub fn is_book_event(&self) -> bool {
+        matches!(
+            self,
+            MarketEvent::OrderAdded(_)
+                | MarketEvent::OrderModified(_)
+                | MarketEvent::OrderDeleted(_)
+                | MarketEvent::BookSnapshot(_)
+        )
+    }
+
+    /// Whether the event changes the state of a Level 2 (price level) book.
+    pub fn is_level_event(&self) -> bool {
+        matches!(
+            self,
+            MarketEvent::LevelUpdated(_) | MarketEvent::LevelSnapshot(_)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Side {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "buy" | "bid" => Ok(Side::Buy),
+            "sell" | "ask" => Ok(Side::Sell),
+            other => Err(anyhow!("Unknown side: {}", other)),
+        }
+    }
+}
+
+/// A single order added to, modified in or deleted from a Level 3 book.
+///
+/// `checksum` is the exchange's book checksum expected after this event has been
+/// applied. Venues that checksum a whole update message set it on the last order
+/// event of that message only. `sequence` is assigned by the engine when the event
+/// is applied to its book; see `Level3Book::sequence`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub exchange: String,
+    pub symbol: String,
+    pub side: Side,
+    pub order_id: String,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+    pub sequence: Option<u64>,
+    pub checksum: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookOrder {
+    pub order_id: String,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Full state of a Level 3 book, replacing anything previously known for the symbol.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub exchange: String,
+    pub symbol: String,
+    pub bids: Vec<BookOrder>,
+    pub asks: Vec<BookOrder>,
+    pub sequence: Option<u64>,
+    pub checksum: Option<u32>,
+}
+
+/// Aggregate quantity resting at one price of a Level 2 book.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+}
+
+/// A price level of a Level 2 book set to a new aggregate quantity. A quantity of
+/// zero removes the level.
+///
+/// `checksum` and `sequence` follow the same rules as on [`OrderEvent`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelEvent {
+    pub exchange: String,
+    pub symbol: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+    pub sequence: Option<u64>,
+    pub checksum: Option<u32>,
+}
+
+/// Full state of a Level 2 book, replacing anything previously known for the
+/// symbol. `depth` is the number of levels per side the venue keeps current;
+/// levels pushed beyond it are dropped from the book.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelSnapshot {
+    pub exchange: String,
+    pub symbol: String,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+    pub depth: Option<usize>,
+    pub sequence: Option<u64>,
+    pub checksum: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trade {
+    pub exchange: String,
+    pub symbol: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub order_type: String,
+    pub trade_id: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Top of book and rolling 24 hour statistics for a symbol, as summarised by
+/// the venue independently of any order book feed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ticker {
+    pub exchange: String,
+    pub symbol: String,
+    pub bid: BigDecimal,
+    pub bid_quantity: BigDecimal,
+    pub ask: BigDecimal,
+    pub ask_quantity: BigDecimal,
+    pub last: BigDecimal,
+    pub volume: BigDecimal,
+    pub vwap: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub change: BigDecimal,
+    pub change_pct: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Open, high, low and close of a symbol's trades over one interval.
+/// `interval_begin` and `interval`, in minutes, identify the candle; venues
+/// send it repeatedly while the interval is running.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Candle {
+    pub exchange: String,
+    pub symbol: String,
+    pub interval: u32,
+    pub interval_begin: DateTime<Utc>,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    pub volume: BigDecimal,
+    pub vwap: BigDecimal,
+    pub trades: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Trading rules and reference data of a symbol on a venue. Prices are quoted
+/// to `price_precision` decimals in multiples of `tick_size`, quantities to
+/// `quantity_precision` decimals in multiples of `lot_size`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Instrument {
+    pub exchange: String,
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+    pub status: InstrumentStatus,
+    pub price_precision: u32,
+    pub quantity_precision: u32,
+    pub tick_size: BigDecimal,
+    pub lot_size: BigDecimal,
+    pub min_order_quantity: BigDecimal,
+    pub min_order_cost: Option<BigDecimal>,
+    pub marginable: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// What a venue currently accepts for a symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstrumentStatus {
+    /// Trading normally.
+    Online,
+    /// Only orders that add liquidity are accepted.
+    PostOnly,
+    /// Only limit orders are accepted.
+    LimitOnly,
+    /// Orders can only be cancelled.
+    CancelOnly,
+    /// Only orders reducing a position are accepted.
+    ReduceOnly,
+    /// Trading is suspended, e.g. for maintenance.
+    Halted,
+    /// The symbol is no longer traded.
+    Delisted,
+}
+
+impl InstrumentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstrumentStatus::Online => "online",
+            InstrumentStatus::PostOnly => "post_only",
+            InstrumentStatus::LimitOnly => "limit_only",
+            InstrumentStatus::CancelOnly => "cancel_only",
+            InstrumentStatus::ReduceOnly => "reduce_only",
+            InstrumentStatus::Halted => "halted",
+            InstrumentStatus::Delisted => "delisted",
+        }
+    }
+}
+
+impl fmt::Display for InstrumentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Activity on the engine's own account, as reported on a venue's
+/// authenticated channels.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AccountEvent {
+    /// The latest state of one of our orders.
+    Order(Box<AccountOrder>),
+    /// One of our orders traded.
+    Fill(Box<Fill>),
+    /// The balance of an asset, and the ledger entry that changed it if any.
+    Balance(Box<Balance>),
+}
+
+impl AccountEvent {
+    pub fn exchange(&self) -> &str {
+        match self {
+            AccountEvent::Order(order) => &order.exchange,
+            AccountEvent::Fill(fill) => &fill.exchange,
+            AccountEvent::Balance(balance) => &balance.exchange,
+        }
+    }
+
+    /// The symbol traded, `None` for balances.
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            AccountEvent::Order(order) => Some(&order.symbol),
+            AccountEvent::Fill(fill) => Some(&fill.symbol),
+            AccountEvent::Balance(_) => None,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AccountEvent::Order(_) => "account_order",
+            AccountEvent::Fill(_) => "fill",
+            AccountEvent::Balance(_) => "balance",
+        }
+    }
+}
+
+/// One of our orders, merged from every report the venue sent about it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountOrder {
+    pub exchange: String,
+    pub order_id: String,
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub side: Side,
+    pub order_type: String,
+    pub status: OrderStatus,
+    pub quantity: BigDecimal,
+    pub limit_price: Option<BigDecimal>,
+    pub filled_quantity: BigDecimal,
+    pub average_price: Option<BigDecimal>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// Received by the venue but not yet accepted.
+    PendingNew,
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Expired,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::PendingNew => "pending_new",
+            OrderStatus::New => "new",
+            OrderStatus::PartiallyFilled => "partially_filled",
+            OrderStatus::Filled => "filled",
+            OrderStatus::Canceled => "canceled",
+            OrderStatus::Expired => "expired",
+        }
+    }
+
+    /// Whether the order is done and will not change again.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::Expired
+        )
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending_new" => Ok(OrderStatus::PendingNew),
+            "new" => Ok(OrderStatus::New),
+            "partially_filled" => Ok(OrderStatus::PartiallyFilled),
+            "filled" => Ok(OrderStatus::Filled),
+            "canceled" => Ok(OrderStatus::Canceled),
+            "expired" => Ok(OrderStatus::Expired),
+            other => Err(anyhow!("Unknown order status: {}", other)),
+        }
+    }
+}
+
+/// A trade of one of our orders. Venues repeat recent fills when resubscribing,
+/// so `execution_id` is what tells them apart.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fill {
+    pub exchange: String,
+    pub execution_id: String,
+    pub trade_id: Option<u64>,
+    pub order_id: String,
+    pub symbol: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    /// Value of the fill in the quote currency, before fees.
+    pub cost: BigDecimal,
+    pub fee: BigDecimal,
+    pub fee_asset: Option<String>,
+    pub liquidity: Option<Liquidity>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+impl Liquidity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Liquidity::Maker => "maker",
+            Liquidity::Taker => "taker",
+        }
+    }
+}
+
+/// The balance of an asset. Balances from a snapshot carry no ledger entry.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Balance {
+    pub exchange: String,
+    pub asset: String,
+    pub balance: BigDecimal,
+    pub ledger: Option<LedgerEntry>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A change to a balance, such as a trade, deposit or withdrawal.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub ledger_id: String,
+    pub entry_type: String,
+    pub amount: BigDecimal,
+    pub fee: BigDecimal,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub exchange: String,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatusEvent {
+    pub exchange: String,
+    pub system: String,
+    pub api_version: String,
+    pub connection_id: u64,
+    pub version: String,
+}
+
+/// Rounds `value` to `decimals` places, half to even.
+pub fn round(value: &mut BigDecimal, decimals: u32) {
+    *value = value.with_scale_round(decimals as i64, RoundingMode::HalfEven);
+}
+
+/// Parses an RFC 3339 exchange timestamp into UTC.
+pub fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| anyhow!("Invalid timestamp {}: {}", timestamp, e))
+}
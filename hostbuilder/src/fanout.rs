@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+use crate::{
+    events::MarketEvent,
+    feeds::FeedManager,
+    sinks::{BookContext, EventSink},
+};
+
+/// Level 3 book snapshots and deltas.
+pub const BOOK_CHANNEL: &str = "book";
+/// Level 2 book snapshots and level updates.
+pub const DEPTH_CHANNEL: &str = "depth";
+/// Trades.
+pub const TRADE_CHANNEL: &str = "trade";
+/// Venue tickers.
+pub const TICKER_CHANNEL: &str = "ticker";
+/// Candles and their closes.
+pub const CANDLE_CHANNEL: &str = "candle";
+/// Instrument reference data.
+pub const INSTRUMENT_CHANNEL: &str = "instrument";
+
+/// Updates a stream buffers for its slowest subscriber.
+pub const STREAM_CAPACITY: usize = 4096;
+
+/// (exchange, symbol, channel)
+pub type StreamKey = (String, String, String);
+
+/// An event on a downstream stream. Book updates carry the book's sequence
+/// number; trades are numbered per stream.
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamUpdate {
+    pub sequence: u64,
+    pub event: MarketEvent,
+}
+
+struct Stream {
+    sender: Sender<Arc<StreamUpdate>>,
+    sequence: u64,
+}
+
+/// Fans applied events out to downstream subscribers. Each stream is a bounded
+/// broadcast, so publishing never waits on a subscriber; one that falls more than
+/// `capacity` updates behind sees its receiver lag and must resnapshot. A stream
+/// is dropped once its last subscriber has gone.
+pub struct FanoutHub {
+    streams: RwLock<HashMap<StreamKey, Stream>>,
+    capacity: usize,
+}
+
+impl FanoutHub {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            streams: RwLock::new(HashMap::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Subscribes to `channel` of `exchange`/`symbol`, creating the stream on first
+    /// use. Symbols `feeds` does not currently feed are rejected, so clients cannot
+    /// create streams that will never carry anything.
+    pub fn subscribe(
+        &self,
+        feeds: &FeedManager,
+        exchange: &str,
+        symbol: &str,
+        channel: &str,
+    ) -> Result<Receiver<Arc<StreamUpdate>>> {
+        if ![
+            BOOK_CHANNEL,
+            DEPTH_CHANNEL,
+            TRADE_CHANNEL,
+            TICKER_CHANNEL,
+            CANDLE_CHANNEL,
+            INSTRUMENT_CHANNEL,
+        ]
+        .contains(&channel)
+        {
+            return Err(anyhow!("Unknown channel: {}", channel));
+        }
+        if !feeds.is_subscribed(exchange, symbol) {
+            return Err(anyhow!("{} {} is not configured", exchange, symbol));
+        }
+
+        let mut streams = self
+            .streams
+            .write()
+            .map_err(|_| anyhow!("Fan-out stream lock poisoned"))?;
+        streams.retain(|_, stream| stream.sender.receiver_count() > 0);
+        let stream = streams
+            .entry((
+                exchange.to_string(),
+                symbol.to_string(),
+                channel.to_string(),
+            ))
+            .or_insert_with(|| Stream {
+                sender: broadcast::channel(self.capacity).0,
+                sequence: 0,
+            });
+        Ok(stream.sender.subscribe())
+    }
+
+    /// Number of subscribers per stream.
+    pub fn subscribers(&self) -> Vec<(StreamKey, usize)> {
+        self.streams
+            .read()
+            .map(|streams| {
+                streams
+                    .iter()
+                    .map(|(key, stream)| (key.clone(), stream.sender.receiver_count()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl EventSink for FanoutHub {
+    fn name(&self) -> &str {
+        "fanout"
+    }
+
+    async fn handle(&self, _context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+        let mut streams = self
+            .streams
+            .write()
+            .map_err(|_| anyhow!("Fan-out stream lock poisoned"))?;
+
+        for event in events {
+            let (channel, sequence) = match event {
+                MarketEvent::OrderAdded(order)
+                | MarketEvent::OrderModified(order)
+                | MarketEvent::OrderDeleted(order) => (BOOK_CHANNEL, order.sequence),
+                MarketEvent::BookSnapshot(snapshot) => (BOOK_CHANNEL, snapshot.sequence),
+                MarketEvent::LevelUpdated(level) => (DEPTH_CHANNEL, level.sequence),
+                MarketEvent::LevelSnapshot(snapshot) => (DEPTH_CHANNEL, snapshot.sequence),
+                MarketEvent::Trade(_) => (TRADE_CHANNEL, None),
+                MarketEvent::Ticker(_) => (TICKER_CHANNEL, None),
+                MarketEvent::Candle(_) | MarketEvent::CandleClosed(_) => (CANDLE_CHANNEL, None),
+                MarketEvent::Instrument(_) => (INSTRUMENT_CHANNEL, None),
+                MarketEvent::Account(_) | MarketEvent::Heartbeat(_) | MarketEvent::Status(_) => {
+                    continue
+                }
+            };
+            let Some(symbol) = event.symbol() else {
+                continue;
+            };
+
+            let key = (
+                event.exchange().to_string(),
+                symbol.to_string(),
+                channel.to_string(),
+            );
+            let Some(stream) = streams.get_mut(&key) else {
+                continue;
+            };
+            stream.sequence = sequence.unwrap_or(stream.sequence + 1);
+            // Sending only fails when nobody is subscribed any more.
+            let sent = stream.sender.send(Arc::new(StreamUpdate {
+                sequence: stream.sequence,
+                event: event.clone(),
+            }));
+            if sent.is_err() {
+                streams.remove(&key);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,569 @@
+use anyhow::anyhow;
+use databaseschema::{
+    models::{Exchange as DbExchange, OrderBook},
+    CustomAsyncPgConnectionManager,
+};
+use deadpool::managed::Pool;
+use deadpool_redis::{Connection, Manager};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    sync::{broadcast, Mutex},
+    task::JoinHandle,
+    time::{timeout, timeout_at, Instant},
+};
+use tracing::{error, info};
+
+use crate::{
+    config::{Api, Exchange, Websocket},
+    connector::{ConnectorRegistry, ExchangeConnector},
+    endpoint::EndpointHandler,
+    events::{BookSnapshot, LevelSnapshot, MarketEvent},
+    get_info::{get_exchange, get_orderbooks, get_securities},
+    order_books::OrderBookRegistry,
+    processor::MarketDataProcessor,
+    sinks::redis_keys::RedisKeys,
+};
+
+/// How long a connection removed at runtime gets to unsubscribe and apply the
+/// frames it has read.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum FeedError {
+    #[error("Unknown exchange: {0}")]
+    UnknownExchange(String),
+    #[error("{0} is already subscribed on {1}")]
+    AlreadySubscribed(String, String),
+    #[error("{0} is not subscribed on {1}")]
+    NotSubscribed(String, String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Every connection the engine keeps to the venues, and the symbols and channels
+/// they carry. Symbols and channels can be added and removed while the engine
+/// runs; changes are applied one at a time.
+pub struct FeedManager {
+    registry: ConnectorRegistry,
+    redis_pool: Arc<Pool<Manager, Connection>>,
+    redis_keys: RedisKeys,
+    postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+    order_books: Arc<OrderBookRegistry>,
+    processor: Arc<MarketDataProcessor>,
+    feeds: Mutex<BTreeMap<String, ExchangeFeed>>,
+    /// The exchanges as currently fed, for readers that cannot wait on `feeds`.
+    exchanges: RwLock<Vec<Exchange>>,
+}
+
+struct ExchangeFeed {
+    config: Exchange,
+    connector: Arc<dyn ExchangeConnector>,
+    db_exchange: DbExchange,
+    order_books: Vec<OrderBook>,
+    channels: Vec<ChannelFeed>,
+}
+
+/// The connections of one channel, each carrying up to `shard_size` symbols.
+/// An account channel has exactly one connection, kept open whatever symbols
+/// it carries, since its data does not depend on them.
+struct ChannelFeed {
+    websocket: Websocket,
+    account: bool,
+    shard_size: usize,
+    shards: Vec<Shard>,
+    next_shard: usize,
+}
+
+struct Shard {
+    handler: Arc<dyn EndpointHandler + Send + Sync>,
+    stop: broadcast::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl FeedManager {
+    pub fn new(
+        registry: ConnectorRegistry,
+        redis_pool: Arc<Pool<Manager, Connection>>,
+        redis_keys: RedisKeys,
+        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+        order_books: Arc<OrderBookRegistry>,
+        processor: Arc<MarketDataProcessor>,
+    ) -> Self {
+        Self {
+            registry,
+            redis_pool,
+            redis_keys,
+            postgres_pool,
+            order_books,
+            processor,
+            feeds: Mutex::new(BTreeMap::new()),
+            exchanges: RwLock::new(vec![]),
+        }
+    }
+
+    /// The exchanges as currently fed, with the symbols and channels added or
+    /// removed since startup.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges
+            .read()
+            .map(|exchanges| exchanges.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether `symbol` is currently fed on `exchange`.
+    pub fn is_subscribed(&self, exchange: &str, symbol: &str) -> bool {
+        self.exchanges
+            .read()
+            .map(|exchanges| {
+                exchanges.iter().any(|configured| {
+                    configured.exchange == exchange
+                        && configured.symbols.iter().any(|s| s == symbol)
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    /// Authenticates with `exchange` and connects every channel it lists.
+    pub async fn add_exchange(&self, exchange: &Exchange) -> Result<(), FeedError> {
+        let mut feeds = self.feeds.lock().await;
+        if feeds.contains_key(&exchange.exchange) {
+            return Err(anyhow!("{} is already connected", exchange.exchange).into());
+        }
+        let connector = self
+            .registry
+            .create(exchange)
+            .ok_or_else(|| FeedError::UnknownExchange(exchange.exchange.clone()))?;
+
+        let db_exchange = get_exchange(
+            self.redis_pool.clone(),
+            &self.redis_keys,
+            self.postgres_pool.clone(),
+            exchange.exchange.clone(),
+        )
+        .await;
+
+        connector.authenticate().await?;
+
+        let securities = get_securities(
+            self.redis_pool.clone(),
+            &self.redis_keys,
+            self.postgres_pool.clone(),
+            &exchange.symbols,
+        )
+        .await;
+        let order_books = get_orderbooks(&self.order_books, securities, &db_exchange).await;
+
+        let mut feed = ExchangeFeed {
+            config: exchange.clone(),
+            connector,
+            db_exchange,
+            order_books,
+            channels: vec![],
+        };
+        self.load_instruments(feed.connector.as_ref(), &feed.order_books)
+            .await;
+        for websocket in exchange.websockets() {
+            let channel = self.start_channel(&feed, websocket).await?;
+            feed.channels.push(channel);
+        }
+
+        feeds.insert(exchange.exchange.clone(), feed);
+        self.publish(&feeds);
+        Ok(())
+    }
+
+    /// Closes every connection to `exchange` and clears the books they fed.
+    pub async fn remove_exchange(&self, exchange: &str) -> Result<(), FeedError> {
+        let mut feeds = self.feeds.lock().await;
+        let feed = feeds
+            .remove(exchange)
+            .ok_or_else(|| FeedError::UnknownExchange(exchange.to_string()))?;
+
+        for shard in feed.channels.into_iter().flat_map(|channel| channel.shards) {
+            shard.stop().await;
+        }
+        for order_book in feed.order_books.iter() {
+            self.clear_book(feed.connector.as_ref(), order_book).await;
+        }
+
+        info!("Removed {}", exchange);
+        self.publish(&feeds);
+        Ok(())
+    }
+
+    /// Subscribes every channel of `exchange` to `symbol`, creating its security
+    /// and order book if needed. The symbol joins a connection with room for it,
+    /// or a new one.
+    pub async fn add_symbol(&self, exchange: &str, symbol: &str) -> Result<(), FeedError> {
+        let mut feeds = self.feeds.lock().await;
+        let feed = feeds
+            .get_mut(exchange)
+            .ok_or_else(|| FeedError::UnknownExchange(exchange.to_string()))?;
+        if feed.config.symbols.iter().any(|s| s == symbol) {
+            return Err(FeedError::AlreadySubscribed(
+                symbol.to_string(),
+                exchange.to_string(),
+            ));
+        }
+
+        let securities = get_securities(
+            self.redis_pool.clone(),
+            &self.redis_keys,
+            self.postgres_pool.clone(),
+            &vec![symbol.to_string()],
+        )
+        .await;
+        let order_book = get_orderbooks(&self.order_books, securities, &feed.db_exchange)
+            .await
+            .pop()
+            .ok_or_else(|| anyhow!("No order book for {} on {}", symbol, exchange))?;
+        self.load_instruments(feed.connector.as_ref(), std::slice::from_ref(&order_book))
+            .await;
+
+        for channel in feed.channels.iter_mut() {
+            channel
+                .add(&feed.connector, &self.processor, order_book.clone())
+                .await?;
+        }
+
+        info!("Added {} on {}", symbol, exchange);
+        feed.config.symbols.push(symbol.to_string());
+        feed.order_books.push(order_book);
+        self.publish(&feeds);
+        Ok(())
+    }
+
+    /// Unsubscribes every channel of `exchange` from `symbol`, closes market data
+    /// connections left without symbols and clears the symbol's book.
+    pub async fn remove_symbol(&self, exchange: &str, symbol: &str) -> Result<(), FeedError> {
+        let mut feeds = self.feeds.lock().await;
+        let feed = feeds
+            .get_mut(exchange)
+            .ok_or_else(|| FeedError::UnknownExchange(exchange.to_string()))?;
+        if !feed.config.symbols.iter().any(|s| s == symbol) {
+            return Err(FeedError::NotSubscribed(
+                symbol.to_string(),
+                exchange.to_string(),
+            ));
+        }
+
+        for channel in feed.channels.iter_mut() {
+            channel.remove(symbol).await?;
+        }
+
+        if let Some(order_book) = feed
+            .order_books
+            .iter()
+            .find(|order_book| order_book.symbol == symbol)
+        {
+            self.clear_book(feed.connector.as_ref(), order_book).await;
+        }
+
+        info!("Removed {} from {}", symbol, exchange);
+        feed.config.symbols.retain(|s| s != symbol);
+        feed.order_books
+            .retain(|order_book| order_book.symbol != symbol);
+        self.publish(&feeds);
+        Ok(())
+    }
+
+    /// Connects `websocket` for every symbol of `exchange`.
+    pub async fn add_channel(
+        &self,
+        exchange: &str,
+        websocket: &Websocket,
+    ) -> Result<(), FeedError> {
+        let mut feeds = self.feeds.lock().await;
+        let feed = feeds
+            .get_mut(exchange)
+            .ok_or_else(|| FeedError::UnknownExchange(exchange.to_string()))?;
+        if feed
+            .channels
+            .iter()
+            .any(|channel| channel.websocket.channel == websocket.channel)
+        {
+            return Err(FeedError::AlreadySubscribed(
+                websocket.channel.clone(),
+                exchange.to_string(),
+            ));
+        }
+
+        // Fail on a channel the venue does not support before connecting to it.
+        feed.connector
+            .subscribe_messages(websocket, &feed.config.symbols)
+            .await?;
+
+        let channel = self.start_channel(feed, websocket).await?;
+        feed.channels.push(channel);
+        match feed.config.apis.last_mut() {
+            Some(api) => api.websockets.push(websocket.clone()),
+            None => feed.config.apis.push(Api {
+                websockets: vec![websocket.clone()],
+            }),
+        }
+
+        info!("Added {} channel on {}", websocket.channel, exchange);
+        self.publish(&feeds);
+        Ok(())
+    }
+
+    /// Closes every connection of `channel` on `exchange`. Books the channel fed
+    /// are invalidated, since nothing keeps them current any more.
+    pub async fn remove_channel(&self, exchange: &str, channel: &str) -> Result<(), FeedError> {
+        let mut feeds = self.feeds.lock().await;
+        let feed = feeds
+            .get_mut(exchange)
+            .ok_or_else(|| FeedError::UnknownExchange(exchange.to_string()))?;
+        let index = feed
+            .channels
+            .iter()
+            .position(|feed| feed.websocket.channel == channel)
+            .ok_or_else(|| FeedError::NotSubscribed(channel.to_string(), exchange.to_string()))?;
+
+        let removed = feed.channels.remove(index);
+        for shard in removed.shards {
+            shard.stop().await;
+        }
+        if let Some(kind) = feed.connector.book_kind(&removed.websocket) {
+            self.processor
+                .books()
+                .invalidate(exchange, &feed.config.symbols, kind);
+        }
+
+        for api in feed.config.apis.iter_mut() {
+            api.websockets
+                .retain(|websocket| websocket.channel != channel);
+        }
+        feed.config.apis.retain(|api| !api.websockets.is_empty());
+
+        info!("Removed {} channel from {}", channel, exchange);
+        self.publish(&feeds);
+        Ok(())
+    }
+
+    /// Stops every connection, giving up on those still busy at `deadline`.
+    pub async fn stop(&self, deadline: Instant) {
+        let mut feeds = self.feeds.lock().await;
+        let shards: Vec<Shard> = feeds
+            .values_mut()
+            .flat_map(|feed| feed.channels.drain(..))
+            .flat_map(|channel| channel.shards)
+            .collect();
+
+        for shard in shards.iter() {
+            let _ = shard.stop.send(());
+        }
+        for shard in shards {
+            if timeout_at(deadline, shard.task).await.is_err() {
+                error!("A feed did not unsubscribe before the shutdown deadline");
+            }
+        }
+    }
+
+    async fn start_channel(
+        &self,
+        feed: &ExchangeFeed,
+        websocket: &Websocket,
+    ) -> Result<ChannelFeed, FeedError> {
+        // One connection carries every symbol, unless the venue caps the symbols
+        // per connection, in which case they are spread over as few connections
+        // as the cap allows. An account channel always gets exactly one.
+        let account = feed.connector.is_account_channel(websocket);
+        let shard_size = if account {
+            usize::MAX
+        } else {
+            websocket
+                .max_symbols_per_connection
+                .or_else(|| feed.connector.max_symbols_per_connection(websocket))
+                .unwrap_or(usize::MAX)
+                .max(1)
+        };
+        let mut channel = ChannelFeed {
+            websocket: websocket.clone(),
+            account,
+            shard_size,
+            shards: vec![],
+            next_shard: 0,
+        };
+
+        info!("Connecting to {:?}", &websocket.endpoint);
+        if account {
+            channel
+                .spawn(&feed.connector, &self.processor, feed.order_books.clone())
+                .await?;
+            return Ok(channel);
+        }
+        for order_books in feed.order_books.chunks(shard_size) {
+            channel
+                .spawn(&feed.connector, &self.processor, order_books.to_vec())
+                .await?;
+        }
+        Ok(channel)
+    }
+
+    /// Passes the venue's reference data for each of `order_books` through the
+    /// processor, so their books are rounded to its precision from the first
+    /// snapshot and every sink records it.
+    async fn load_instruments(&self, connector: &dyn ExchangeConnector, order_books: &[OrderBook]) {
+        for order_book in order_books {
+            let Some(instrument) = connector.instrument(&order_book.symbol) else {
+                continue;
+            };
+            let event = MarketEvent::Instrument(Box::new(instrument));
+            if let Err(e) = self
+                .processor
+                .process(connector, order_book, vec![event])
+                .await
+            {
+                error!("Failed to load instrument for {}: {}", order_book.symbol, e);
+            }
+        }
+    }
+
+    /// Empties the books of `order_book` through the processor, so every sink
+    /// sees their orders and levels deleted, then discards them along with the
+    /// symbol's open candles.
+    async fn clear_book(&self, connector: &dyn ExchangeConnector, order_book: &OrderBook) {
+        let books = self.processor.books();
+        let (exchange, symbol) = (&order_book.exchange, &order_book.symbol);
+        let mut empty = vec![];
+        if books.with_book(exchange, symbol, |_| ()).is_some() {
+            empty.push(MarketEvent::BookSnapshot(BookSnapshot {
+                exchange: exchange.clone(),
+                symbol: symbol.clone(),
+                bids: vec![],
+                asks: vec![],
+                sequence: None,
+                checksum: None,
+            }));
+        }
+        if books.with_level_book(exchange, symbol, |_| ()).is_some() {
+            empty.push(MarketEvent::LevelSnapshot(LevelSnapshot {
+                exchange: exchange.clone(),
+                symbol: symbol.clone(),
+                bids: vec![],
+                asks: vec![],
+                depth: None,
+                sequence: None,
+                checksum: None,
+            }));
+        }
+
+        for event in empty {
+            if let Err(e) = self
+                .processor
+                .process(connector, order_book, vec![event])
+                .await
+            {
+                error!("Failed to clear book for {}: {}", symbol, e);
+            }
+        }
+        books.remove(exchange, symbol);
+        self.processor.candles().remove(exchange, symbol);
+    }
+
+    fn publish(&self, feeds: &BTreeMap<String, ExchangeFeed>) {
+        if let Ok(mut exchanges) = self.exchanges.write() {
+            *exchanges = feeds.values().map(|feed| feed.config.clone()).collect();
+        }
+    }
+}
+
+impl ChannelFeed {
+    /// Adds `order_book` to a connection with room for it, or opens a new one.
+    async fn add(
+        &mut self,
+        connector: &Arc<dyn ExchangeConnector>,
+        processor: &Arc<MarketDataProcessor>,
+        order_book: OrderBook,
+    ) -> Result<(), FeedError> {
+        let shard_size = self.shard_size;
+        match self
+            .shards
+            .iter()
+            .find(|shard| shard.handler.symbols().len() < shard_size)
+        {
+            Some(shard) => shard.handler.subscribe(vec![order_book]).await?,
+            None => self.spawn(connector, processor, vec![order_book]).await?,
+        }
+        Ok(())
+    }
+
+    /// Unsubscribes the connections carrying `symbol` and closes those left
+    /// without symbols, unless they carry an account channel.
+    async fn remove(&mut self, symbol: &str) -> Result<(), FeedError> {
+        let symbols = vec![symbol.to_string()];
+        for shard in self.shards.iter() {
+            if shard.handler.symbols().contains(&symbols[0]) {
+                shard.handler.unsubscribe(&symbols).await?;
+            }
+        }
+        if self.account {
+            return Ok(());
+        }
+
+        let (empty, shards): (Vec<Shard>, Vec<Shard>) = std::mem::take(&mut self.shards)
+            .into_iter()
+            .partition(|shard| shard.handler.symbols().is_empty());
+        self.shards = shards;
+        for shard in empty {
+            shard.stop().await;
+        }
+        Ok(())
+    }
+
+    async fn spawn(
+        &mut self,
+        connector: &Arc<dyn ExchangeConnector>,
+        processor: &Arc<MarketDataProcessor>,
+        order_books: Vec<OrderBook>,
+    ) -> Result<(), FeedError> {
+        let count = order_books.len();
+        let handler = connector
+            .clone()
+            .connect(
+                &self.websocket,
+                self.next_shard,
+                order_books,
+                processor.clone(),
+            )
+            .await?;
+
+        info!(
+            "Listening to {} {} for {} symbols on connection {}",
+            connector.exchange(),
+            self.websocket.channel,
+            count,
+            self.next_shard
+        );
+        let (stop, stop_rx) = broadcast::channel(1);
+        let listener = handler.clone();
+        let task = tokio::spawn(async move {
+            if let Err(e) = listener.listen(stop_rx).await {
+                error!("Failed to listen: {}", e);
+            }
+        });
+
+        self.shards.push(Shard {
+            handler,
+            stop,
+            task,
+        });
+        self.next_shard += 1;
+        Ok(())
+    }
+}
+
+impl Shard {
+    async fn stop(self) {
+        let _ = self.stop.send(());
+        if timeout(STOP_TIMEOUT, self.task).await.is_err() {
+            error!("A feed did not unsubscribe within {:?}", STOP_TIMEOUT);
+        }
+    }
+}
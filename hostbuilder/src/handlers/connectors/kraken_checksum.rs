@@ -0,0 +1,185 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+
+use crate::{
+    book::{level2::Level2Book, level3::Level3Book},
+    events::Side,
+};
+
+/// Number of price levels per side covered by Kraken's book checksum.
+pub const CHECKSUM_LEVELS: usize = 10;
+
+/// Decimal places Kraken uses when formatting a pair's prices and quantities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Precision {
+    pub price: u32,
+    pub quantity: u32,
+}
+
+/// Kraken's Level 3 checksum: CRC32 over every order in the top ten ask levels
+/// (lowest first) followed by the top ten bid levels (highest first), each order
+/// contributing its formatted price then quantity.
+pub fn level3_checksum(book: &Level3Book, precision: Precision) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+
+    for side in [Side::Sell, Side::Buy] {
+        for order in book.top_orders(side, CHECKSUM_LEVELS) {
+            hasher.update(format_value(&order.price, precision.price).as_bytes());
+            hasher.update(format_value(&order.quantity, precision.quantity).as_bytes());
+        }
+    }
+
+    hasher.finalize()
+}
+
+/// Kraken's Level 2 checksum: CRC32 over the top ten ask levels (lowest first)
+/// followed by the top ten bid levels (highest first), each level contributing
+/// its formatted price then quantity.
+pub fn level2_checksum(book: &Level2Book, precision: Precision) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+
+    for side in [Side::Sell, Side::Buy] {
+        for level in book.depth(side, CHECKSUM_LEVELS) {
+            hasher.update(format_value(&level.price, precision.price).as_bytes());
+            hasher.update(format_value(&level.quantity, precision.quantity).as_bytes());
+        }
+    }
+
+    hasher.finalize()
+}
+
+/// Formats `value` to `decimals` places, then drops the decimal point and any
+/// leading zeros, as Kraken does before hashing.
+pub fn format_value(value: &BigDecimal, decimals: u32) -> String {
+    let (digits, _) = value
+        .with_scale_round(decimals as i64, RoundingMode::HalfEven)
+        .as_bigint_and_exponent();
+    digits.to_string().trim_start_matches('0').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{BookLevel, BookOrder, BookSnapshot, LevelSnapshot, MarketEvent};
+    use chrono::Utc;
+
+    /// The BTC/USD book from Kraken's book checksum guide, prices to one decimal
+    /// place and quantities to eight, with its published checksum.
+    const BIDS: [(&str, &str); 10] = [
+        ("45283.5", "0.10000000"),
+        ("45283.4", "1.54582015"),
+        ("45282.1", "0.10000000"),
+        ("45281.0", "0.10000000"),
+        ("45280.3", "1.54592586"),
+        ("45279.0", "0.07990000"),
+        ("45277.6", "0.03310103"),
+        ("45277.5", "0.30000000"),
+        ("45277.3", "1.54602737"),
+        ("45276.6", "0.15445238"),
+    ];
+    const ASKS: [(&str, &str); 10] = [
+        ("45285.2", "0.00100000"),
+        ("45286.4", "1.54571953"),
+        ("45286.6", "1.54571109"),
+        ("45289.6", "1.54560911"),
+        ("45290.2", "0.15890660"),
+        ("45291.8", "1.54553491"),
+        ("45294.7", "0.04454749"),
+        ("45296.1", "0.35380000"),
+        ("45297.5", "0.09945542"),
+        ("45299.5", "0.18772827"),
+    ];
+    const CHECKSUM: u32 = 3310070434;
+    const PRECISION: Precision = Precision {
+        price: 1,
+        quantity: 8,
+    };
+
+    fn levels(levels: &[(&str, &str)]) -> Vec<BookLevel> {
+        levels
+            .iter()
+            .map(|(price, quantity)| BookLevel {
+                price: price.parse().unwrap(),
+                quantity: quantity.parse().unwrap(),
+            })
+            .collect()
+    }
+
+    fn orders(side: &str, levels: &[(&str, &str)]) -> Vec<BookOrder> {
+        levels
+            .iter()
+            .enumerate()
+            .map(|(index, (price, quantity))| BookOrder {
+                order_id: format!("{side}{index}"),
+                price: price.parse().unwrap(),
+                quantity: quantity.parse().unwrap(),
+                timestamp: Utc::now(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn format_value_drops_the_decimal_point_and_leading_zeros() {
+        let format = |value: &str, decimals| format_value(&value.parse().unwrap(), decimals);
+
+        assert_eq!(format("45285.2", 1), "452852");
+        assert_eq!(format("0.00100000", 8), "100000");
+        assert_eq!(format("0.001", 8), "100000");
+        assert_eq!(format("1.54582015", 8), "154582015");
+        assert_eq!(format("45281", 1), "452810");
+        assert_eq!(format("0.123456789", 8), "12345679");
+    }
+
+    #[test]
+    fn level3_checksum_hashes_asks_then_bids() {
+        let mut book = Level3Book::new("kraken", "BTC/USD");
+        book.apply_snapshot(&BookSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: orders("b", &BIDS),
+            asks: orders("a", &ASKS),
+            sequence: None,
+            checksum: None,
+        });
+
+        // With one order per level, every order contributes exactly what its
+        // level does in the Level 2 example.
+        assert_eq!(level3_checksum(&book, PRECISION), CHECKSUM);
+    }
+
+    #[test]
+    fn level3_checksum_covers_every_order_of_a_level_in_queue_order() {
+        let mut book = Level3Book::new("kraken", "BTC/USD");
+        book.apply_snapshot(&BookSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: orders("b", &[("45283.5", "0.1"), ("45283.5", "0.2")]),
+            asks: orders("a", &[("45285.2", "0.001")]),
+            sequence: None,
+            checksum: None,
+        });
+
+        let mut hasher = crc32fast::Hasher::new();
+        for value in [
+            "452852", "100000", "452835", "10000000", "452835", "20000000",
+        ] {
+            hasher.update(value.as_bytes());
+        }
+        assert_eq!(level3_checksum(&book, PRECISION), hasher.finalize());
+    }
+
+    #[test]
+    fn level2_checksum_matches_krakens_example() {
+        let mut book = Level2Book::new("kraken", "BTC/USD");
+        book.apply(&MarketEvent::LevelSnapshot(LevelSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: levels(&BIDS),
+            asks: levels(&ASKS),
+            depth: Some(10),
+            sequence: None,
+            checksum: None,
+        }));
+
+        assert_eq!(level2_checksum(&book, PRECISION), CHECKSUM);
+    }
+}
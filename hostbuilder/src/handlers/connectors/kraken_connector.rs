@@ -0,0 +1,486 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use databaseschema::models::OrderBook;
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+use tracing::{info, warn};
+
+use crate::{
+    book::{level2::Level2Book, level3::Level3Book, BookKind},
+    config::{Exchange, Websocket},
+    connector::ExchangeConnector,
+    endpoint::EndpointHandler,
+    events::{Instrument, InstrumentStatus, MarketEvent},
+    handlers::{
+        connectors::kraken_checksum::{level2_checksum, level3_checksum, Precision},
+        rest::{kraken_rest_api::KrakenRestHandler, kraken_token_manager::KrakenTokenManager},
+        structs::responses::parse_message,
+        translators::kraken_translator::{increment, instrument_status, KrakenTranslator},
+        websockets::kraken_websocket_handler::KrakenWebSocketHandler,
+    },
+    processor::MarketDataProcessor,
+};
+
+/// Kraken limits how many symbols one connection may subscribe to on the
+/// authenticated `level3` channel.
+const LEVEL3_SYMBOLS_PER_CONNECTION: usize = 200;
+
+/// Depths the public `book` channel can be subscribed at.
+const BOOK_DEPTHS: &[u32] = &[10, 25, 100, 500, 1000];
+/// Depth Kraken uses when a `book` subscription does not name one.
+const DEFAULT_BOOK_DEPTH: u32 = 10;
+
+/// Candle intervals, in minutes, the `ohlc` channel can be subscribed at.
+const OHLC_INTERVALS: &[u32] = &[1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+/// Interval Kraken uses when an `ohlc` subscription does not name one.
+const DEFAULT_OHLC_INTERVAL: u32 = 1;
+
+pub struct KrakenConnector {
+    exchange: Exchange,
+    tokens: Arc<KrakenTokenManager>,
+    translator: KrakenTranslator,
+    precisions: RwLock<HashMap<String, Precision>>,
+    /// Reference data of every pair, loaded from `AssetPairs` and kept current
+    /// by the `instrument` channel.
+    instruments: RwLock<HashMap<String, Instrument>>,
+    /// Symbols subscribed to the `instrument` channel, which Kraken only offers
+    /// for every pair at once.
+    instrument_symbols: RwLock<HashSet<String>>,
+    /// Depth each symbol was last subscribed to the `book` channel at, handed to
+    /// its book with every snapshot.
+    book_depths: RwLock<HashMap<String, usize>>,
+}
+
+impl KrakenConnector {
+    pub fn new(exchange: &Exchange) -> Self {
+        Self {
+            exchange: exchange.clone(),
+            tokens: Arc::new(KrakenTokenManager::new(&exchange.websocket_token)),
+            translator: KrakenTranslator::new(&exchange.exchange),
+            precisions: RwLock::new(HashMap::new()),
+            instruments: RwLock::new(HashMap::new()),
+            instrument_symbols: RwLock::new(HashSet::new()),
+            book_depths: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Loads the reference data of every pair, keyed by websocket v2 symbol, so
+    /// book checksums can be verified and prices rounded before the `instrument`
+    /// channel, if subscribed, sends its snapshot.
+    async fn load_instruments(&self) -> Result<()> {
+        let asset_pairs = KrakenRestHandler::asset_pairs(&self.exchange.websocket_token).await?;
+
+        let instruments: Vec<Instrument> = asset_pairs
+            .result
+            .values()
+            .filter_map(|pair| {
+                let symbol = v2_symbol(pair.wsname.as_ref()?);
+                let (base, quote) = symbol.split_once('/')?;
+                let lot_size = increment(pair.lot_decimals);
+                Some(Instrument {
+                    exchange: self.exchange.exchange.clone(),
+                    symbol: symbol.clone(),
+                    base: base.to_string(),
+                    quote: quote.to_string(),
+                    status: pair
+                        .status
+                        .as_deref()
+                        .map(instrument_status)
+                        .unwrap_or(InstrumentStatus::Online),
+                    price_precision: pair.pair_decimals,
+                    quantity_precision: pair.lot_decimals,
+                    tick_size: pair
+                        .tick_size
+                        .clone()
+                        .unwrap_or_else(|| increment(pair.pair_decimals)),
+                    min_order_quantity: pair.ordermin.clone().unwrap_or_else(|| lot_size.clone()),
+                    lot_size,
+                    min_order_cost: pair.costmin.clone(),
+                    marginable: !pair.leverage_buy.is_empty(),
+                    timestamp: Utc::now(),
+                })
+            })
+            .collect();
+
+        info!(
+            "Loaded reference data for {} Kraken pairs",
+            instruments.len()
+        );
+        self.update_instruments(instruments.iter())
+    }
+
+    /// Records `instruments` as the current reference data of their pairs.
+    fn update_instruments<'a>(
+        &self,
+        instruments: impl Iterator<Item = &'a Instrument>,
+    ) -> Result<()> {
+        let mut precisions = self
+            .precisions
+            .write()
+            .map_err(|_| anyhow!("Kraken precision lock poisoned"))?;
+        let mut known = self
+            .instruments
+            .write()
+            .map_err(|_| anyhow!("Kraken instrument lock poisoned"))?;
+
+        for instrument in instruments {
+            precisions.insert(
+                instrument.symbol.clone(),
+                Precision {
+                    price: instrument.price_precision,
+                    quantity: instrument.quantity_precision,
+                },
+            );
+            known.insert(instrument.symbol.clone(), instrument.clone());
+        }
+        Ok(())
+    }
+
+    /// A websocket token for channels that require authentication.
+    async fn token(&self) -> Result<String> {
+        Ok(self.tokens.token().await?)
+    }
+}
+
+#[async_trait]
+impl ExchangeConnector for KrakenConnector {
+    fn exchange(&self) -> &str {
+        &self.exchange.exchange
+    }
+
+    fn channels(&self) -> &[&str] {
+        &[
+            "level3",
+            "book",
+            "trade",
+            "ticker",
+            "ohlc",
+            "instrument",
+            "executions",
+            "balances",
+        ]
+    }
+
+    fn is_account_channel(&self, websocket: &Websocket) -> bool {
+        matches!(websocket.channel.as_str(), "executions" | "balances")
+    }
+
+    fn depths(&self, channel: &str) -> &[u32] {
+        match channel {
+            "book" => BOOK_DEPTHS,
+            _ => &[],
+        }
+    }
+
+    fn intervals(&self, channel: &str) -> &[u32] {
+        match channel {
+            "ohlc" => OHLC_INTERVALS,
+            _ => &[],
+        }
+    }
+
+    fn book_kind(&self, websocket: &Websocket) -> Option<BookKind> {
+        match websocket.channel.as_str() {
+            "level3" => Some(BookKind::Level3),
+            "book" => Some(BookKind::Level2),
+            _ => None,
+        }
+    }
+
+    async fn authenticate(&self) -> Result<()> {
+        self.tokens.start().await?;
+
+        if let Err(e) = self.load_instruments().await {
+            warn!(
+                "Kraken book checksums will not be verified until instruments arrive: {}",
+                e
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe_messages(
+        &self,
+        websocket: &Websocket,
+        symbols: &[String],
+    ) -> Result<Vec<Value>> {
+        match websocket.channel.as_str() {
+            "level3" => Ok(vec![json!({
+                "method": "subscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "depth": 10,
+                    "snapshot": true,
+                    "token": self.token().await?,
+                }
+            })]),
+            "book" => {
+                let depth = websocket.depth.unwrap_or(DEFAULT_BOOK_DEPTH);
+                if !BOOK_DEPTHS.contains(&depth) {
+                    return Err(anyhow!("Unsupported book depth: {}", depth));
+                }
+                if let Ok(mut depths) = self.book_depths.write() {
+                    for symbol in symbols {
+                        depths.insert(symbol.clone(), depth as usize);
+                    }
+                }
+                Ok(vec![json!({
+                    "method": "subscribe",
+                    "params": {
+                        "channel": &websocket.channel,
+                        "symbol": symbols,
+                        "depth": depth,
+                        "snapshot": true
+                    }
+                })])
+            }
+            "trade" => Ok(vec![json!({
+                "method": "subscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "snapshot": true
+                }
+            })]),
+            // Triggered on every change of the best bid or offer rather than on
+            // trades, so the ticker can be checked against the books.
+            "ticker" => Ok(vec![json!({
+                "method": "subscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "event_trigger": "bbo",
+                    "snapshot": true
+                }
+            })]),
+            "ohlc" => ohlc_messages("subscribe", websocket, symbols),
+            "instrument" => {
+                if let Ok(mut subscribed) = self.instrument_symbols.write() {
+                    subscribed.extend(symbols.iter().cloned());
+                }
+                Ok(vec![json!({
+                    "method": "subscribe",
+                    "params": {
+                        "channel": &websocket.channel,
+                        "snapshot": true
+                    }
+                })])
+            }
+            // Open orders and the latest fills, so the account state is complete
+            // after every reconnect.
+            "executions" => Ok(vec![json!({
+                "method": "subscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "snap_orders": true,
+                    "snap_trades": true,
+                    "token": self.token().await?,
+                }
+            })]),
+            "balances" => Ok(vec![json!({
+                "method": "subscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "snapshot": true,
+                    "token": self.token().await?,
+                }
+            })]),
+            channel => Err(anyhow!("Unknown websocket type: {}", channel)),
+        }
+    }
+
+    async fn unsubscribe_messages(
+        &self,
+        websocket: &Websocket,
+        symbols: &[String],
+    ) -> Result<Vec<Value>> {
+        match websocket.channel.as_str() {
+            "level3" => Ok(vec![json!({
+                "method": "unsubscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "token": self.token().await?,
+                }
+            })]),
+            "book" => Ok(vec![json!({
+                "method": "unsubscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "depth": websocket.depth.unwrap_or(DEFAULT_BOOK_DEPTH),
+                }
+            })]),
+            "trade" => Ok(vec![json!({
+                "method": "unsubscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "snapshot": true
+                }
+            })]),
+            "ticker" => Ok(vec![json!({
+                "method": "unsubscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "event_trigger": "bbo"
+                }
+            })]),
+            "ohlc" => ohlc_messages("unsubscribe", websocket, symbols),
+            // The subscription covers every pair, so it is only dropped with
+            // the last symbol.
+            "instrument" => {
+                let mut subscribed = self
+                    .instrument_symbols
+                    .write()
+                    .map_err(|_| anyhow!("Kraken instrument lock poisoned"))?;
+                for symbol in symbols {
+                    subscribed.remove(symbol);
+                }
+                if !subscribed.is_empty() {
+                    return Ok(vec![]);
+                }
+                Ok(vec![json!({
+                    "method": "unsubscribe",
+                    "params": {
+                        "channel": &websocket.channel
+                    }
+                })])
+            }
+            "executions" | "balances" => Ok(vec![json!({
+                "method": "unsubscribe",
+                "params": {
+                    "channel": &websocket.channel,
+                    "token": self.token().await?,
+                }
+            })]),
+            channel => Err(anyhow!("Unknown websocket type: {}", channel)),
+        }
+    }
+
+    fn parse_frame(&self, text: &str) -> Result<Vec<MarketEvent>> {
+        let mut events = self.translator.translate(parse_message(text)?)?;
+
+        for event in events.iter_mut() {
+            if let MarketEvent::LevelSnapshot(snapshot) = event {
+                snapshot.depth = self
+                    .book_depths
+                    .read()
+                    .ok()
+                    .and_then(|depths| depths.get(&snapshot.symbol).copied());
+            }
+        }
+
+        // Every pair's reference data is kept, but only that of the symbols
+        // subscribed is passed on.
+        if events
+            .iter()
+            .any(|event| matches!(event, MarketEvent::Instrument(_)))
+        {
+            self.update_instruments(events.iter().filter_map(|event| match event {
+                MarketEvent::Instrument(instrument) => Some(instrument.as_ref()),
+                _ => None,
+            }))?;
+            let subscribed = self
+                .instrument_symbols
+                .read()
+                .map_err(|_| anyhow!("Kraken instrument lock poisoned"))?;
+            events.retain(|event| match event {
+                MarketEvent::Instrument(instrument) => subscribed.contains(&instrument.symbol),
+                _ => true,
+            });
+        }
+
+        Ok(events)
+    }
+
+    fn instrument(&self, symbol: &str) -> Option<Instrument> {
+        self.instruments.read().ok()?.get(symbol).cloned()
+    }
+
+    fn book_checksum(&self, book: &Level3Book) -> Option<u32> {
+        let precision = *self.precisions.read().ok()?.get(book.symbol())?;
+        Some(level3_checksum(book, precision))
+    }
+
+    fn level_book_checksum(&self, book: &Level2Book) -> Option<u32> {
+        let precision = *self.precisions.read().ok()?.get(book.symbol())?;
+        Some(level2_checksum(book, precision))
+    }
+
+    fn max_symbols_per_connection(&self, websocket: &Websocket) -> Option<usize> {
+        match websocket.channel.as_str() {
+            "level3" => Some(LEVEL3_SYMBOLS_PER_CONNECTION),
+            _ => None,
+        }
+    }
+
+    async fn connect(
+        self: Arc<Self>,
+        websocket: &Websocket,
+        shard: usize,
+        order_books: Vec<OrderBook>,
+        processor: Arc<MarketDataProcessor>,
+    ) -> Result<Arc<dyn EndpointHandler + Send + Sync>> {
+        let handler = KrakenWebSocketHandler::new(websocket, shard, order_books, self, processor);
+
+        Ok(Arc::new(handler))
+    }
+}
+
+/// Converts a REST `wsname` such as `XBT/USD` to the websocket v2 symbol `BTC/USD`.
+fn v2_symbol(wsname: &str) -> String {
+    wsname
+        .split('/')
+        .map(|asset| match asset {
+            "XBT" => "BTC",
+            "XDG" => "DOGE",
+            asset => asset,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `ohlc` frames for `method`, one per interval since Kraken takes a single
+/// interval per frame. Each frame carries the symbols configured for its
+/// interval.
+fn ohlc_messages(method: &str, websocket: &Websocket, symbols: &[String]) -> Result<Vec<Value>> {
+    let mut by_interval: BTreeMap<u32, Vec<&String>> = BTreeMap::new();
+    for symbol in symbols {
+        let intervals = match websocket.intervals(symbol) {
+            [] => &[DEFAULT_OHLC_INTERVAL],
+            intervals => intervals,
+        };
+        for interval in intervals {
+            if !OHLC_INTERVALS.contains(interval) {
+                return Err(anyhow!("Unsupported ohlc interval: {}", interval));
+            }
+            by_interval.entry(*interval).or_default().push(symbol);
+        }
+    }
+
+    Ok(by_interval
+        .into_iter()
+        .map(|(interval, symbols)| {
+            let mut message = json!({
+                "method": method,
+                "params": {
+                    "channel": &websocket.channel,
+                    "symbol": symbols,
+                    "interval": interval,
+                }
+            });
+            if method == "subscribe" {
+                message["params"]["snapshot"] = json!(true);
+            }
+            message
+        })
+        .collect())
+}
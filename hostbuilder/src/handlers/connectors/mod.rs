@@ -0,0 +1,2 @@
+pub mod kraken_checksum;
+pub mod kraken_connector;
@@ -1,3 +1,5 @@
-pub mod websockets;
+pub mod connectors;
 pub mod rest;
-pub mod structs;
\ No newline at end of file
+pub mod structs;
+pub mod translators;
+pub mod websockets;
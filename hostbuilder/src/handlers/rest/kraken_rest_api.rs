@@ -12,26 +12,30 @@ use std::{
 };
 use url::Url;
 
-use crate::handlers::structs::responses::{parse_message, Response, TokenResponse};
+use crate::handlers::{
+    rest::kraken_token_manager::TokenError,
+    structs::responses::{parse_message, AssetPairsResponse, Response, TokenResponse},
+};
 
 pub struct KrakenRestHandler;
 
 impl KrakenRestHandler {
-    pub async fn authenticate(endpoint: &str) -> Result<TokenResponse> {
+    pub async fn authenticate(endpoint: &str) -> Result<TokenResponse, TokenError> {
         let nonce = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
+            .unwrap_or_default()
             .as_millis() as u64;
 
         let post_data = format!("nonce={}", nonce);
 
-        let url = Url::parse(endpoint).expect("Failed to parse URL");
+        let url = Url::parse(endpoint)?;
 
-        let api_secret = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+        let api_secret =
+            env::var("SECRET_KEY").map_err(|_| TokenError::MissingCredential("SECRET_KEY"))?;
 
-        let signature = generate_kraken_signature(url.path(), nonce, &post_data, &api_secret);
+        let signature = generate_kraken_signature(url.path(), nonce, &post_data, &api_secret)?;
         let client = Client::new();
-        let api_key = env::var("API_KEY").expect("API_KEY must be set");
+        let api_key = env::var("API_KEY").map_err(|_| TokenError::MissingCredential("API_KEY"))?;
 
         let body = post_data;
         // Create the headers
@@ -42,35 +46,64 @@ impl KrakenRestHandler {
             CONTENT_TYPE,
             HeaderValue::from_static("application/x-www-form-urlencoded"),
         );
-        
-        match client.post(url).headers(headers).body(body).send().await {
-            Ok(res) => {
-                let body = res.text().await.expect("Failed to parse API response");
 
-                match parse_message(&body) {
-                    Ok(Response::TokenResponse(api_response)) => {
-                        println!("Successfully received TokenResponse: {:?}", api_response);
-                        Ok(api_response)
-                    }
-                    Ok(other_response) => {
-                        // Return an error because we expected a TokenResponse
-                        Err(anyhow!(
-                            "Expected TokenResponse but got {:?}",
-                            other_response
-                        ))
-                    }
-                    Err(e) => {
-                        // Handle parsing error
-                        println!("Error: Failed to parse message: {}", e);
-                        Err(e.into())
-                    }
-                }
+        let body = client
+            .post(url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        // Kraken reports failures in the `error` array with an empty `result`.
+        let json: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| TokenError::UnexpectedResponse(e.to_string()))?;
+        if let Some(errors) = json.get("error").and_then(|error| error.as_array()) {
+            if !errors.is_empty() {
+                return Err(TokenError::Rejected(
+                    errors.iter().map(|error| error.to_string()).collect(),
+                ));
+            }
+        }
+
+        match parse_message(&body) {
+            Ok(Response::TokenResponse(api_response)) => {
+                println!(
+                    "Successfully received TokenResponse, expires in {}s",
+                    api_response.expires()
+                );
+                Ok(api_response)
             }
-            Err(e) => {
-                println!("Error: {:?}", e);
-                Err(e.into())
+            Ok(other_response) => {
+                // Return an error because we expected a TokenResponse
+                Err(TokenError::UnexpectedResponse(format!(
+                    "{:?}",
+                    other_response
+                )))
             }
+            Err(e) => Err(TokenError::UnexpectedResponse(e.to_string())),
+        }
+    }
+}
+
+impl KrakenRestHandler {
+    /// Fetches every tradable pair from the public `AssetPairs` endpoint on the same
+    /// host as `endpoint`.
+    pub async fn asset_pairs(endpoint: &str) -> Result<AssetPairsResponse> {
+        let url = Url::parse(endpoint)?.join("/0/public/AssetPairs")?;
+        let body = Client::new().get(url).send().await?.text().await?;
+        let json: serde_json::Value = serde_json::from_str(&body)?;
+        let asset_pairs = AssetPairsResponse::from_json(&json)?;
+
+        if !asset_pairs.error.is_empty() {
+            return Err(anyhow!(
+                "AssetPairs request failed: {:?}",
+                asset_pairs.error
+            ));
         }
+
+        Ok(asset_pairs)
     }
 }
 
@@ -80,15 +113,13 @@ fn generate_kraken_signature(
     nonce: u64,
     post_data: &str,
     api_secret: &str,
-) -> String {
+) -> Result<String, TokenError> {
     // 1. Calculate SHA256 of the nonce + POST data
     let mut sha256 = Sha256::new();
     sha256.update(nonce.to_string() + post_data);
     let sha256_hash = sha256.finalize();
     // 2. Decode the API secret from base64
-    let decoded_secret = BASE64_STANDARD
-        .decode(api_secret)
-        .expect("Failed to decode API secret from base64");
+    let decoded_secret = BASE64_STANDARD.decode(api_secret)?;
     // 3. Create an HMAC-SHA512 instance with the decoded key
     let mut mac =
         HmacSha512::new_from_slice(&decoded_secret).expect("HMAC can take key of any size");
@@ -98,5 +129,5 @@ fn generate_kraken_signature(
     // 4. Finalize the HMAC calculation and encode the result into base64
     let hmac_result = mac.finalize();
     let hmac_bytes = hmac_result.into_bytes();
-    BASE64_STANDARD.encode(hmac_bytes)
+    Ok(BASE64_STANDARD.encode(hmac_bytes))
 }
@@ -0,0 +1,186 @@
+use reqwest::header::InvalidHeaderValue;
+use std::{sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::{
+    sync::Mutex,
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+use tracing::{error, info};
+
+use crate::handlers::rest::kraken_rest_api::KrakenRestHandler;
+
+/// Tokens are replaced this long before Kraken stops accepting them, or halfway
+/// through their lifetime if that is shorter.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Shortest wait between refreshes, however short-lived Kraken's tokens are.
+const MIN_REFRESH_DELAY: Duration = Duration::from_secs(1);
+
+/// Wait before retrying a failed background refresh.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Why a websocket token could not be obtained.
+#[derive(Debug, Error)]
+pub enum TokenError {
+    #[error("{0} must be set")]
+    MissingCredential(&'static str),
+    #[error("API secret is not valid base64: {0}")]
+    InvalidSecret(#[from] base64::DecodeError),
+    #[error("invalid token endpoint: {0}")]
+    InvalidEndpoint(#[from] url::ParseError),
+    #[error("invalid request header: {0}")]
+    InvalidHeader(#[from] InvalidHeaderValue),
+    #[error("token request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Kraken rejected the token request: {}", .0.join(", "))]
+    Rejected(Vec<String>),
+    #[error("unexpected token response: {0}")]
+    UnexpectedResponse(String),
+}
+
+struct CachedToken {
+    token: String,
+    /// When the token is due to be replaced, ahead of its expiry.
+    refresh_at: Instant,
+}
+
+impl CachedToken {
+    fn is_fresh(&self) -> bool {
+        Instant::now() < self.refresh_at
+    }
+}
+
+/// Caches the `GetWebSocketsToken` token used by authenticated channels. Kraken
+/// only accepts a token for a limited time after it is issued, so the manager
+/// replaces it ahead of expiry and every (re)subscription asks it for a token
+/// instead of reusing the one fetched at startup.
+pub struct KrakenTokenManager {
+    endpoint: String,
+    token: Mutex<Option<CachedToken>>,
+    refresher: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl KrakenTokenManager {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            token: Mutex::new(None),
+            refresher: Mutex::new(None),
+        }
+    }
+
+    /// A token that is not yet due for replacement, fetching a new one if the
+    /// cached token is missing or about to expire.
+    pub async fn token(&self) -> Result<String, TokenError> {
+        let mut token = self.token.lock().await;
+        match token.as_ref() {
+            Some(cached) if cached.is_fresh() => Ok(cached.token.clone()),
+            _ => {
+                let cached = self.fetch().await?;
+                let value = cached.token.clone();
+                *token = Some(cached);
+                Ok(value)
+            }
+        }
+    }
+
+    /// Fetches a new token, replacing the cached one.
+    pub async fn refresh(&self) -> Result<String, TokenError> {
+        let mut token = self.token.lock().await;
+        let cached = self.fetch().await?;
+        let value = cached.token.clone();
+        *token = Some(cached);
+        Ok(value)
+    }
+
+    /// Fetches the first token and starts a task that refreshes it before it
+    /// expires, for as long as the manager is alive. Calling this again replaces
+    /// the previous refresh task.
+    pub async fn start(self: &Arc<Self>) -> Result<(), TokenError> {
+        self.refresh().await?;
+
+        let manager = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            loop {
+                let delay = match manager.upgrade() {
+                    Some(manager) => manager.until_refresh().await,
+                    None => return,
+                };
+                sleep(delay).await;
+
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                match manager.refresh().await {
+                    Ok(_) => info!("Refreshed Kraken websocket token"),
+                    Err(e) => {
+                        error!("Failed to refresh Kraken websocket token: {}", e);
+                        sleep(RETRY_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        if let Some(previous) = self.refresher.lock().await.replace(handle) {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    async fn until_refresh(&self) -> Duration {
+        match self.token.lock().await.as_ref() {
+            Some(cached) => cached.refresh_at.saturating_duration_since(Instant::now()),
+            None => Duration::ZERO,
+        }
+    }
+
+    async fn fetch(&self) -> Result<CachedToken, TokenError> {
+        let issued_at = Instant::now();
+        let response = KrakenRestHandler::authenticate(&self.endpoint).await?;
+        Ok(CachedToken {
+            token: response.get_token(),
+            refresh_at: issued_at + refresh_after(Duration::from_secs(response.expires())),
+        })
+    }
+}
+
+/// How long after issue a token valid for `lifetime` is replaced: `REFRESH_MARGIN`
+/// before it expires, but never sooner than halfway through its lifetime, so a
+/// short-lived token does not make the refresh loop spin.
+fn refresh_after(lifetime: Duration) -> Duration {
+    lifetime
+        .saturating_sub(REFRESH_MARGIN)
+        .max(lifetime / 2)
+        .max(MIN_REFRESH_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lived_tokens_are_refreshed_a_margin_before_expiry() {
+        assert_eq!(
+            refresh_after(Duration::from_secs(900)),
+            Duration::from_secs(840)
+        );
+    }
+
+    #[test]
+    fn short_lived_tokens_are_refreshed_halfway_through() {
+        assert_eq!(
+            refresh_after(Duration::from_secs(90)),
+            Duration::from_secs(45)
+        );
+        assert_eq!(
+            refresh_after(Duration::from_secs(30)),
+            Duration::from_secs(15)
+        );
+    }
+
+    #[test]
+    fn refreshes_never_come_back_to_back() {
+        assert_eq!(refresh_after(Duration::ZERO), MIN_REFRESH_DELAY);
+    }
+}
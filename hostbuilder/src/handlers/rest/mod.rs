@@ -1 +1,2 @@
-pub mod kraken_rest_api;
\ No newline at end of file
+pub mod kraken_rest_api;
+pub mod kraken_token_manager;
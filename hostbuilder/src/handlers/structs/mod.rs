@@ -1 +1 @@
-pub mod responses;
\ No newline at end of file
+pub mod responses;
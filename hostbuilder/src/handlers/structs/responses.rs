@@ -1,33 +1,8 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr};
 
 use anyhow::{anyhow, Result};
-use bigdecimal::{BigDecimal, Zero};
-use databaseschema::{
-    models::{
-        NewModifiedBuyOrder, NewModifiedSellOrder, NewOpenBuyOrder, NewOpenSellOrder, NewTrade, OrderBook
-    },
-    ops::{
-        modified_buy_order_ops::{
-            create_modified_buy_orders, delete_modified_buy_orders,
-        },
-        modified_sell_order_ops::{
-            create_modified_sell_orders, delete_modified_sell_orders,
-        },
-        open_buy_order_ops::{
-            create_open_buy_orders, delete_open_buy_orders,
-        },
-        open_sell_order_ops::{
-            create_open_sell_orders, delete_open_sell_orders,
-        },
-        order_book_ops::update_orderbook, trades_ops::create_trades,
-    },
-    CustomAsyncPgConnectionManager,
-};
-use deadpool::managed::Pool;
-use deadpool_redis::{Connection, Manager};
-use redis::cmd;
-use redis_utils::create_redis_connection;
-use serde::{Deserialize, Serialize};
+use bigdecimal::BigDecimal;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,6 +22,18 @@ pub enum Response {
     TradeUnsubscribeAck(TradeUnsubscribeAcknowledgement),
     TradeSnapshot(TradeSnapshot),
     TradeUpdate(TradeUpdate),
+    Level2Snapshot(Level2Snapshot),
+    Level2Update(Level2Update),
+    TickerSnapshot(TickerSnapshot),
+    TickerUpdate(TickerUpdate),
+    OhlcSnapshot(OhlcSnapshot),
+    OhlcUpdate(OhlcUpdate),
+    InstrumentSnapshot(InstrumentSnapshot),
+    InstrumentUpdate(InstrumentUpdate),
+    ExecutionsSnapshot(ExecutionsSnapshot),
+    ExecutionsUpdate(ExecutionsUpdate),
+    BalancesSnapshot(BalancesSnapshot),
+    BalancesUpdate(BalancesUpdate),
 }
 
 //----------------------------------------------------------------------
@@ -66,6 +53,11 @@ impl TokenResponse {
     pub fn get_token(&self) -> String {
         self.result.token.clone()
     }
+
+    /// Seconds the token stays valid for establishing a connection.
+    pub fn expires(&self) -> u64 {
+        self.result.expires
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -76,11 +68,44 @@ pub struct TokenResultData {
 
 //-------------------------------------------------------------------------
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AssetPairsResponse {
+    pub(crate) error: Vec<String>,
+    pub(crate) result: HashMap<String, AssetPair>,
+}
+
+impl AssetPairsResponse {
+    pub fn from_json(json: &serde_json::Value) -> Result<AssetPairsResponse> {
+        let asset_pairs: AssetPairsResponse = serde_json::from_value(json.clone())?;
+        Ok(asset_pairs)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AssetPair {
+    pub(crate) altname: String,
+    pub(crate) wsname: Option<String>,
+    pub(crate) pair_decimals: u32,
+    pub(crate) lot_decimals: u32,
+    #[serde(default)]
+    pub(crate) tick_size: Option<BigDecimal>,
+    #[serde(default)]
+    pub(crate) ordermin: Option<BigDecimal>,
+    #[serde(default)]
+    pub(crate) costmin: Option<BigDecimal>,
+    #[serde(default)]
+    pub(crate) leverage_buy: Vec<u32>,
+    #[serde(default)]
+    pub(crate) status: Option<String>,
+}
+
+//-------------------------------------------------------------------------
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Status {
-    channel: String,
-    data: Vec<StatusData>,
-    r#type: String,
+    pub(crate) channel: String,
+    pub(crate) data: Vec<StatusData>,
+    pub(crate) r#type: String,
 }
 
 impl Status {
@@ -92,10 +117,10 @@ impl Status {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StatusData {
-    api_version: String,
-    connection_id: u64,
-    system: String,
-    version: String,
+    pub(crate) api_version: String,
+    pub(crate) connection_id: u64,
+    pub(crate) system: String,
+    pub(crate) version: String,
 }
 
 //-------------------------------------------------------------------------
@@ -182,7 +207,8 @@ pub struct Level3UnsubscribeAcknowledgement {
 
 impl Level3UnsubscribeAcknowledgement {
     pub fn from_json(json: &serde_json::Value) -> Result<Level3UnsubscribeAcknowledgement> {
-        let unsubscribe_ack: Level3UnsubscribeAcknowledgement = serde_json::from_value(json.clone())?;
+        let unsubscribe_ack: Level3UnsubscribeAcknowledgement =
+            serde_json::from_value(json.clone())?;
         Ok(unsubscribe_ack)
     }
 }
@@ -289,7 +315,8 @@ pub struct TradeUnsubscribeAcknowledgement {
 
 impl TradeUnsubscribeAcknowledgement {
     pub fn from_json(json: &serde_json::Value) -> Result<TradeUnsubscribeAcknowledgement> {
-        let unsubscribe_ack: TradeUnsubscribeAcknowledgement = serde_json::from_value(json.clone())?;
+        let unsubscribe_ack: TradeUnsubscribeAcknowledgement =
+            serde_json::from_value(json.clone())?;
         Ok(unsubscribe_ack)
     }
 }
@@ -304,9 +331,9 @@ pub struct TradeUnsubscribeResonseData {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Level3Snapshot {
-    channel: String,
-    r#type: String,
-    data: Vec<Level3SnapshotData>,
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<Level3SnapshotData>,
 }
 
 impl Level3Snapshot {
@@ -314,215 +341,31 @@ impl Level3Snapshot {
         let snapshot: Level3Snapshot = serde_json::from_value(json.clone())?;
         Ok(snapshot)
     }
-
-    pub async fn process_snapshot(
-        &self,
-        redis_pool: Arc<Pool<Manager, Connection>>,
-        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
-        order_book: OrderBook,
-    ) -> Result<()> {
-        let mut book_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-        let mut bid_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-        let mut ask_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-
-        if self.channel == "level3" {
-            if self.data.is_empty() {
-                println!("Snapshot data is empty");
-            }
-
-            let snapshot_data = &self.data[0];
-
-            if snapshot_data.bids.is_empty() && snapshot_data.asks.is_empty() {
-                println!("Both bids and asks are empty");
-            }
-
-            let mut open_bid_volume = BigDecimal::zero();
-            let mut open_ask_volume = BigDecimal::zero();
-
-            let bid_futures = async {
-                if !snapshot_data.bids.is_empty() {
-                    let mut buy_orders = Vec::new();
-                    let mut pipe = redis::pipe();
-
-                    for bid in snapshot_data.bids.iter() {
-                        let price_level = &bid.limit_price;
-                        let buy_quantity = &bid.order_qty;
-                        let unique_id = &bid.order_id;
-
-                        open_bid_volume += buy_quantity;
-
-                        let order = NewOpenBuyOrder::new(
-                            &order_book.symbol,
-                            &order_book.exchange,
-                            order_book.security_id,
-                            order_book.exchange_id,
-                            order_book.buy_order_book_id,
-                            unique_id,
-                            price_level,
-                            buy_quantity,
-                        );
-
-                        pipe.atomic()
-                            .cmd("HSET")
-                            .arg(format!("buy_order:{}", order.unique_id))
-                            .arg("symbol")
-                            .arg(&order.symbol)
-                            .arg("exchange")
-                            .arg(&order.exchange)
-                            .arg("security_id")
-                            .arg(&order.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order.exchange_id.to_string())
-                            .arg("buy_order_book_id")
-                            .arg(&order.buy_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&order.unique_id)
-                            .arg("price_level")
-                            .arg(&order.price_level.to_string())
-                            .arg("buy_quantity")
-                            .arg(&order.buy_quantity.to_string());
-
-                        buy_orders.push(order);
-                    }
-
-                    match pipe.query_async::<_, ()>(&mut bid_connection).await {
-                        Ok(_) => println!("Added buy orders to redis"),
-                        Err(e) => println!("Failed to add buy orders to redis: {}", e),
-                    }
-
-                    create_open_buy_orders(postgres_pool.clone(), buy_orders).await;
-                }
-            };
-
-            let ask_futures = async {
-                if !snapshot_data.asks.is_empty() {
-                    let mut add_sell_orders = Vec::new();
-                    let mut pipe = redis::pipe();
-                    for ask in snapshot_data.asks.iter() {
-                        let price_level = &ask.limit_price;
-                        let sell_quantity = &ask.order_qty;
-                        let unique_id = &ask.order_id;
-
-                        open_ask_volume += sell_quantity;
-
-                        let order = NewOpenSellOrder::new(
-                            &order_book.symbol,
-                            &order_book.exchange,
-                            order_book.security_id,
-                            order_book.exchange_id,
-                            order_book.sell_order_book_id,
-                            unique_id,
-                            price_level,
-                            sell_quantity,
-                        );
-
-                        pipe.atomic()
-                            .cmd("HSET")
-                            .arg(format!("sell_order:{}", order.unique_id))
-                            .arg("symbol")
-                            .arg(&order.symbol)
-                            .arg("exchange")
-                            .arg(&order.exchange)
-                            .arg("security_id")
-                            .arg(&order.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order.exchange_id.to_string())
-                            .arg("sell_order_book_id")
-                            .arg(&order.sell_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&order.unique_id)
-                            .arg("price_level")
-                            .arg(&order.price_level.to_string())
-                            .arg("sell_quantity")
-                            .arg(&order.sell_quantity.to_string());
-
-                        add_sell_orders.push(order);
-                    }
-
-                    match pipe.query_async::<_, ()>(&mut ask_connection).await {
-                        Ok(_) => println!("Added sell orders to redis"),
-                        Err(e) => println!("Failed to add sell orders to redis: {}", e),
-                    }
-
-                    create_open_sell_orders(postgres_pool.clone(), add_sell_orders).await;
-                }
-            };
-
-            tokio::join!(bid_futures, ask_futures);
-
-
-            let total_volume = open_bid_volume + open_ask_volume;
-
-            println!("Total volume: {}", total_volume);
-
-            match cmd("HSET")
-                .arg(format!("order_book:{}", order_book.order_book_id))
-                .arg("created_at")
-                .arg(&order_book.created_at.to_string())
-                .arg("updated_at")
-                .arg(&order_book.updated_at.expect("").to_string())
-                .arg("symbol")
-                .arg(&order_book.symbol)
-                .arg("exchange")
-                .arg(&order_book.exchange)
-                .arg("security_id")
-                .arg(&order_book.security_id.to_string())
-                .arg("exchange_id")
-                .arg(&order_book.exchange_id.to_string())
-                .arg("buy_order_book_id")
-                .arg(&order_book.buy_order_book_id.to_string())
-                .arg("sell_order_book_id")
-                .arg(&order_book.sell_order_book_id.to_string())
-                .arg("total_volume")
-                .arg(&total_volume.to_string())
-                .query_async::<_, ()>(&mut book_connection)
-                .await
-            {
-                Ok(_) => {
-                    println!("Added order book to redis");
-                }
-                Err(e) => {
-                    println!("Failed to add order book to redis: {}", e);
-                }
-            }
-            update_orderbook(postgres_pool.clone(), order_book, total_volume).await;
-            Ok(())
-        } else {
-            println!("Channel is not level3");
-            Ok(())
-        }
-    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Level3SnapshotData {
-    symbol: String,
-    checksum: i64,
-    bids: Vec<SnapshotOrder>,
-    asks: Vec<SnapshotOrder>,
+pub(crate) struct Level3SnapshotData {
+    pub(crate) symbol: String,
+    pub(crate) checksum: i64,
+    pub(crate) bids: Vec<SnapshotOrder>,
+    pub(crate) asks: Vec<SnapshotOrder>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct SnapshotOrder {
-    order_id: String,
-    limit_price: BigDecimal,
-    order_qty: BigDecimal,
-    timestamp: String,
+pub(crate) struct SnapshotOrder {
+    pub(crate) order_id: String,
+    pub(crate) limit_price: BigDecimal,
+    pub(crate) order_qty: BigDecimal,
+    pub(crate) timestamp: String,
 }
 
 //-------------------------------------------------------------------------
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Level3Update {
-    channel: String,
-    r#type: String,
-    data: Vec<Level3UpdateData>,
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<Level3UpdateData>,
 }
 
 impl Level3Update {
@@ -530,383 +373,32 @@ impl Level3Update {
         let update: Level3Update = serde_json::from_value(json.clone())?;
         Ok(update)
     }
-
-    pub async fn process_update(
-        &self,
-        redis_pool: Arc<Pool<Manager, Connection>>,
-        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
-        order_book: OrderBook,
-    ) -> Result<()> {
-        let mut book_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-        let mut bid_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-        let mut ask_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-
-        if self.channel == "level3" {
-            if self.data.is_empty() {
-                println!("update data is empty");
-            }
-
-            let update_data = &self.data[0];
-
-            if update_data.bids.is_empty() && update_data.asks.is_empty() {
-                println!("Both bids and asks are empty");
-            }
-
-            let total_volume = cmd("HGET")
-            .arg(format!("order_book:{}", order_book.order_book_id))
-            .arg("total_volume")
-            .query_async::<_, String>(&mut book_connection).await.expect("Failed to get total volume");
-
-            let mut bid_volume = BigDecimal::zero();
-            let mut ask_volume = BigDecimal::zero();
-
-            let mut total_volume = BigDecimal::from_str(&total_volume).expect("Failed to convert total volume to BigDecimal");
-
-            let bid_futures = async {
-                let mut buy_orders = Vec::new();
-                let mut modified_orders = Vec::new();
-                let mut delete_orders = Vec::new();
-                let mut pipe = redis::pipe();
-
-                for bid in update_data.bids.iter() {
-                    if bid.event == "add" {
-                        let price_level = &bid.limit_price;
-                        let buy_quantity = &bid.order_qty;
-                        let unique_id = &bid.order_id;
-
-                        bid_volume += buy_quantity;
-
-                        let order = NewOpenBuyOrder::new(
-                            &order_book.symbol,
-                            &order_book.exchange,
-                            order_book.security_id,
-                            order_book.exchange_id,
-                            order_book.buy_order_book_id,
-                            unique_id,
-                            price_level,
-                            buy_quantity,
-                        );
-
-                        pipe.atomic()
-                            .cmd("HSET")
-                            .arg(format!("buy_order:{}", order.unique_id))
-                            .arg("symbol")
-                            .arg(&order.symbol)
-                            .arg("exchange")
-                            .arg(&order.exchange)
-                            .arg("security_id")
-                            .arg(&order.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order.exchange_id.to_string())
-                            .arg("buy_order_book_id")
-                            .arg(&order.buy_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&order.unique_id)
-                            .arg("price_level")
-                            .arg(&order.price_level.to_string())
-                            .arg("buy_quantity")
-                            .arg(&order.buy_quantity.to_string());
-
-                        buy_orders.push(order);
-                    } else if bid.event == "modify" {
-                        let price_level = &bid.limit_price;
-                        let new_buy_quantity = &bid.order_qty;
-                        let unique_id = &bid.order_id;
-
-                        let buy_quantity = cmd("HGET")
-                            .arg(format!("buy_order:{}", unique_id))
-                            .arg("buy_quantity")
-                            .query_async::<_, String>(&mut bid_connection).await.expect("Failed to get total volume");
-
-                        let buy_quantity = BigDecimal::from_str(&buy_quantity).expect("Failed to convert total volume to BigDecimal");
-
-                        let buy_quantity = (new_buy_quantity - buy_quantity).abs();
-
-                        bid_volume += buy_quantity;
-
-                        let order = NewModifiedBuyOrder::new(
-                            &order_book.symbol,
-                            &order_book.exchange,
-                            order_book.security_id,
-                            order_book.exchange_id,
-                            order_book.buy_order_book_id,
-                            unique_id,
-                            price_level,
-                            new_buy_quantity,
-                        );
-
-                        pipe.atomic()
-                            .cmd("HSET")
-                            .arg(format!("buy_order:{}", order.unique_id))
-                            .arg("symbol")
-                            .arg(&order.symbol)
-                            .arg("exchange")
-                            .arg(&order.exchange)
-                            .arg("security_id")
-                            .arg(&order.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order.exchange_id.to_string())
-                            .arg("buy_order_book_id")
-                            .arg(&order.buy_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&order.unique_id)
-                            .arg("price_level")
-                            .arg(&order.price_level.to_string())
-                            .arg("buy_quantity")
-                            .arg(&order.new_buy_quantity.to_string());
-
-                        delete_orders.push(unique_id);
-                        modified_orders.push(order);
-                    } else if bid.event == "delete" {
-                        pipe.atomic()
-                            .cmd("HDEL")
-                            .arg(format!("buy_order:{}", bid.order_id))
-                            .arg("symbol")
-                            .arg(&order_book.symbol)
-                            .arg("exchange")
-                            .arg(&order_book.exchange)
-                            .arg("security_id")
-                            .arg(&order_book.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order_book.exchange_id.to_string())
-                            .arg("buy_order_book_id")
-                            .arg(&order_book.buy_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&bid.order_id)
-                            .arg("price_level")
-                            .arg(&bid.limit_price.to_string())
-                            .arg("buy_quantity")
-                            .arg(&bid.order_qty.to_string());
-
-                        bid_volume -= &bid.order_qty;
-
-                        delete_orders.push(&bid.order_id);
-                    }
-                }
-                match pipe.query_async::<_, ()>(&mut bid_connection).await {
-                    Ok(_) => {
-                        println!("Batch added, modified, and deleted buy orders to/from redis")
-                    }
-                    Err(e) => println!(
-                        "Failed to batch add, modify, and delete buy orders to/from redis: {}",
-                        e
-                    ),
-                }
-                create_open_buy_orders(postgres_pool.clone(), buy_orders).await;
-                create_modified_buy_orders(postgres_pool.clone(), modified_orders).await;
-                delete_open_buy_orders(postgres_pool.clone(), delete_orders.clone()).await;
-                delete_modified_buy_orders(postgres_pool.clone(), delete_orders.clone()).await;
-            };
-
-            let ask_futures = async {
-                let mut sell_orders = Vec::new();
-                let mut modified_orders = Vec::new();
-                let mut delete_orders = Vec::new();
-                let mut pipe = redis::pipe();
-
-                for ask in update_data.asks.iter() {
-                    if ask.event == "add" {
-                        let price_level = &ask.limit_price;
-                        let sell_quantity = &ask.order_qty;
-                        let unique_id = &ask.order_id;
-
-                        ask_volume += sell_quantity;
-
-                        let order = NewOpenSellOrder::new(
-                            &order_book.symbol,
-                            &order_book.exchange,
-                            order_book.security_id,
-                            order_book.exchange_id,
-                            order_book.sell_order_book_id,
-                            unique_id,
-                            price_level,
-                            sell_quantity,
-                        );
-
-                        pipe.atomic()
-                            .cmd("HSET")
-                            .arg(format!("sell_order:{}", order.unique_id))
-                            .arg("symbol")
-                            .arg(&order.symbol)
-                            .arg("exchange")
-                            .arg(&order.exchange)
-                            .arg("security_id")
-                            .arg(&order.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order.exchange_id.to_string())
-                            .arg("sell_order_book_id")
-                            .arg(&order.sell_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&order.unique_id)
-                            .arg("price_level")
-                            .arg(&order.price_level.to_string())
-                            .arg("sell_quantity")
-                            .arg(&order.sell_quantity.to_string());
-
-                        sell_orders.push(order);
-                    } else if ask.event == "modify" {
-                        let price_level = &ask.limit_price;
-                        let new_sell_quantity = &ask.order_qty;
-                        let unique_id = &ask.order_id;
-
-                        let ask_quantity = cmd("HGET")
-                            .arg(format!("ask_order:{}", unique_id))
-                            .arg("ask_quantity")
-                            .query_async::<_, String>(&mut ask_connection).await.expect("Failed to get total volume");
-
-                        let ask_quantity = BigDecimal::from_str(&ask_quantity).expect("Failed to convert total volume to BigDecimal");
-
-                        let ask_quantity = (new_sell_quantity - ask_quantity).abs();
-
-                        ask_volume += ask_quantity;
-
-                        let order = NewModifiedSellOrder::new(
-                            &order_book.symbol,
-                            &order_book.exchange,
-                            order_book.security_id,
-                            order_book.exchange_id,
-                            order_book.sell_order_book_id,
-                            unique_id,
-                            price_level,
-                            new_sell_quantity,
-                        );
-
-                        pipe.atomic()
-                            .cmd("HSET")
-                            .arg(format!("sell_order:{}", order.unique_id))
-                            .arg("symbol")
-                            .arg(&order.symbol)
-                            .arg("exchange")
-                            .arg(&order.exchange)
-                            .arg("security_id")
-                            .arg(&order.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order.exchange_id.to_string())
-                            .arg("sell_order_book_id")
-                            .arg(&order.sell_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&order.unique_id)
-                            .arg("price_level")
-                            .arg(&order.price_level.to_string())
-                            .arg("sell_quantity")
-                            .arg(&order.new_sell_quantity.to_string());
-
-                        delete_orders.push(unique_id);
-                        modified_orders.push(order);
-                    } else if ask.event == "delete" {
-                        pipe.atomic()
-                            .cmd("HDEL")
-                            .arg(format!("sell_order:{}", ask.order_id))
-                            .arg("symbol")
-                            .arg(&order_book.symbol)
-                            .arg("exchange")
-                            .arg(&order_book.exchange)
-                            .arg("security_id")
-                            .arg(&order_book.security_id.to_string())
-                            .arg("exchange_id")
-                            .arg(&order_book.exchange_id.to_string())
-                            .arg("sell_order_book_id")
-                            .arg(&order_book.sell_order_book_id.to_string())
-                            .arg("unique_id")
-                            .arg(&ask.order_id)
-                            .arg("price_level")
-                            .arg(&ask.limit_price.to_string())
-                            .arg("sell_quantity")
-                            .arg(&ask.order_qty.to_string());
-
-                        ask_volume -= &ask.order_qty;
-
-                        delete_orders.push(&ask.order_id);
-                    }
-                }
-                match pipe.query_async::<_, ()>(&mut ask_connection).await {
-                    Ok(_) => {
-                        println!("Batch added, modified, and deleted buy orders to/from redis")
-                    }
-                    Err(e) => println!(
-                        "Failed to batch add, modify, and delete buy orders to/from redis: {}",
-                        e
-                    ),
-                }
-                create_open_sell_orders(postgres_pool.clone(), sell_orders).await;
-                create_modified_sell_orders(postgres_pool.clone(), modified_orders).await;
-                delete_open_sell_orders(postgres_pool.clone(), delete_orders.clone()).await;
-                delete_modified_sell_orders(postgres_pool.clone(), delete_orders.clone()).await;
-            };
-
-            tokio::join!(bid_futures, ask_futures);
-
-            total_volume += bid_volume + ask_volume;
-
-            match cmd("HSET")
-                .arg(format!("order_book:{}", order_book.order_book_id))
-                .arg("created_at")
-                .arg(&order_book.created_at.to_string())
-                .arg("updated_at")
-                .arg(&order_book.updated_at.expect("").to_string())
-                .arg("symbol")
-                .arg(&order_book.symbol)
-                .arg("exchange")
-                .arg(&order_book.exchange)
-                .arg("security_id")
-                .arg(&order_book.security_id.to_string())
-                .arg("exchange_id")
-                .arg(&order_book.exchange_id.to_string())
-                .arg("buy_order_book_id")
-                .arg(&order_book.buy_order_book_id.to_string())
-                .arg("sell_order_book_id")
-                .arg(&order_book.sell_order_book_id.to_string())
-                .arg("total_volume")
-                .arg(&total_volume.to_string())
-                .query_async::<_, ()>(&mut book_connection)
-                .await
-            {
-                Ok(_) => {
-                    println!("Updated order book in redis");
-                }
-                Err(e) => {
-                    println!("Failed to update order book in redis: {}", e);
-                }
-            }
-            update_orderbook(postgres_pool.clone(), order_book, total_volume).await;
-            Ok(())
-        } else {
-            println!("Channel is not level3");
-            Ok(())
-        }
-    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Level3UpdateData {
-    symbol: String,
-    checksum: i64,
-    bids: Vec<UpdateOrder>,
-    asks: Vec<UpdateOrder>,
+pub(crate) struct Level3UpdateData {
+    pub(crate) symbol: String,
+    pub(crate) checksum: i64,
+    pub(crate) bids: Vec<UpdateOrder>,
+    pub(crate) asks: Vec<UpdateOrder>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct UpdateOrder {
-    event: String,
-    order_id: String,
-    limit_price: BigDecimal,
-    order_qty: BigDecimal,
-    timestamp: String,
+pub(crate) struct UpdateOrder {
+    pub(crate) event: String,
+    pub(crate) order_id: String,
+    pub(crate) limit_price: BigDecimal,
+    pub(crate) order_qty: BigDecimal,
+    pub(crate) timestamp: String,
 }
 
 //-------------------------------------------------------------------------
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TradeSnapshot {
-    channel: String,
-    r#type: String,
-    data: Vec<TradeData>,
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<TradeData>,
 }
 
 impl TradeSnapshot {
@@ -914,90 +406,26 @@ impl TradeSnapshot {
         let snapshot: TradeSnapshot = serde_json::from_value(json.clone())?;
         Ok(snapshot)
     }
-
-    pub async fn process_snapshot(
-        &self,
-        redis_pool: Arc<Pool<Manager, Connection>>,
-        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
-        order_book: OrderBook
-    ) -> Result<()> {
-        let mut trade_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-
-        if self.channel == "trade" {
-            if self.data.is_empty() {
-                println!("Trade data is empty");
-            }
-
-            let mut trades = Vec::new();
-            let mut pipe = redis::pipe();
-
-            for trade in self.data.iter() {
-                let new_trade = NewTrade::new(
-                    &trade.symbol,
-                    &order_book.exchange,
-                    order_book.security_id,
-                    order_book.exchange_id,
-                    &trade.side,
-                    &trade.price,
-                    &trade.qty,
-                );
-
-                pipe.atomic()
-                    .cmd("HSET")
-                    .arg(format!("trade:{}", trade.trade_id))
-                    .arg("symbol")
-                    .arg(&trade.symbol)
-                    .arg("side")
-                    .arg(&trade.side)
-                    .arg("price")
-                    .arg(&trade.price.to_string())
-                    .arg("qty")
-                    .arg(&trade.qty.to_string())
-                    .arg("ord_type")
-                    .arg(&trade.ord_type)
-                    .arg("trade_id")
-                    .arg(&trade.trade_id)
-                    .arg("timestamp")
-                    .arg(&trade.timestamp);
-
-                trades.push(new_trade);
-            }
-
-            match pipe.query_async::<_, ()>(&mut trade_connection).await {
-                Ok(_) => println!("Added trades to redis"),
-                Err(e) => println!("Failed to add trades to redis: {}", e),
-            }
-
-            create_trades(postgres_pool.clone(), trades).await;
-
-            Ok(())
-        } else {
-            println!("Channel is not trade");
-            Ok(())
-        }
-    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct TradeData {
-    symbol: String,
-    side: String,
-    price: BigDecimal,
-    qty: BigDecimal,
-    ord_type: String,
-    trade_id: u64,
-    timestamp: String,
+pub(crate) struct TradeData {
+    pub(crate) symbol: String,
+    pub(crate) side: String,
+    pub(crate) price: BigDecimal,
+    pub(crate) qty: BigDecimal,
+    pub(crate) ord_type: String,
+    pub(crate) trade_id: u64,
+    pub(crate) timestamp: String,
 }
 
 //-------------------------------------------------------------------------
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TradeUpdate {
-    channel: String,
-    r#type: String,
-    data: Vec<TradeData>,
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<TradeData>,
 }
 
 impl TradeUpdate {
@@ -1005,72 +433,363 @@ impl TradeUpdate {
         let update: TradeUpdate = serde_json::from_value(json.clone())?;
         Ok(update)
     }
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Level2Snapshot {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<Level2SnapshotData>,
+}
+
+impl Level2Snapshot {
+    pub fn from_json(json: &serde_json::Value) -> Result<Level2Snapshot> {
+        let snapshot: Level2Snapshot = serde_json::from_value(json.clone())?;
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Level2SnapshotData {
+    pub(crate) symbol: String,
+    pub(crate) checksum: i64,
+    pub(crate) bids: Vec<PriceLevel>,
+    pub(crate) asks: Vec<PriceLevel>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct PriceLevel {
+    pub(crate) price: BigDecimal,
+    pub(crate) qty: BigDecimal,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Level2Update {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<Level2UpdateData>,
+}
+
+impl Level2Update {
+    pub fn from_json(json: &serde_json::Value) -> Result<Level2Update> {
+        let update: Level2Update = serde_json::from_value(json.clone())?;
+        Ok(update)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Level2UpdateData {
+    pub(crate) symbol: String,
+    pub(crate) checksum: i64,
+    pub(crate) bids: Vec<PriceLevel>,
+    pub(crate) asks: Vec<PriceLevel>,
+    pub(crate) timestamp: String,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TickerSnapshot {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<TickerData>,
+}
+
+impl TickerSnapshot {
+    pub fn from_json(json: &serde_json::Value) -> Result<TickerSnapshot> {
+        let snapshot: TickerSnapshot = serde_json::from_value(json.clone())?;
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TickerData {
+    pub(crate) symbol: String,
+    pub(crate) bid: BigDecimal,
+    pub(crate) bid_qty: BigDecimal,
+    pub(crate) ask: BigDecimal,
+    pub(crate) ask_qty: BigDecimal,
+    pub(crate) last: BigDecimal,
+    pub(crate) volume: BigDecimal,
+    pub(crate) vwap: BigDecimal,
+    pub(crate) low: BigDecimal,
+    pub(crate) high: BigDecimal,
+    pub(crate) change: BigDecimal,
+    pub(crate) change_pct: BigDecimal,
+    /// Only sent by newer versions of the API.
+    #[serde(default)]
+    pub(crate) timestamp: Option<String>,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TickerUpdate {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<TickerData>,
+}
+
+impl TickerUpdate {
+    pub fn from_json(json: &serde_json::Value) -> Result<TickerUpdate> {
+        let update: TickerUpdate = serde_json::from_value(json.clone())?;
+        Ok(update)
+    }
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhlcSnapshot {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<OhlcData>,
+}
+
+impl OhlcSnapshot {
+    pub fn from_json(json: &serde_json::Value) -> Result<OhlcSnapshot> {
+        let snapshot: OhlcSnapshot = serde_json::from_value(json.clone())?;
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct OhlcData {
+    pub(crate) symbol: String,
+    pub(crate) open: BigDecimal,
+    pub(crate) high: BigDecimal,
+    pub(crate) low: BigDecimal,
+    pub(crate) close: BigDecimal,
+    pub(crate) trades: u64,
+    pub(crate) volume: BigDecimal,
+    pub(crate) vwap: BigDecimal,
+    pub(crate) interval_begin: String,
+    pub(crate) interval: u32,
+    pub(crate) timestamp: String,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhlcUpdate {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<OhlcData>,
+}
+
+impl OhlcUpdate {
+    pub fn from_json(json: &serde_json::Value) -> Result<OhlcUpdate> {
+        let update: OhlcUpdate = serde_json::from_value(json.clone())?;
+        Ok(update)
+    }
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstrumentSnapshot {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: InstrumentData,
+}
 
-    pub async fn process_update(
-        &self,
-        redis_pool: Arc<Pool<Manager, Connection>>,
-        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
-        order_book: OrderBook
-    ) -> Result<()> {
-        let mut trade_connection = create_redis_connection(&redis_pool)
-            .await
-            .expect("Failed to create redis connection");
-
-        if self.channel == "trade" {
-            if self.data.is_empty() {
-                println!("Trade data is empty");
-            }
-
-            let mut trades = Vec::new();
-            let mut pipe = redis::pipe();
-
-            for trade in self.data.iter() {
-                let new_trade = NewTrade::new(
-                    &trade.symbol,
-                    &order_book.exchange,
-                    order_book.security_id,
-                    order_book.exchange_id,
-                    &trade.side,
-                    &trade.price,
-                    &trade.qty,
-                );
-
-                pipe.atomic()
-                    .cmd("HSET")
-                    .arg(format!("trade:{}", trade.trade_id))
-                    .arg("symbol")
-                    .arg(&trade.symbol)
-                    .arg("side")
-                    .arg(&trade.side)
-                    .arg("price")
-                    .arg(&trade.price.to_string())
-                    .arg("qty")
-                    .arg(&trade.qty.to_string())
-                    .arg("ord_type")
-                    .arg(&trade.ord_type)
-                    .arg("trade_id")
-                    .arg(&trade.trade_id)
-                    .arg("timestamp")
-                    .arg(&trade.timestamp);
-
-                trades.push(new_trade);
-            }
-
-            match pipe.query_async::<_, ()>(&mut trade_connection).await {
-                Ok(_) => println!("Added trades to redis"),
-                Err(e) => println!("Failed to add trades to redis: {}", e),
-            }
-
-            create_trades(postgres_pool.clone(), trades).await;
-
-            Ok(())
-        } else {
-            println!("Channel is not trade");
-            Ok(())
-        }
+impl InstrumentSnapshot {
+    pub fn from_json(json: &serde_json::Value) -> Result<InstrumentSnapshot> {
+        let snapshot: InstrumentSnapshot = serde_json::from_value(json.clone())?;
+        Ok(snapshot)
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct InstrumentData {
+    #[serde(default)]
+    pub(crate) pairs: Vec<InstrumentPair>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct InstrumentPair {
+    pub(crate) symbol: String,
+    pub(crate) base: String,
+    pub(crate) quote: String,
+    pub(crate) status: String,
+    pub(crate) qty_precision: u32,
+    pub(crate) qty_increment: BigDecimal,
+    pub(crate) price_precision: u32,
+    #[serde(default)]
+    pub(crate) price_increment: Option<BigDecimal>,
+    #[serde(default)]
+    pub(crate) tick_size: Option<BigDecimal>,
+    pub(crate) cost_precision: u32,
+    #[serde(default)]
+    pub(crate) cost_min: Option<BigDecimal>,
+    pub(crate) qty_min: BigDecimal,
+    pub(crate) marginable: bool,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstrumentUpdate {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: InstrumentData,
+}
+
+impl InstrumentUpdate {
+    pub fn from_json(json: &serde_json::Value) -> Result<InstrumentUpdate> {
+        let update: InstrumentUpdate = serde_json::from_value(json.clone())?;
+        Ok(update)
+    }
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutionsSnapshot {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<ExecutionReport>,
+}
+
+impl ExecutionsSnapshot {
+    pub fn from_json(json: &serde_json::Value) -> Result<ExecutionsSnapshot> {
+        let snapshot: ExecutionsSnapshot = serde_json::from_value(json.clone())?;
+        Ok(snapshot)
+    }
+}
+
+/// A report on one of our orders. Only the first report of an order carries
+/// all of it; later ones mostly carry what changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ExecutionReport {
+    pub(crate) order_id: String,
+    pub(crate) exec_type: String,
+    pub(crate) timestamp: String,
+    pub(crate) order_status: Option<String>,
+    pub(crate) cl_ord_id: Option<String>,
+    pub(crate) symbol: Option<String>,
+    pub(crate) side: Option<String>,
+    pub(crate) order_type: Option<String>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) order_qty: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) limit_price: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) cum_qty: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) avg_price: Option<BigDecimal>,
+    pub(crate) exec_id: Option<String>,
+    pub(crate) trade_id: Option<u64>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) last_qty: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) last_price: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "optional_decimal")]
+    pub(crate) cost: Option<BigDecimal>,
+    #[serde(default)]
+    pub(crate) fees: Vec<ExecutionFee>,
+    pub(crate) liquidity_ind: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ExecutionFee {
+    pub(crate) asset: String,
+    #[serde(deserialize_with = "decimal")]
+    pub(crate) qty: BigDecimal,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutionsUpdate {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<ExecutionReport>,
+}
+
+impl ExecutionsUpdate {
+    pub fn from_json(json: &serde_json::Value) -> Result<ExecutionsUpdate> {
+        let update: ExecutionsUpdate = serde_json::from_value(json.clone())?;
+        Ok(update)
+    }
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalancesSnapshot {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<AssetBalance>,
+}
+
+impl BalancesSnapshot {
+    pub fn from_json(json: &serde_json::Value) -> Result<BalancesSnapshot> {
+        let snapshot: BalancesSnapshot = serde_json::from_value(json.clone())?;
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct AssetBalance {
+    pub(crate) asset: String,
+    #[serde(deserialize_with = "decimal")]
+    pub(crate) balance: BigDecimal,
+}
+
+//-------------------------------------------------------------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalancesUpdate {
+    pub(crate) channel: String,
+    pub(crate) r#type: String,
+    pub(crate) data: Vec<LedgerUpdate>,
+}
+
+impl BalancesUpdate {
+    pub fn from_json(json: &serde_json::Value) -> Result<BalancesUpdate> {
+        let update: BalancesUpdate = serde_json::from_value(json.clone())?;
+        Ok(update)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct LedgerUpdate {
+    pub(crate) ledger_id: String,
+    pub(crate) r#type: String,
+    pub(crate) asset: String,
+    #[serde(deserialize_with = "decimal")]
+    pub(crate) amount: BigDecimal,
+    #[serde(deserialize_with = "decimal")]
+    pub(crate) fee: BigDecimal,
+    #[serde(deserialize_with = "decimal")]
+    pub(crate) balance: BigDecimal,
+    pub(crate) timestamp: String,
+}
+
+/// Reads a decimal Kraken sent as a JSON number from the shortest form of the
+/// float, which is the number as sent, rather than from the float's binary
+/// expansion. Account balances and fills are kept exact this way.
+fn decimal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigDecimal, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    BigDecimal::from_str(&value.to_string()).map_err(de::Error::custom)
+}
+
+fn optional_decimal<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<BigDecimal>, D::Error> {
+    let Some(value) = Option::<f64>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    BigDecimal::from_str(&value.to_string())
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
 pub fn parse_message(msg: &str) -> Result<Response> {
     // First, parse the string into a serde_json::Value
     let json_msg: Value =
@@ -1137,7 +856,91 @@ pub fn parse_message(msg: &str) -> Result<Response> {
     {
         let update = TradeUpdate::from_json(&json_msg)?;
         Ok(Response::TradeUpdate(update))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "book"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "snapshot"
+    {
+        let snapshot = Level2Snapshot::from_json(&json_msg)?;
+        Ok(Response::Level2Snapshot(snapshot))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "book"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "update"
+    {
+        let update = Level2Update::from_json(&json_msg)?;
+        Ok(Response::Level2Update(update))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "ticker"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "snapshot"
+    {
+        let snapshot = TickerSnapshot::from_json(&json_msg)?;
+        Ok(Response::TickerSnapshot(snapshot))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "ticker"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "update"
+    {
+        let update = TickerUpdate::from_json(&json_msg)?;
+        Ok(Response::TickerUpdate(update))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "ohlc"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "snapshot"
+    {
+        let snapshot = OhlcSnapshot::from_json(&json_msg)?;
+        Ok(Response::OhlcSnapshot(snapshot))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "ohlc"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "update"
+    {
+        let update = OhlcUpdate::from_json(&json_msg)?;
+        Ok(Response::OhlcUpdate(update))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "instrument"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "snapshot"
+    {
+        let snapshot = InstrumentSnapshot::from_json(&json_msg)?;
+        Ok(Response::InstrumentSnapshot(snapshot))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "instrument"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "update"
+    {
+        let update = InstrumentUpdate::from_json(&json_msg)?;
+        Ok(Response::InstrumentUpdate(update))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "executions"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "snapshot"
+    {
+        let snapshot = ExecutionsSnapshot::from_json(&json_msg)?;
+        Ok(Response::ExecutionsSnapshot(snapshot))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "executions"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "update"
+    {
+        let update = ExecutionsUpdate::from_json(&json_msg)?;
+        Ok(Response::ExecutionsUpdate(update))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "balances"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "snapshot"
+    {
+        let snapshot = BalancesSnapshot::from_json(&json_msg)?;
+        Ok(Response::BalancesSnapshot(snapshot))
+    } else if json_msg.get("channel").is_some()
+        && json_msg["channel"] == "balances"
+        && json_msg.get("type").is_some()
+        && json_msg["type"] == "update"
+    {
+        let update = BalancesUpdate::from_json(&json_msg)?;
+        Ok(Response::BalancesUpdate(update))
     } else {
         Err(anyhow!("Failed to parse message: {:?}", json_msg))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,638 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Mutex};
+use tracing::{debug, warn};
+
+use crate::{
+    events::{
+        parse_timestamp, round, AccountEvent, AccountOrder, Balance, BookLevel, BookOrder,
+        BookSnapshot, Candle, Fill, Heartbeat, Instrument, InstrumentStatus, LedgerEntry,
+        LevelEvent, LevelSnapshot, Liquidity, MarketEvent, OrderEvent, OrderStatus, Side,
+        StatusEvent, Ticker, Trade,
+    },
+    handlers::structs::responses::{
+        AssetBalance, ExecutionReport, InstrumentData, LedgerUpdate, Level2Snapshot, Level2Update,
+        Level3Snapshot, Level3Update, OhlcData, PriceLevel, Response, SnapshotOrder, TickerData,
+        TradeData, UpdateOrder,
+    },
+};
+
+/// Translates Kraken v2 websocket responses into venue-neutral [`MarketEvent`]s.
+pub struct KrakenTranslator {
+    exchange: String,
+    /// Our open orders by id, since execution reports after an order's first
+    /// only carry what changed.
+    orders: Mutex<HashMap<String, AccountOrder>>,
+}
+
+impl KrakenTranslator {
+    pub fn new(exchange: &str) -> Self {
+        Self {
+            exchange: exchange.to_string(),
+            orders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn translate(&self, response: Response) -> Result<Vec<MarketEvent>> {
+        match response {
+            Response::Level3Snapshot(snapshot) => self.level3_snapshot(snapshot),
+            Response::Level3Update(update) => self.level3_update(update),
+            Response::Level2Snapshot(snapshot) => self.level2_snapshot(snapshot),
+            Response::Level2Update(update) => self.level2_update(update),
+            Response::TradeSnapshot(snapshot) => self.trades(snapshot.data),
+            Response::TradeUpdate(update) => self.trades(update.data),
+            Response::TickerSnapshot(snapshot) => self.tickers(snapshot.data),
+            Response::TickerUpdate(update) => self.tickers(update.data),
+            Response::OhlcSnapshot(snapshot) => self.candles(snapshot.data),
+            Response::OhlcUpdate(update) => self.candles(update.data),
+            Response::InstrumentSnapshot(snapshot) => Ok(self.instruments(snapshot.data)),
+            Response::InstrumentUpdate(update) => Ok(self.instruments(update.data)),
+            Response::ExecutionsSnapshot(snapshot) => self.executions(snapshot.data),
+            Response::ExecutionsUpdate(update) => self.executions(update.data),
+            Response::BalancesSnapshot(snapshot) => Ok(self.balances(snapshot.data)),
+            Response::BalancesUpdate(update) => self.ledger_updates(update.data),
+            Response::HeartBeat(_) => Ok(vec![MarketEvent::Heartbeat(Heartbeat {
+                exchange: self.exchange.clone(),
+                received_at: Utc::now(),
+            })]),
+            Response::Status(status) => Ok(status
+                .data
+                .into_iter()
+                .map(|data| {
+                    MarketEvent::Status(StatusEvent {
+                        exchange: self.exchange.clone(),
+                        system: data.system,
+                        api_version: data.api_version,
+                        connection_id: data.connection_id,
+                        version: data.version,
+                    })
+                })
+                .collect()),
+            ack @ (Response::Level3SubscribeAck(_)
+            | Response::Level3UnsubscribeAck(_)
+            | Response::TradeSubscribeAck(_)
+            | Response::TradeUnsubscribeAck(_)) => {
+                debug!("Received {:?}", ack);
+                Ok(vec![])
+            }
+            // Requests are only ever sent and tokens fetched over REST, so
+            // neither should arrive on the feed. The token is not logged.
+            Response::TokenResponse(_) => {
+                warn!(
+                    "Ignoring a token response received on the {} feed",
+                    self.exchange
+                );
+                Ok(vec![])
+            }
+            request @ (Response::Level3Subscribe(_)
+            | Response::Level3Unsubscribe(_)
+            | Response::TradeSubscribe(_)
+            | Response::TradeUnsubscribe(_)) => {
+                warn!("Ignoring unexpected request {:?}", request);
+                Ok(vec![])
+            }
+        }
+    }
+
+    fn level3_snapshot(&self, snapshot: Level3Snapshot) -> Result<Vec<MarketEvent>> {
+        snapshot
+            .data
+            .into_iter()
+            .map(|data| {
+                Ok(MarketEvent::BookSnapshot(BookSnapshot {
+                    exchange: self.exchange.clone(),
+                    symbol: data.symbol,
+                    bids: book_orders(data.bids)?,
+                    asks: book_orders(data.asks)?,
+                    sequence: None,
+                    checksum: Some(checksum(data.checksum)?),
+                }))
+            })
+            .collect()
+    }
+
+    fn level3_update(&self, update: Level3Update) -> Result<Vec<MarketEvent>> {
+        let mut events = Vec::new();
+
+        for data in update.data {
+            let start = events.len();
+
+            for (side, orders) in [(Side::Buy, data.bids), (Side::Sell, data.asks)] {
+                for order in orders {
+                    events.push(self.order_event(&data.symbol, side, order)?);
+                }
+            }
+
+            // Kraken checksums the book after the whole message has been applied.
+            if let Some(
+                MarketEvent::OrderAdded(last)
+                | MarketEvent::OrderModified(last)
+                | MarketEvent::OrderDeleted(last),
+            ) = events[start..].last_mut()
+            {
+                last.checksum = Some(checksum(data.checksum)?);
+            }
+        }
+
+        Ok(events)
+    }
+
+    fn order_event(&self, symbol: &str, side: Side, order: UpdateOrder) -> Result<MarketEvent> {
+        let event = OrderEvent {
+            exchange: self.exchange.clone(),
+            symbol: symbol.to_string(),
+            side,
+            order_id: order.order_id,
+            price: order.limit_price,
+            quantity: order.order_qty,
+            timestamp: parse_timestamp(&order.timestamp)?,
+            sequence: None,
+            checksum: None,
+        };
+
+        match order.event.as_str() {
+            "add" => Ok(MarketEvent::OrderAdded(event)),
+            "modify" => Ok(MarketEvent::OrderModified(event)),
+            "delete" => Ok(MarketEvent::OrderDeleted(event)),
+            other => Err(anyhow!("Unknown level3 event: {}", other)),
+        }
+    }
+
+    /// Snapshots leave `depth` unset; the connector knows what was subscribed.
+    fn level2_snapshot(&self, snapshot: Level2Snapshot) -> Result<Vec<MarketEvent>> {
+        snapshot
+            .data
+            .into_iter()
+            .map(|data| {
+                Ok(MarketEvent::LevelSnapshot(LevelSnapshot {
+                    exchange: self.exchange.clone(),
+                    symbol: data.symbol,
+                    bids: book_levels(data.bids),
+                    asks: book_levels(data.asks),
+                    depth: None,
+                    sequence: None,
+                    checksum: Some(checksum(data.checksum)?),
+                }))
+            })
+            .collect()
+    }
+
+    fn level2_update(&self, update: Level2Update) -> Result<Vec<MarketEvent>> {
+        let mut events = Vec::new();
+
+        for data in update.data {
+            let timestamp = parse_timestamp(&data.timestamp)?;
+            let start = events.len();
+
+            for (side, levels) in [(Side::Buy, data.bids), (Side::Sell, data.asks)] {
+                for level in levels {
+                    events.push(MarketEvent::LevelUpdated(LevelEvent {
+                        exchange: self.exchange.clone(),
+                        symbol: data.symbol.clone(),
+                        side,
+                        price: level.price,
+                        quantity: level.qty,
+                        timestamp,
+                        sequence: None,
+                        checksum: None,
+                    }));
+                }
+            }
+
+            // As for level3, the checksum covers the book after the whole message.
+            if let Some(MarketEvent::LevelUpdated(last)) = events[start..].last_mut() {
+                last.checksum = Some(checksum(data.checksum)?);
+            }
+        }
+
+        Ok(events)
+    }
+
+    fn trades(&self, trades: Vec<TradeData>) -> Result<Vec<MarketEvent>> {
+        trades
+            .into_iter()
+            .map(|trade| {
+                Ok(MarketEvent::Trade(Trade {
+                    exchange: self.exchange.clone(),
+                    symbol: trade.symbol,
+                    side: trade.side.parse()?,
+                    price: trade.price,
+                    quantity: trade.qty,
+                    order_type: trade.ord_type,
+                    trade_id: trade.trade_id,
+                    timestamp: parse_timestamp(&trade.timestamp)?,
+                }))
+            })
+            .collect()
+    }
+
+    /// Tickers without a venue timestamp are stamped on receipt.
+    fn tickers(&self, tickers: Vec<TickerData>) -> Result<Vec<MarketEvent>> {
+        tickers
+            .into_iter()
+            .map(|ticker| {
+                let timestamp = match &ticker.timestamp {
+                    Some(timestamp) => parse_timestamp(timestamp)?,
+                    None => Utc::now(),
+                };
+                Ok(MarketEvent::Ticker(Box::new(Ticker {
+                    exchange: self.exchange.clone(),
+                    symbol: ticker.symbol,
+                    bid: ticker.bid,
+                    bid_quantity: ticker.bid_qty,
+                    ask: ticker.ask,
+                    ask_quantity: ticker.ask_qty,
+                    last: ticker.last,
+                    volume: ticker.volume,
+                    vwap: ticker.vwap,
+                    high: ticker.high,
+                    low: ticker.low,
+                    change: ticker.change,
+                    change_pct: ticker.change_pct,
+                    timestamp,
+                })))
+            })
+            .collect()
+    }
+
+    fn candles(&self, candles: Vec<OhlcData>) -> Result<Vec<MarketEvent>> {
+        candles
+            .into_iter()
+            .map(|candle| {
+                Ok(MarketEvent::Candle(Box::new(Candle {
+                    exchange: self.exchange.clone(),
+                    symbol: candle.symbol,
+                    interval: candle.interval,
+                    interval_begin: parse_timestamp(&candle.interval_begin)?,
+                    open: candle.open,
+                    high: candle.high,
+                    low: candle.low,
+                    close: candle.close,
+                    volume: candle.volume,
+                    vwap: candle.vwap,
+                    trades: candle.trades,
+                    timestamp: parse_timestamp(&candle.timestamp)?,
+                })))
+            })
+            .collect()
+    }
+
+    /// Kraken sends increments and minimums as floats, so they are rounded to
+    /// the precision they are quoted at.
+    fn instruments(&self, data: InstrumentData) -> Vec<MarketEvent> {
+        data.pairs
+            .into_iter()
+            .map(|pair| {
+                let mut tick_size = pair
+                    .price_increment
+                    .or(pair.tick_size)
+                    .unwrap_or_else(|| increment(pair.price_precision));
+                round(&mut tick_size, pair.price_precision);
+                let mut lot_size = pair.qty_increment;
+                round(&mut lot_size, pair.qty_precision);
+                let mut min_order_quantity = pair.qty_min;
+                round(&mut min_order_quantity, pair.qty_precision);
+                let min_order_cost = pair.cost_min.map(|mut cost_min| {
+                    round(&mut cost_min, pair.cost_precision);
+                    cost_min
+                });
+
+                MarketEvent::Instrument(Box::new(Instrument {
+                    exchange: self.exchange.clone(),
+                    symbol: pair.symbol,
+                    base: pair.base,
+                    quote: pair.quote,
+                    status: instrument_status(&pair.status),
+                    price_precision: pair.price_precision,
+                    quantity_precision: pair.qty_precision,
+                    tick_size,
+                    lot_size,
+                    min_order_quantity,
+                    min_order_cost,
+                    marginable: pair.marginable,
+                    timestamp: Utc::now(),
+                }))
+            })
+            .collect()
+    }
+
+    /// Turns execution reports into fills and the resulting state of their
+    /// orders. An order is forgotten once final; reports on an order never seen
+    /// in full yield its fills but no state.
+    fn executions(&self, reports: Vec<ExecutionReport>) -> Result<Vec<MarketEvent>> {
+        let mut orders = self
+            .orders
+            .lock()
+            .map_err(|_| anyhow!("Kraken order lock poisoned"))?;
+
+        let mut events = vec![];
+        for report in reports {
+            let timestamp = parse_timestamp(&report.timestamp)?;
+            let order = match orders.remove(&report.order_id) {
+                Some(order) => Some(order),
+                None => self.new_order(&report, timestamp)?,
+            };
+
+            if report.exec_type == "trade" {
+                if let Some(fill) = self.fill(&report, order.as_ref(), timestamp)? {
+                    events.push(MarketEvent::Account(AccountEvent::Fill(Box::new(fill))));
+                }
+            }
+
+            let Some(mut order) = order else {
+                continue;
+            };
+            if let Some(status) = &report.order_status {
+                order.status = status.parse()?;
+            }
+            if let Some(client_order_id) = report.cl_ord_id {
+                order.client_order_id = Some(client_order_id);
+            }
+            if let Some(quantity) = report.order_qty {
+                order.quantity = quantity;
+            }
+            if let Some(limit_price) = report.limit_price {
+                order.limit_price = Some(limit_price);
+            }
+            if let Some(filled_quantity) = report.cum_qty {
+                order.filled_quantity = filled_quantity;
+            }
+            if let Some(average_price) = report.avg_price {
+                order.average_price = Some(average_price);
+            }
+            order.timestamp = timestamp;
+
+            if !order.status.is_final() {
+                orders.insert(order.order_id.clone(), order.clone());
+            }
+            events.push(MarketEvent::Account(AccountEvent::Order(Box::new(order))));
+        }
+        Ok(events)
+    }
+
+    /// The order a first report describes, or `None` if the report lacks any of
+    /// what an order needs.
+    fn new_order(
+        &self,
+        report: &ExecutionReport,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<AccountOrder>> {
+        let (Some(symbol), Some(side), Some(order_type), Some(quantity)) = (
+            &report.symbol,
+            &report.side,
+            &report.order_type,
+            &report.order_qty,
+        ) else {
+            return Ok(None);
+        };
+
+        Ok(Some(AccountOrder {
+            exchange: self.exchange.clone(),
+            order_id: report.order_id.clone(),
+            client_order_id: None,
+            symbol: symbol.clone(),
+            side: side.parse()?,
+            order_type: order_type.clone(),
+            status: OrderStatus::PendingNew,
+            quantity: quantity.clone(),
+            limit_price: None,
+            filled_quantity: BigDecimal::from(0),
+            average_price: None,
+            timestamp,
+        }))
+    }
+
+    /// The fill a `trade` report describes, taking what the report leaves out
+    /// from its order.
+    fn fill(
+        &self,
+        report: &ExecutionReport,
+        order: Option<&AccountOrder>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<Fill>> {
+        let symbol = report
+            .symbol
+            .clone()
+            .or_else(|| order.map(|order| order.symbol.clone()));
+        let side = match (&report.side, order) {
+            (Some(side), _) => Some(side.parse()?),
+            (None, Some(order)) => Some(order.side),
+            (None, None) => None,
+        };
+        let (Some(execution_id), Some(symbol), Some(side), Some(price), Some(quantity)) = (
+            &report.exec_id,
+            symbol,
+            side,
+            &report.last_price,
+            &report.last_qty,
+        ) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Fill {
+            exchange: self.exchange.clone(),
+            execution_id: execution_id.clone(),
+            trade_id: report.trade_id,
+            order_id: report.order_id.clone(),
+            symbol,
+            side,
+            price: price.clone(),
+            quantity: quantity.clone(),
+            cost: report.cost.clone().unwrap_or_else(|| price * quantity),
+            fee: report.fees.iter().map(|fee| &fee.qty).sum(),
+            fee_asset: report.fees.first().map(|fee| fee.asset.clone()),
+            liquidity: match report.liquidity_ind.as_deref() {
+                Some("m") => Some(Liquidity::Maker),
+                Some("t") => Some(Liquidity::Taker),
+                _ => None,
+            },
+            timestamp,
+        }))
+    }
+
+    /// Snapshot balances carry no timestamp, so they are stamped on receipt.
+    fn balances(&self, balances: Vec<AssetBalance>) -> Vec<MarketEvent> {
+        balances
+            .into_iter()
+            .map(|balance| {
+                MarketEvent::Account(AccountEvent::Balance(Box::new(Balance {
+                    exchange: self.exchange.clone(),
+                    asset: balance.asset,
+                    balance: balance.balance,
+                    ledger: None,
+                    timestamp: Utc::now(),
+                })))
+            })
+            .collect()
+    }
+
+    fn ledger_updates(&self, updates: Vec<LedgerUpdate>) -> Result<Vec<MarketEvent>> {
+        updates
+            .into_iter()
+            .map(|update| {
+                Ok(MarketEvent::Account(AccountEvent::Balance(Box::new(
+                    Balance {
+                        exchange: self.exchange.clone(),
+                        asset: update.asset,
+                        balance: update.balance,
+                        ledger: Some(LedgerEntry {
+                            ledger_id: update.ledger_id,
+                            entry_type: update.r#type,
+                            amount: update.amount,
+                            fee: update.fee,
+                        }),
+                        timestamp: parse_timestamp(&update.timestamp)?,
+                    },
+                ))))
+            })
+            .collect()
+    }
+}
+
+/// Maps a Kraken pair status onto the engine's. Statuses it does not know are
+/// treated as halted.
+pub(crate) fn instrument_status(status: &str) -> InstrumentStatus {
+    match status {
+        "online" => InstrumentStatus::Online,
+        "post_only" => InstrumentStatus::PostOnly,
+        "limit_only" => InstrumentStatus::LimitOnly,
+        "cancel_only" => InstrumentStatus::CancelOnly,
+        "reduce_only" => InstrumentStatus::ReduceOnly,
+        "delisted" => InstrumentStatus::Delisted,
+        _ => InstrumentStatus::Halted,
+    }
+}
+
+/// The smallest step at `decimals` decimal places, e.g. 0.01 for 2.
+pub(crate) fn increment(decimals: u32) -> BigDecimal {
+    BigDecimal::new(1.into(), decimals as i64)
+}
+
+fn book_orders(orders: Vec<SnapshotOrder>) -> Result<Vec<BookOrder>> {
+    orders
+        .into_iter()
+        .map(|order| {
+            Ok(BookOrder {
+                order_id: order.order_id,
+                price: order.limit_price,
+                quantity: order.order_qty,
+                timestamp: parse_timestamp(&order.timestamp)?,
+            })
+        })
+        .collect()
+}
+
+fn book_levels(levels: Vec<PriceLevel>) -> Vec<BookLevel> {
+    levels
+        .into_iter()
+        .map(|level| BookLevel {
+            price: level.price,
+            quantity: level.qty,
+        })
+        .collect()
+}
+
+fn checksum(checksum: i64) -> Result<u32> {
+    u32::try_from(checksum).map_err(|_| anyhow!("Checksum out of range: {}", checksum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::structs::responses::parse_message;
+    use bigdecimal::{BigDecimal, RoundingMode};
+
+    /// Level 3 frames in the shape Kraken's v2 websocket sends them.
+    const LEVEL3_SNAPSHOT: &str = r#"{"channel":"level3","type":"snapshot","data":[{"symbol":"BTC/USD","checksum":3310070434,"bids":[{"order_id":"OUEN3U-ZR4GT-RTWHFJ","limit_price":45283.5,"order_qty":0.10000000,"timestamp":"2024-05-19T18:59:44.999999Z"}],"asks":[{"order_id":"OHZI4N-4EJJL-XTXG2D","limit_price":45285.2,"order_qty":0.00100000,"timestamp":"2024-05-19T18:59:45.012345Z"},{"order_id":"O6JZB2-QYQNX-ZZZN7D","limit_price":45285.2,"order_qty":0.50000000,"timestamp":"2024-05-19T18:59:46.000000Z"}]}]}"#;
+    const LEVEL3_UPDATE: &str = r#"{"channel":"level3","type":"update","data":[{"symbol":"BTC/USD","checksum":281817320,"bids":[{"event":"delete","order_id":"OUEN3U-ZR4GT-RTWHFJ","limit_price":45283.5,"order_qty":0.10000000,"timestamp":"2024-05-19T18:59:47.000000Z"},{"event":"add","order_id":"OKRFKM-XNZNI-NQGX4A","limit_price":45283.4,"order_qty":1.54582015,"timestamp":"2024-05-19T18:59:47.000000Z"}],"asks":[{"event":"modify","order_id":"O6JZB2-QYQNX-ZZZN7D","limit_price":45285.2,"order_qty":0.25000000,"timestamp":"2024-05-19T18:59:47.000000Z"}]}]}"#;
+
+    fn translate(frame: &str) -> Vec<MarketEvent> {
+        KrakenTranslator::new("kraken")
+            .translate(parse_message(frame).unwrap())
+            .unwrap()
+    }
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    /// Kraken sends prices and quantities as JSON floats, so they are compared
+    /// at BTC/USD's precision.
+    fn price(value: &BigDecimal) -> BigDecimal {
+        value.with_scale_round(1, RoundingMode::HalfEven)
+    }
+
+    fn quantity(value: &BigDecimal) -> BigDecimal {
+        value.with_scale_round(8, RoundingMode::HalfEven)
+    }
+
+    #[test]
+    fn translates_a_level3_snapshot() {
+        let events = translate(LEVEL3_SNAPSHOT);
+
+        assert_eq!(events.len(), 1);
+        let MarketEvent::BookSnapshot(snapshot) = &events[0] else {
+            panic!("expected a book snapshot, got {:?}", events[0]);
+        };
+        assert_eq!(snapshot.exchange, "kraken");
+        assert_eq!(snapshot.symbol, "BTC/USD");
+        assert_eq!(snapshot.checksum, Some(3310070434));
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.bids[0].order_id, "OUEN3U-ZR4GT-RTWHFJ");
+        assert_eq!(price(&snapshot.bids[0].price), decimal("45283.5"));
+        assert_eq!(quantity(&snapshot.bids[0].quantity), decimal("0.1"));
+        let asks: Vec<&str> = snapshot
+            .asks
+            .iter()
+            .map(|order| order.order_id.as_str())
+            .collect();
+        assert_eq!(asks, ["OHZI4N-4EJJL-XTXG2D", "O6JZB2-QYQNX-ZZZN7D"]);
+        assert_eq!(
+            snapshot.asks[0].timestamp,
+            parse_timestamp("2024-05-19T18:59:45.012345Z").unwrap()
+        );
+    }
+
+    #[test]
+    fn translates_a_level3_update_with_the_checksum_on_its_last_event() {
+        let events = translate(LEVEL3_UPDATE);
+
+        let kinds: Vec<(&str, &OrderEvent)> = events
+            .iter()
+            .map(|event| match event {
+                MarketEvent::OrderAdded(order) => ("add", order),
+                MarketEvent::OrderModified(order) => ("modify", order),
+                MarketEvent::OrderDeleted(order) => ("delete", order),
+                other => panic!("expected an order event, got {other:?}"),
+            })
+            .collect();
+        let summary: Vec<(&str, Side, &str)> = kinds
+            .iter()
+            .map(|(kind, order)| (*kind, order.side, order.order_id.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("delete", Side::Buy, "OUEN3U-ZR4GT-RTWHFJ"),
+                ("add", Side::Buy, "OKRFKM-XNZNI-NQGX4A"),
+                ("modify", Side::Sell, "O6JZB2-QYQNX-ZZZN7D"),
+            ]
+        );
+
+        let checksums: Vec<Option<u32>> = kinds.iter().map(|(_, order)| order.checksum).collect();
+        assert_eq!(checksums, [None, None, Some(281817320)]);
+        assert_eq!(price(&kinds[1].1.price), decimal("45283.4"));
+        assert_eq!(quantity(&kinds[1].1.quantity), decimal("1.54582015"));
+        assert_eq!(quantity(&kinds[2].1.quantity), decimal("0.25"));
+    }
+
+    #[test]
+    fn rejects_unknown_level3_events() {
+        let frame = LEVEL3_UPDATE.replace("\"modify\"", "\"amend\"");
+
+        let result = KrakenTranslator::new("kraken").translate(parse_message(&frame).unwrap());
+
+        assert!(result.is_err());
+    }
+}
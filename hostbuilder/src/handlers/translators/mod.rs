@@ -0,0 +1 @@
+pub mod kraken_translator;
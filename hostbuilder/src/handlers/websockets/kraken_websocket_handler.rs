@@ -1,212 +1,573 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use databaseschema::{
-    models::OrderBook, CustomAsyncPgConnectionManager,
-};
-use deadpool::managed::Pool;
-use deadpool_redis::{Connection, Manager};
+use databaseschema::models::OrderBook;
 use futures_util::{
     stream::{SplitSink, SplitStream},
     SinkExt, StreamExt,
 };
 use serde_json::Value;
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
 use tokio::{
     net::TcpStream,
-    sync::{broadcast::Receiver, Mutex},
+    sync::{
+        broadcast::Receiver,
+        mpsc::{self, error::TrySendError, Sender},
+        Mutex,
+    },
+    time::timeout,
+};
+use tokio_retry::{
+    strategy::{jitter, ExponentialBackoff},
+    Retry,
 };
 use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    config::{OverflowPolicy, Websocket},
+    connector::ExchangeConnector,
     endpoint::EndpointHandler,
-    handlers::
-        structs::responses::{parse_message, Response}
-    ,
+    events::MarketEvent,
+    processor::MarketDataProcessor,
 };
 //-------------------------------------------------------------------------
 
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Kraken sends a heartbeat every second; a connection silent for this long is
+/// treated as lost.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait between reconnect attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// What the reader hands to the parser, in the order it happened.
+enum Frame {
+    Text {
+        text: String,
+        received_at: Instant,
+    },
+    /// The connection was lost, so the books it fed can no longer be trusted.
+    Disconnected,
+}
+
+/// One connection to a channel, carrying every symbol of its shard. Events are
+/// routed to their book by the symbol they carry.
 pub struct KrakenWebSocketHandler {
-    writer: Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>,
-    reader: Mutex<SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>>,
-    unsubscribe_message: Value,
-    order_book: OrderBook,
-    redis_pool: Arc<Pool<Manager, Connection>>,
-    postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+    writer: Mutex<Option<SplitSink<WsStream, Message>>>,
+    websocket: Websocket,
+    /// Books fed by the connection, keyed by symbol. Symbols can be added and
+    /// removed while it runs.
+    order_books: RwLock<BTreeMap<String, OrderBook>>,
+    connection_id: String,
+    connector: Arc<dyn ExchangeConnector>,
+    processor: Arc<MarketDataProcessor>,
+    /// Set once a frame has been dropped, until the queue accepts frames again,
+    /// so an overflow triggers a single resync.
+    dropping: AtomicBool,
 }
 
 impl KrakenWebSocketHandler {
-    pub async fn new(endpoint: &str, order_book: OrderBook, subscribe_message: &Value, unsubscribe_message: &Value, redis_pool: Arc<Pool<Manager, Connection>>, postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>) -> Result<Self> {
-        let (ws_stream, _) = match connect_async(endpoint).await {
+    pub fn new(
+        websocket: &Websocket,
+        shard: usize,
+        order_books: Vec<OrderBook>,
+        connector: Arc<dyn ExchangeConnector>,
+        processor: Arc<MarketDataProcessor>,
+    ) -> Self {
+        let symbols: Vec<String> = order_books
+            .iter()
+            .map(|order_book| order_book.symbol.clone())
+            .collect();
+        let connection_id = format!("{}:{}:{}", connector.exchange(), websocket.channel, shard);
+        processor.connections().register(
+            &connection_id,
+            connector.exchange(),
+            &websocket.channel,
+            &websocket.endpoint,
+            &symbols,
+        );
+
+        Self {
+            writer: Mutex::new(None),
+            websocket: websocket.clone(),
+            order_books: RwLock::new(
+                order_books
+                    .into_iter()
+                    .map(|order_book| (order_book.symbol.clone(), order_book))
+                    .collect(),
+            ),
+            connection_id,
+            connector,
+            processor,
+            dropping: AtomicBool::new(false),
+        }
+    }
+
+    fn labels(&self) -> [(&str, &str); 2] {
+        [
+            ("endpoint", self.websocket.endpoint.as_str()),
+            ("channel", self.websocket.channel.as_str()),
+        ]
+    }
+
+    /// Keeps the endpoint connected: whenever the connection drops, the books it
+    /// fed are invalidated and the handler reconnects with jittered exponential
+    /// backoff, subscribing again so the venue sends fresh snapshots. Returns once
+    /// shutdown is signalled, after unsubscribing and applying every frame read.
+    ///
+    /// Reading and applying are separate stages joined by a bounded queue, so the
+    /// socket keeps being drained while a frame is applied.
+    async fn run(self: &Arc<Self>, shutdown_rx: &mut Receiver<()>) {
+        let capacity = self.processor.settings().frame_capacity.max(1);
+        let (frames, frames_rx) = mpsc::channel(capacity);
+        let parser = tokio::spawn(self.clone().parse(frames_rx));
+
+        self.stream(&frames, shutdown_rx).await;
+
+        drop(frames);
+        let _ = parser.await;
+    }
+
+    async fn stream(&self, frames: &Sender<Frame>, shutdown_rx: &mut Receiver<()>) {
+        let metrics = self.processor.metrics().clone();
+        let mut disconnected_at: Option<Instant> = None;
+
+        loop {
+            let strategy = ExponentialBackoff::from_millis(2)
+                .factor(250)
+                .max_delay(MAX_RECONNECT_DELAY)
+                .map(jitter);
+            let reader = tokio::select! {
+                result = Retry::start(strategy, || self.connect()) => match result {
+                    Ok(reader) => reader,
+                    Err(e) => {
+                        error!("Gave up connecting to {}: {}", self.websocket.endpoint, e);
+                        return;
+                    }
+                },
+                _ = shutdown_rx.recv() => return,
+            };
+
+            let connections = self.processor.connections();
+            connections.connected(&self.connection_id, disconnected_at.is_some());
+            if let Some(disconnected_at) = disconnected_at.take() {
+                let downtime = disconnected_at.elapsed();
+                metrics.increment("websocket_reconnects_total", &self.labels());
+                metrics.add(
+                    "websocket_downtime_milliseconds_total",
+                    &self.labels(),
+                    downtime.as_millis() as u64,
+                );
+                info!(
+                    "Reconnected to {} after {:?}",
+                    self.websocket.endpoint, downtime
+                );
+            }
+            metrics.set_gauge("websocket_connected", &self.labels(), 1.0);
+
+            let stopped = self.read(reader, frames, shutdown_rx).await;
+
+            metrics.set_gauge("websocket_connected", &self.labels(), 0.0);
+            connections.disconnected(&self.connection_id);
+            if stopped {
+                self.stop().await;
+                return;
+            }
+            *self.writer.lock().await = None;
+            let _ = frames.send(Frame::Disconnected).await;
+            disconnected_at = Some(Instant::now());
+        }
+    }
+
+    /// Opens the connection and subscribes, returning the read half.
+    async fn connect(&self) -> Result<SplitStream<WsStream>> {
+        self.processor
+            .metrics()
+            .increment("websocket_connect_attempts_total", &self.labels());
+
+        let (ws_stream, _) = match connect_async(&self.websocket.endpoint).await {
             Ok(val) => {
-                println!("Connected to endpoint: {}", endpoint);
+                info!("Connected to endpoint: {}", self.websocket.endpoint);
                 val
             }
             Err(e) => {
-                println!("Failed to connect to endpoint: {}", e);
+                warn!("Failed to connect to endpoint: {}", e);
                 return Err(e.into());
             }
         };
 
         let (mut writer, reader) = ws_stream.split();
-        
-        writer
-            .send(Message::Text(subscribe_message.to_string()))
+
+        let subscribe_messages = self
+            .connector
+            .subscribe_messages(&self.websocket, &self.symbols())
+            .await?;
+        for message in subscribe_messages {
+            writer.send(Message::Text(message.to_string())).await?;
+        }
+
+        *self.writer.lock().await = Some(writer);
+        Ok(reader)
+    }
+
+    /// Reads frames until the connection closes, fails or goes silent, or until
+    /// shutdown is signalled, in which case it returns `true`. Shutdown is only
+    /// observed between frames, so a frame is never abandoned halfway through
+    /// being applied and written.
+    async fn read(
+        &self,
+        mut reader: SplitStream<WsStream>,
+        frames: &Sender<Frame>,
+        shutdown_rx: &mut Receiver<()>,
+    ) -> bool {
+        loop {
+            let message = tokio::select! {
+                message = timeout(READ_TIMEOUT, reader.next()) => message,
+                _ = shutdown_rx.recv() => return true,
+            };
+            let message = match message {
+                Ok(message) => message,
+                Err(_) => {
+                    warn!(
+                        "No message from {} in {:?}",
+                        self.websocket.endpoint, READ_TIMEOUT
+                    );
+                    return false;
+                }
+            };
+
+            if let Some(Ok(_)) = &message {
+                self.processor
+                    .connections()
+                    .message_received(&self.connection_id);
+            }
+
+            match message {
+                Some(Ok(Message::Text(text))) => {
+                    let received_at = Instant::now();
+                    self.enqueue(frames, Frame::Text { text, received_at })
+                        .await;
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    info!("WebSocket connection closed");
+                    return false;
+                }
+                Some(Ok(Message::Ping(ping))) => {
+                    debug!("Received ping: {:?}", ping);
+                }
+                Some(Ok(Message::Pong(pong))) => {
+                    debug!("Received pong: {:?}", pong);
+                }
+                Some(Ok(Message::Binary(bin))) => {
+                    debug!("Received binary: {:?}", bin);
+                }
+                Some(Ok(Message::Frame(frame))) => {
+                    debug!("Received frame: {:?}", frame);
+                }
+                Some(Err(e)) => {
+                    warn!("Error receiving message: {}", e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Queues a frame for the parser. When the queue is full, the overflow policy
+    /// decides whether to wait or to drop the frame; a dropped frame leaves a gap
+    /// in the books, so they are invalidated and fresh snapshots requested.
+    async fn enqueue(&self, frames: &Sender<Frame>, frame: Frame) {
+        let metrics = self.processor.metrics();
+
+        match self.processor.settings().overflow {
+            OverflowPolicy::Block => {
+                let _ = frames.send(frame).await;
+            }
+            OverflowPolicy::Drop => match frames.try_send(frame) {
+                Ok(()) => self.dropping.store(false, Ordering::Relaxed),
+                Err(TrySendError::Full(_)) => {
+                    metrics.increment("pipeline_frames_dropped_total", &self.labels());
+                    if !self.dropping.swap(true, Ordering::Relaxed) {
+                        warn!(
+                            "Frame queue for {} is full, resynchronizing",
+                            self.websocket.endpoint
+                        );
+                        let symbols = self.symbols();
+                        self.invalidate(&symbols);
+                        if let Err(e) = self.resync(&symbols).await {
+                            error!("Failed to request snapshot: {}", e);
+                        }
+                    }
+                }
+                Err(TrySendError::Closed(_)) => {}
+            },
+        }
+
+        metrics.set_gauge(
+            "pipeline_frame_queue_depth",
+            &self.labels(),
+            (frames.max_capacity() - frames.capacity()) as f64,
+        );
+    }
+
+    /// Invalidates the books of `symbols` this connection feeds, if it feeds any.
+    fn invalidate(&self, symbols: &[String]) {
+        if let Some(kind) = self.connector.book_kind(&self.websocket) {
+            self.processor
+                .books()
+                .invalidate(self.connector.exchange(), symbols, kind);
+        }
+    }
+
+    /// Applies frames in the order they were read.
+    async fn parse(self: Arc<Self>, mut frames: mpsc::Receiver<Frame>) {
+        let metrics = self.processor.metrics().clone();
+
+        while let Some(frame) = frames.recv().await {
+            match frame {
+                Frame::Text { text, received_at } => {
+                    metrics.observe(
+                        "pipeline_stage_latency",
+                        &[("stage", "frame_queue")],
+                        received_at.elapsed(),
+                    );
+                    let started = Instant::now();
+                    self.handle_text(&text).await;
+                    metrics.observe(
+                        "pipeline_stage_latency",
+                        &[("stage", "apply")],
+                        started.elapsed(),
+                    );
+                }
+                Frame::Disconnected => self.invalidate(&self.symbols()),
+            }
+        }
+    }
+
+    async fn handle_text(&self, text: &str) {
+        let events = match self.connector.parse_frame(text) {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Failed to parse message: {}", e);
+                return;
+            }
+        };
+
+        let mut account = vec![];
+        let events: Vec<MarketEvent> = events
+            .into_iter()
+            .filter_map(|event| match event {
+                MarketEvent::Account(event) => {
+                    account.push(event);
+                    None
+                }
+                event => Some(event),
+            })
+            .collect();
+        self.processor.process_account(account).await;
+
+        let mut resync = vec![];
+        for (order_book, events) in self.route(events) {
+            match self
+                .processor
+                .process(self.connector.as_ref(), &order_book, events)
+                .await
+            {
+                Ok(symbols) => resync.extend(symbols),
+                Err(e) => {
+                    error!("Failed to process message: {}", e);
+                }
+            }
+        }
+
+        if !resync.is_empty() {
+            if let Err(e) = self.resync(&resync).await {
+                error!("Failed to request snapshot: {}", e);
+            }
+        }
+    }
+
+    /// Groups events by the book of their symbol, keeping each book's events in
+    /// the order they arrived. Heartbeats and status updates concern the whole
+    /// connection, which the connection registry already tracks, so they are not
+    /// routed to any book.
+    fn route(&self, events: Vec<MarketEvent>) -> Vec<(OrderBook, Vec<MarketEvent>)> {
+        let mut routed: Vec<(OrderBook, Vec<MarketEvent>)> = vec![];
+        let Ok(order_books) = self.order_books.read() else {
+            return routed;
+        };
+
+        for event in events {
+            let Some(symbol) = event.symbol() else {
+                continue;
+            };
+            let Some(order_book) = order_books.get(symbol) else {
+                warn!(
+                    "Dropping {} for {}, which is not subscribed on this connection",
+                    event.kind(),
+                    symbol
+                );
+                self.processor
+                    .metrics()
+                    .increment("websocket_unrouted_events_total", &self.labels());
+                continue;
+            };
+
+            match routed
+                .iter_mut()
+                .find(|(routed_book, _)| routed_book.symbol == order_book.symbol)
+            {
+                Some((_, events)) => events.push(event),
+                None => routed.push((order_book.clone(), vec![event])),
+            }
+        }
+
+        routed
+    }
+
+    async fn send(&self, message: Message) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        let writer = writer
+            .as_mut()
+            .ok_or_else(|| anyhow!("Not connected to {}", self.websocket.endpoint))?;
+        writer.send(message).await?;
+        Ok(())
+    }
+
+    /// Sends each of `messages` as a text frame, in order.
+    async fn send_all(&self, messages: Vec<Value>) -> Result<()> {
+        for message in messages {
+            self.send(Message::Text(message.to_string())).await?;
+        }
+        Ok(())
+    }
+
+    /// Drops the subscription for `symbols` and subscribes again, so the venue
+    /// sends a fresh snapshot to rebuild their books from.
+    async fn resync(&self, symbols: &[String]) -> Result<()> {
+        let unsubscribe_messages = self
+            .connector
+            .unsubscribe_messages(&self.websocket, symbols)
+            .await?;
+        let subscribe_messages = self
+            .connector
+            .subscribe_messages(&self.websocket, symbols)
+            .await?;
+
+        self.send_all(unsubscribe_messages).await?;
+        self.send_all(subscribe_messages).await?;
+
+        info!("Requested fresh snapshot for {:?}", symbols);
+        Ok(())
+    }
+
+    /// Unsubscribes and closes the connection.
+    async fn stop(&self) {
+        info!("Unsubscribing from WebSocket");
+        match self
+            .connector
+            .unsubscribe_messages(&self.websocket, &self.symbols())
             .await
-            .expect("Failed to send message");
-
-        let reader = Mutex::new(reader);
-        let writer = Mutex::new(writer);
-        Ok(Self {
-            writer,
-            reader,
-            unsubscribe_message: unsubscribe_message.clone(),
-            order_book,
-            redis_pool,
-            postgres_pool,
-        })
+        {
+            Ok(messages) => {
+                if let Err(e) = self.send_all(messages).await {
+                    warn!("Failed to unsubscribe: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to build unsubscribe message: {}", e),
+        }
+
+        if let Some(mut writer) = self.writer.lock().await.take() {
+            if let Err(e) = writer.close().await {
+                warn!("Failed to close WebSocket: {}", e);
+            }
+        }
     }
 }
 
 #[async_trait]
 impl EndpointHandler for KrakenWebSocketHandler {
     async fn listen(self: Arc<Self>, mut shutdown_rx: Receiver<()>) -> Result<()> {
-        let handler = self.clone();
-
-        tokio::spawn(async move {
-            tokio::select! {
-                // Listening to WebSocket Messages
-                _ = async {
-                    loop {
-                        let message = handler.reader.lock().await.next().await;
-
-                        match message {
-                            Some(Ok(Message::Text(text))) => {
-                                match parse_message(&text) {
-                                    Ok(Response::Level3Snapshot(snapshot)) => {
-                                        println!("Received level 3 snapshot: {:?}", snapshot);
-                                        // Process the snapshot
-                                        match snapshot.process_snapshot(self.redis_pool.clone(), self.postgres_pool.clone(), handler.order_book.clone()).await {
-                                            Ok(()) => {
-                                                println!("Order book snapshot processed successfully");
-                                            },
-                                            Err(e) => {
-                                                println!("Failed to process order book snapshot: {}", e);
-                                            }
-                                        }
-                                    },
-                                    Ok(Response::Level3Update(update)) => {
-                                        println!("Received level 3 update: {:?}", update);
-                                        // Process the update
-                                        match update.process_update(self.redis_pool.clone(), self.postgres_pool.clone(), handler.order_book.clone()).await {
-                                            Ok(()) => {
-                                                println!("Order book update processed successfully");
-                                            },
-                                            Err(e) => {
-                                                println!("Failed to process order book update: {}", e);
-                                            }
-                                        }
-                                    },
-                                    Ok(Response::TradeSnapshot(snapshot)) => {
-                                        println!("Received trade snapshot: {:?}", snapshot);
-                                        // Process the snapshot
-                                        match snapshot.process_snapshot(self.redis_pool.clone(), self.postgres_pool.clone(), handler.order_book.clone()).await {
-                                            Ok(()) => {
-                                                println!("Trade snapshot processed successfully");
-                                            },
-                                            Err(e) => {
-                                                println!("Failed to process trade snapshot: {}", e);
-                                            }
-                                        }
-                                    },
-                                    Ok(Response::TradeUpdate(update)) => {
-                                        println!("Received trade update: {:?}", update);
-                                        // Process the update
-                                        match update.process_update(self.redis_pool.clone(), self.postgres_pool.clone(), handler.order_book.clone()).await {
-                                            Ok(()) => {
-                                                println!("Trade update processed successfully");
-                                            },
-                                            Err(e) => {
-                                                println!("Failed to process trade update: {}", e);
-                                            }
-                                        }
-                                    },
-                                    Ok(Response::HeartBeat(heartbeat)) => {
-                                        println!("Received heartbeat: {:?}", heartbeat);
-                                    },
-                                    Ok(Response::Status(status)) => {
-                                        println!("Received status: {:?}", status);
-                                    },
-                                    Ok(Response::Level3Subscribe(subscribe)) => {
-                                        println!("Received level 3 subscribe: {:?}", subscribe);
-                                    },
-                                    Ok(Response::Level3SubscribeAck(subscribe_ack)) => {
-                                        println!("Received level 3 subscribe_ack: {:?}", subscribe_ack);
-                                    },
-                                    Ok(Response::Level3Unsubscribe(unsubscribe)) => {
-                                        println!("Received level 3 unsubscribe: {:?}", unsubscribe);
-                                    },
-                                    Ok(Response::Level3UnsubscribeAck(unsubscribe_ack)) => {
-                                        println!("Received level 3 unsubscribe_ack: {:?}", unsubscribe_ack);
-                                    },
-                                    Ok(Response::TradeSubscribe(subscribe)) => {
-                                        println!("Received trade subscribe: {:?}", subscribe);
-                                    },
-                                    Ok(Response::TradeSubscribeAck(subscribe_ack)) => {
-                                        println!("Received trade subscribe_ack: {:?}", subscribe_ack);
-                                    },
-                                    Ok(Response::TradeUnsubscribe(unsubscribe)) => {
-                                        println!("Received trade unsubscribe: {:?}", unsubscribe);
-                                    },
-                                    Ok(Response::TradeUnsubscribeAck(unsubscribe_ack)) => {
-                                        println!("Received trade unsubscribe_ack: {:?}", unsubscribe_ack);
-                                    },
-                                    Ok(Response::TokenResponse(token_response)) => {
-                                        println!("Received token_response: {:?}", token_response);
-                                    },
-                                    Err(e) => {
-                                        println!("Failed to parse message: {}", e);
-                                    }
-                                }
-                            },
-                            Some(Ok(Message::Close(_))) => {
-                                println!("WebSocket connection closed");
-                                break;
-                            },
-                            Some(Ok(Message::Ping(ping))) => {
-                                println!("Received ping: {:?}", ping);
-                            },
-                            Some(Ok(Message::Pong(pong))) => {
-                                println!("Received pong: {:?}", pong);
-                            },
-                            Some(Ok(Message::Binary(bin))) => {
-                                println!("Received binary: {:?}", bin);
-                            },
-                            Some(Ok(Message::Frame(frame))) => {
-                                println!("Received frame: {:?}", frame);
-                            },
-                            Some(Err(e)) => {
-                                println!("Error receiving message: {}", e);
-                            },
-                            None => {
-                                println!("WebSocket connection closed");
-                                break;
-                            }
-                        }
-                    }
-                } => {},
+        self.run(&mut shutdown_rx).await;
+        self.processor.connections().remove(&self.connection_id);
+        info!("Shutting down KrakenWebSocketHandler");
+        Ok(())
+    }
+
+    fn symbols(&self) -> Vec<String> {
+        self.order_books
+            .read()
+            .map(|order_books| order_books.keys().cloned().collect())
+            .unwrap_or_default()
+    }
 
-                // Handling Shutdown
-                _ = shutdown_rx.recv() => {
+    async fn subscribe(&self, order_books: Vec<OrderBook>) -> Result<()> {
+        let symbols: Vec<String> = order_books
+            .iter()
+            .map(|order_book| order_book.symbol.clone())
+            .collect();
+        // An account channel is already subscribed whatever the symbols.
+        let subscribe_messages = if self.connector.is_account_channel(&self.websocket) {
+            vec![]
+        } else {
+            self.connector
+                .subscribe_messages(&self.websocket, &symbols)
+                .await?
+        };
 
-                    // Unsubscribe from the WebSocket
-                    println!("Unsubscribing from WebSocket");
-                    handler.writer.lock().await.send(Message::Text(handler.unsubscribe_message.to_string())).await.expect("Failed to send message");
+        self.order_books
+            .write()
+            .map_err(|_| anyhow!("Order book routes lock poisoned"))?
+            .extend(
+                order_books
+                    .into_iter()
+                    .map(|order_book| (order_book.symbol.clone(), order_book)),
+            );
+        self.processor
+            .connections()
+            .set_symbols(&self.connection_id, &self.symbols());
 
-                    println!("Shutting down KrakenWebSocketHandler");
-                },
+        // Without a connection, the symbols are subscribed once it is back.
+        match self.send_all(subscribe_messages).await {
+            Ok(()) => info!("Subscribed to {:?}", symbols),
+            Err(e) => warn!("Subscribing to {:?} on reconnect: {}", symbols, e),
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, symbols: &[String]) -> Result<()> {
+        let unsubscribe_messages = if self.connector.is_account_channel(&self.websocket) {
+            vec![]
+        } else {
+            self.connector
+                .unsubscribe_messages(&self.websocket, symbols)
+                .await?
+        };
+
+        {
+            let mut order_books = self
+                .order_books
+                .write()
+                .map_err(|_| anyhow!("Order book routes lock poisoned"))?;
+            for symbol in symbols {
+                order_books.remove(symbol);
             }
-        });
+        }
+        self.processor
+            .connections()
+            .set_symbols(&self.connection_id, &self.symbols());
 
+        match self.send_all(unsubscribe_messages).await {
+            Ok(()) => info!("Unsubscribed from {:?}", symbols),
+            Err(e) => warn!("Not unsubscribing from {:?}: {}", symbols, e),
+        }
         Ok(())
     }
 }
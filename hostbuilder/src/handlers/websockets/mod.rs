@@ -1 +1 @@
-pub mod kraken_websocket_handler;
\ No newline at end of file
+pub mod kraken_websocket_handler;
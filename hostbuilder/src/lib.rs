@@ -1,29 +1,57 @@
-mod config;
+pub mod api;
+pub mod book;
+pub mod broker;
+pub mod candles;
+pub mod config;
+pub mod connections;
+pub mod connector;
 pub mod endpoint;
+pub mod events;
+pub mod fanout;
+pub mod feeds;
 pub mod get_info;
 pub mod handlers;
+pub mod metrics;
+pub mod migrations;
+pub mod order_books;
+pub mod processor;
+pub mod proto;
+pub mod reload;
+pub mod schema;
+pub mod sinks;
 
 use anyhow::Result;
+use api::ApiState;
 use async_trait::async_trait;
+use book::BookRegistry;
+use broker::tcp_publisher::TcpBrokerPublisher;
 use config::{Config, DataEngineServerConfiguration};
+use connections::ConnectionRegistry;
+use connector::{ConnectorFactory, ConnectorRegistry};
 use databaseschema::{establish_connection_pool, CustomAsyncPgConnectionManager};
 use deadpool::managed::Pool;
 use deadpool_redis::{Connection, Manager};
-use get_info::{get_exchange, get_orderbooks, get_securities};
-use handlers::{
-    rest::kraken_rest_api::KrakenRestHandler,
-    websockets::kraken_websocket_handler::KrakenWebSocketHandler,
-};
+use fanout::{FanoutHub, STREAM_CAPACITY};
+use feeds::{FeedError, FeedManager};
+use metrics::Metrics;
 use mockall::automock;
-use redis::cmd;
-use redis_utils::{create_redis_connection, create_redis_pool};
-use serde_json::json;
-use std::{env, sync::Arc};
-use tokio::{signal, sync::broadcast::channel};
+use order_books::OrderBookRegistry;
+use processor::MarketDataProcessor;
+use redis_utils::create_redis_pool;
+use reload::ConfigReloader;
+use sinks::{
+    broker_sink::BrokerSink, postgres_sink::PostgresSink, redis_keys::RedisKeys,
+    redis_sink::RedisSink, redis_stream_sink::RedisStreamSink, trade_history::TradeHistory,
+    EventSink,
+};
+use std::{env, sync::Arc, time::Duration};
+use tokio::{
+    signal,
+    sync::broadcast::channel,
+    time::{timeout_at, Instant},
+};
 use tracing::{error, info};
 
-use crate::endpoint::EndpointHandler;
-
 #[automock]
 #[async_trait]
 pub trait HostedObjectTrait {
@@ -32,8 +60,17 @@ pub trait HostedObjectTrait {
 
 pub struct HostedObject {
     config: Config,
+    config_path: String,
     redis_pool: Arc<Pool<Manager, Connection>>,
+    redis_keys: RedisKeys,
     postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+    registry: ConnectorRegistry,
+    books: Arc<BookRegistry>,
+    metrics: Arc<Metrics>,
+    connections: Arc<ConnectionRegistry>,
+    order_books: Arc<OrderBookRegistry>,
+    trades: Arc<TradeHistory>,
+    fanout: Arc<FanoutHub>,
 }
 
 impl HostedObject {
@@ -46,198 +83,213 @@ impl HostedObject {
         let config = Config::new(&config_path)?;
         let redis_pool = Arc::new(create_redis_pool().expect("Failed to create Redis pool"));
         let postgres_pool = Arc::new(establish_connection_pool());
+        let metrics = Arc::new(Metrics::new());
+        let redis_keys = RedisKeys::new(
+            &config
+                .data_engine_server_configuration
+                .redis_settings
+                .namespace,
+        );
+        let order_books = Arc::new(OrderBookRegistry::new(
+            redis_pool.clone(),
+            postgres_pool.clone(),
+            redis_keys.clone(),
+        ));
 
         Ok(Self {
             config,
+            config_path,
             redis_pool,
+            redis_keys,
             postgres_pool,
+            registry: ConnectorRegistry::default(),
+            books: Arc::new(BookRegistry::new(metrics.clone())),
+            metrics,
+            connections: Arc::new(ConnectionRegistry::new()),
+            order_books,
+            trades: Arc::new(TradeHistory::new()),
+            fanout: Arc::new(FanoutHub::new(STREAM_CAPACITY)),
         })
     }
+
+    /// The in-memory order books maintained by the engine.
+    pub fn books(&self) -> Arc<BookRegistry> {
+        self.books.clone()
+    }
+
+    /// Counters and gauges collected by the engine.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Status of every websocket connection the engine maintains.
+    pub fn connections(&self) -> Arc<ConnectionRegistry> {
+        self.connections.clone()
+    }
+
+    /// The order book of every (exchange, security) pair the engine feeds.
+    pub fn order_books(&self) -> Arc<OrderBookRegistry> {
+        self.order_books.clone()
+    }
+
+    /// Registers a connector for an exchange name used in the config, so venues
+    /// other than the built-in ones can be plugged in before `run` is called.
+    pub fn register_connector(&mut self, exchange: &str, factory: ConnectorFactory) {
+        self.registry.register(exchange, factory);
+    }
 }
 
 #[async_trait]
 impl HostedObjectTrait for HostedObject {
     async fn run(&mut self) -> Result<()> {
+        self.config.validate(&self.registry)?;
+        let stop = tokio::spawn(shutdown_signal());
         let (shutdown_tx, _) = channel(1);
-        let mut handles = vec![];
         let config = self.config.clone();
 
-        for exchange in config.exchanges.iter().cloned() {
-            if exchange.exchange == "Kraken" {
-                let kraken_exchange = get_exchange(
-                    self.redis_pool.clone(),
-                    self.postgres_pool.clone(),
-                    exchange.exchange,
-                )
-                .await;
-
-                let auth_endpoint = exchange.websocket_token.clone();
-                let api_response = KrakenRestHandler::authenticate(&auth_endpoint).await?;
-
-                for api in exchange.apis.iter().cloned() {
-                    for websocket in api.websockets.iter().cloned() {
-                        let api_response = api_response.clone();
-
-                        let securities = get_securities(
-                            self.redis_pool.clone(),
-                            self.postgres_pool.clone(),
-                            &exchange.symbols,
-                        )
-                        .await;
-
-                        let orderbooks = get_orderbooks(
-                            self.redis_pool.clone(),
-                            self.postgres_pool.clone(),
-                            securities,
-                            &kraken_exchange,
-                        )
-                        .await;
-
-                        let orderbooks = orderbooks.clone();
-                        let shutdown_tx = shutdown_tx.clone();
-                        let redis_pool = self.redis_pool.clone();
-                        let postgres_pool = self.postgres_pool.clone();
-                        let symbols = exchange.symbols.clone();
-                        let handle = tokio::spawn(async move {
-                            info!("Connecting to {:?}", &websocket.endpoint);
-
-                            match websocket.channel.as_str() {
-                                "level3" => {
-                                    let subscribe_message = json!({
-                                        "method": "subscribe",
-                                        "params": {
-                                            "channel": &websocket.channel,
-                                            "symbol": &symbols,
-                                            "depth": 10,
-                                            "snapshot": true,
-                                            "token": &api_response.get_token(),
-                                        }
-                                    });
-
-                                    let unsubscribe_message = json!({
-                                        "method": "unsubscribe",
-                                        "params": {
-                                            "channel": &websocket.channel,
-                                            "symbol": &symbols,
-                                            "token": &api_response.get_token(),
-                                        }
-                                    });
-
-                                    for orderbook in orderbooks.iter() {
-                                        match KrakenWebSocketHandler::new(
-                                            &websocket.endpoint,
-                                            orderbook.clone(),
-                                            &subscribe_message,
-                                            &unsubscribe_message,
-                                            redis_pool.clone(),
-                                            postgres_pool.clone(),
-                                        )
-                                        .await
-                                        {
-                                            Ok(handler) => {
-                                                let handler = Arc::new(handler);
-                                                match handler.listen(shutdown_tx.subscribe()).await
-                                                {
-                                                    Ok(_) => {
-                                                        println!(
-                                                            "Listening to Kraken level 3 {}",
-                                                            orderbook.symbol
-                                                        );
-                                                    }
-                                                    Err(e) => error!("Failed to listen: {}", e),
-                                                }
-                                            }
-                                            Err(e) => error!("Failed to create handler: {}", e),
-                                        }
-                                    }
-                                }
-                                "trade" => {
-                                    let subscribe_message = json!({
-                                        "method": "subscribe",
-                                        "params": {
-                                            "channel": &websocket.channel,
-                                            "symbol": &symbols,
-                                            "snapshot": true
-                                        }
-                                    });
-
-                                    let unsubscribe_message = json!({
-                                        "method": "unsubscribe",
-                                        "params": {
-                                            "channel": &websocket.channel,
-                                            "symbol": symbols,
-                                            "snapshot": true
-                                        }
-                                    });
-
-                                    for orderbook in orderbooks.iter() {
-                                        match KrakenWebSocketHandler::new(
-                                            &websocket.endpoint,
-                                            orderbook.clone(),
-                                            &subscribe_message,
-                                            &unsubscribe_message,
-                                            redis_pool.clone(),
-                                            postgres_pool.clone(),
-                                        )
-                                        .await
-                                        {
-                                            Ok(handler) => {
-                                                let handler = Arc::new(handler);
-                                                match handler.listen(shutdown_tx.subscribe()).await
-                                                {
-                                                    Ok(_) => {
-                                                        println!(
-                                                            "Listening to Kraken trade {}",
-                                                            orderbook.symbol
-                                                        );
-                                                    }
-                                                    Err(e) => error!("Failed to listen: {}", e),
-                                                }
-                                            }
-                                            Err(e) => error!("Failed to create handler: {}", e),
-                                        }
-                                    }
-                                }
-                                _ => {
-                                    info!("Unknown websocket type: {}", websocket.channel.as_str());
-                                }
-                            };
-                        });
-                        handles.push(handle);
-                    }
-                }
-            } else {
-                println!("Unknown exchange: {}", exchange.exchange);
-            }
-        }
+        let broker = &config.message_broker_server_configuration;
+        let redis_settings = &self.host().redis_settings;
+        let redis_sink = Arc::new(RedisSink::new(
+            self.redis_pool.clone(),
+            self.redis_keys.clone(),
+        ));
+        redis_sink.migrate(&config.exchanges).await?;
+        migrations::migrate(&self.postgres_pool).await?;
+        self.order_books.load().await?;
+
+        let broker_sink = Arc::new(BrokerSink::new(
+            Arc::new(TcpBrokerPublisher::new(
+                &broker.message_broker_server_settings,
+            )),
+            &config.topics,
+            &broker.publisher_settings,
+            self.metrics.clone(),
+        ));
+
+        let sinks: Vec<Arc<dyn EventSink>> = vec![
+            self.fanout.clone(),
+            redis_sink.clone(),
+            Arc::new(RedisStreamSink::new(
+                self.redis_pool.clone(),
+                self.redis_keys.clone(),
+                redis_settings.stream_max_len,
+            )),
+            Arc::new(PostgresSink::new(
+                self.postgres_pool.clone(),
+                &self.host().postgres_settings,
+                self.metrics.clone(),
+            )),
+            self.trades.clone(),
+            broker_sink.clone(),
+        ];
+        let processor = Arc::new(MarketDataProcessor::new(
+            self.books.clone(),
+            sinks,
+            self.metrics.clone(),
+            self.connections.clone(),
+            &self.host().pipeline_settings,
+        ));
 
-        let redis_pool = self.redis_pool.clone();
+        let feeds = Arc::new(FeedManager::new(
+            self.registry.clone(),
+            self.redis_pool.clone(),
+            self.redis_keys.clone(),
+            self.postgres_pool.clone(),
+            self.order_books.clone(),
+            processor.clone(),
+        ));
 
-        let shutdown_handle = tokio::spawn(async move {
-            if let Err(e) = signal::ctrl_c().await {
-                error!("Failed to listen for shutdown signal: {}", e);
+        let api_state = ApiState {
+            feeds: feeds.clone(),
+            books: self.books.clone(),
+            trades: self.trades.clone(),
+            connections: self.connections.clone(),
+            metrics: self.metrics.clone(),
+            fanout: self.fanout.clone(),
+            admin_token: self.host().data_engine_server_settings.admin_token.clone(),
+        };
+        let api_settings = self.host().data_engine_server_settings.clone();
+        let api_shutdown_rx = shutdown_tx.subscribe();
+        let api_server = tokio::spawn(async move {
+            if let Err(e) = api::serve(&api_settings, api_state, api_shutdown_rx).await {
+                error!("HTTP API stopped: {}", e);
             }
+        });
 
-            let mut connection = create_redis_connection(&redis_pool)
-                .await
-                .expect("Failed to create redis connection");
-            match cmd("FLUSHALL").query_async::<_, ()>(&mut connection).await {
-                Ok(_) => {
-                    println!("Successfully flushed Redis cache");
-                }
-                Err(e) => {
-                    println!("Failed to flush Redis cache: {}", e);
+        for exchange in config.exchanges.iter() {
+            match feeds.add_exchange(exchange).await {
+                Ok(()) => {}
+                Err(FeedError::UnknownExchange(exchange)) => {
+                    error!("Unknown exchange: {}", exchange);
                 }
+                Err(e) => return Err(e.into()),
             }
+        }
 
-            let _ = shutdown_tx.send(());
-        });
+        if self.host().reload_settings.enabled {
+            let reloader = ConfigReloader::new(
+                &self.config_path,
+                config.clone(),
+                feeds.clone(),
+                broker_sink,
+                self.registry.clone(),
+                self.metrics.clone(),
+            );
+            tokio::spawn(reloader.watch(shutdown_tx.subscribe()));
+        }
 
-        for handle in handles {
-            let _ = handle.await;
+        let _ = stop.await;
+        info!("Shutting down");
+        let settings = &self.host().shutdown_settings;
+        let deadline = Instant::now() + Duration::from_millis(settings.drain_timeout_ms);
+
+        // Feeds stop reading between frames and unsubscribe, and the API stops
+        // accepting requests. Only then are the sinks drained, so nothing is
+        // written after the flush.
+        let _ = shutdown_tx.send(());
+        feeds.stop(deadline).await;
+        processor.flush(deadline).await;
+
+        // The cache is left in place for readers and the next run, unless the
+        // engine's own keys are configured to expire.
+        if let Some(ttl) = settings.expire_keys_after_secs {
+            match redis_sink.expire_keys(Duration::from_secs(ttl)).await {
+                Ok(count) => info!("Redis keys expiring in {}s: {}", ttl, count),
+                Err(e) => error!("Failed to expire Redis keys: {}", e),
+            }
         }
 
-        let _ = shutdown_handle.await;
+        let _ = timeout_at(deadline, api_server).await;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Resolves on ctrl-c or, on Unix, SIGTERM, which is how Kubernetes stops a pod.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = signal::ctrl_c().await {
+            error!("Failed to listen for ctrl-c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::BTreeMap, fmt::Write, sync::RwLock, time::Duration};
+
+type MetricKey = (String, Vec<(String, String)>);
+
+/// Process-wide counters and gauges, rendered in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    counters: RwLock<BTreeMap<MetricKey, u64>>,
+    gauges: RwLock<BTreeMap<MetricKey, f64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment(&self, name: &str, labels: &[(&str, &str)]) {
+        self.add(name, labels, 1);
+    }
+
+    pub fn add(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        if let Ok(mut counters) = self.counters.write() {
+            *counters.entry(key(name, labels)).or_insert(0) += value;
+        }
+    }
+
+    pub fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if let Ok(mut gauges) = self.gauges.write() {
+            gauges.insert(key(name, labels), value);
+        }
+    }
+
+    /// Records how long something took: `{name}_milliseconds` holds the latest
+    /// duration, while `{name}_milliseconds_sum` and `{name}_count` accumulate so
+    /// an average can be derived.
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], duration: Duration) {
+        self.set_gauge(
+            &format!("{}_milliseconds", name),
+            labels,
+            duration.as_secs_f64() * 1000.0,
+        );
+        self.add(
+            &format!("{}_milliseconds_sum", name),
+            labels,
+            duration.as_millis() as u64,
+        );
+        self.increment(&format!("{}_count", name), labels);
+    }
+
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.counters
+            .read()
+            .ok()
+            .and_then(|counters| counters.get(&key(name, labels)).copied())
+            .unwrap_or(0)
+    }
+
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.gauges
+            .read()
+            .ok()
+            .and_then(|gauges| gauges.get(&key(name, labels)).copied())
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        if let Ok(counters) = self.counters.read() {
+            for ((name, labels), value) in counters.iter() {
+                let _ = writeln!(output, "{}{} {}", name, render_labels(labels), value);
+            }
+        }
+        if let Ok(gauges) = self.gauges.read() {
+            for ((name, labels), value) in gauges.iter() {
+                let _ = writeln!(output, "{}{} {}", name, render_labels(labels), value);
+            }
+        }
+
+        output
+    }
+}
+
+fn key(name: &str, labels: &[(&str, &str)]) -> MetricKey {
+    (
+        name.to_string(),
+        labels
+            .iter()
+            .map(|(label, value)| (label.to_string(), value.to_string()))
+            .collect(),
+    )
+}
+
+fn render_labels(labels: &[(String, String)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| format!("{}=\"{}\"", label, value.replace('"', "\\\"")))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use databaseschema::CustomAsyncPgConnectionManager;
+use deadpool::managed::Pool;
+use diesel::{result::Error as DieselError, ExpressionMethods, QueryDsl};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl, SimpleAsyncConnection,
+};
+use tracing::info;
+
+use crate::schema::data_engine_migrations;
+
+/// Migrations creating the tables in [`crate::schema`], applied in order. Each
+/// is applied once and recorded in `data_engine_migrations`; a released
+/// migration is never edited, only followed by a new one.
+const MIGRATIONS: &[(i32, &str, &str)] = &[
+    (
+        1,
+        "price_levels",
+        include_str!("../migrations/0001_price_levels.sql"),
+    ),
+    (2, "tickers", include_str!("../migrations/0002_tickers.sql")),
+    (3, "candles", include_str!("../migrations/0003_candles.sql")),
+    (
+        4,
+        "instruments",
+        include_str!("../migrations/0004_instruments.sql"),
+    ),
+    (5, "account", include_str!("../migrations/0005_account.sql")),
+];
+
+/// Key of the advisory lock held while migrating, so instances starting together
+/// apply each migration once.
+const MIGRATION_LOCK: i64 = 0x0044_4154_4145_4e47;
+
+const CREATE_MIGRATIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS data_engine_migrations (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Applies the migrations not yet recorded, all in one transaction.
+pub async fn migrate(postgres_pool: &Pool<CustomAsyncPgConnectionManager>) -> Result<()> {
+    let mut connection = postgres_pool
+        .get()
+        .await
+        .map_err(|e| anyhow!("No Postgres connection: {}", e))?;
+
+    let applied = connection
+        .transaction::<_, DieselError, _>(|connection| {
+            async move {
+                connection
+                    .batch_execute(&format!("SELECT pg_advisory_xact_lock({})", MIGRATION_LOCK))
+                    .await?;
+                connection.batch_execute(CREATE_MIGRATIONS_TABLE).await?;
+                let done: Vec<i32> = data_engine_migrations::table
+                    .select(data_engine_migrations::version)
+                    .load(connection)
+                    .await?;
+
+                let mut applied = vec![];
+                for (version, name, sql) in MIGRATIONS {
+                    if done.contains(version) {
+                        continue;
+                    }
+                    connection.batch_execute(sql).await?;
+                    diesel::insert_into(data_engine_migrations::table)
+                        .values((
+                            data_engine_migrations::version.eq(version),
+                            data_engine_migrations::name.eq(name),
+                        ))
+                        .execute(connection)
+                        .await?;
+                    applied.push(*name);
+                }
+                Ok(applied)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    if !applied.is_empty() {
+        info!("Applied Postgres migrations: {:?}", applied);
+    }
+    Ok(())
+}
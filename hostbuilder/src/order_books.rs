@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use databaseschema::{
+    models::{Exchange, NewOrderBook, OrderBook, Security},
+    ops::order_book_ops::create_orderbook,
+    schema::order_books,
+    CustomAsyncPgConnectionManager,
+};
+use deadpool::managed::Pool;
+use deadpool_redis::{Connection, Manager};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use redis_utils::create_redis_connection;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::sinks::redis_keys::RedisKeys;
+
+/// The order book of every (exchange, security) pair the engine feeds, loaded
+/// from Postgres at startup. A pair without a book gets one on first use, so
+/// symbols can be added while the engine runs.
+pub struct OrderBookRegistry {
+    redis_pool: Arc<Pool<Manager, Connection>>,
+    keys: RedisKeys,
+    postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+    /// Keyed by exchange id, then security id. Held while a book is created, so
+    /// two callers never create the same book.
+    books: Mutex<HashMap<(Uuid, Uuid), OrderBook>>,
+}
+
+impl OrderBookRegistry {
+    pub fn new(
+        redis_pool: Arc<Pool<Manager, Connection>>,
+        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+        keys: RedisKeys,
+    ) -> Self {
+        Self {
+            redis_pool,
+            keys,
+            postgres_pool,
+            books: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Loads every stored order book, returning how many there are. Where a pair
+    /// has several books, the oldest is kept.
+    pub async fn load(&self) -> Result<usize> {
+        let mut connection = self
+            .postgres_pool
+            .get()
+            .await
+            .map_err(|e| anyhow!("No Postgres connection: {}", e))?;
+        let stored: Vec<OrderBook> = order_books::table
+            .order(order_books::created_at.asc())
+            .select(OrderBook::as_select())
+            .load(&mut connection)
+            .await?;
+
+        let mut books = self.books.lock().await;
+        for order_book in stored {
+            books
+                .entry((order_book.exchange_id, order_book.security_id))
+                .or_insert(order_book);
+        }
+
+        info!("Loaded {} order books", books.len());
+        Ok(books.len())
+    }
+
+    /// The book of `security` on `exchange`, if there is one yet.
+    pub async fn get(&self, exchange: &Exchange, security: &Security) -> Option<OrderBook> {
+        self.books
+            .lock()
+            .await
+            .get(&(exchange.exchange_id, security.security_id))
+            .cloned()
+    }
+
+    /// Every known book.
+    pub async fn all(&self) -> Vec<OrderBook> {
+        self.books.lock().await.values().cloned().collect()
+    }
+
+    /// The book of `security` on `exchange`, created if it does not exist yet.
+    /// Books are also cached in Redis under [`RedisKeys::order_book`] for
+    /// readers outside the engine.
+    pub async fn resolve(&self, exchange: &Exchange, security: &Security) -> Result<OrderBook> {
+        let key = (exchange.exchange_id, security.security_id);
+        let mut books = self.books.lock().await;
+        if let Some(order_book) = books.get(&key) {
+            return Ok(order_book.clone());
+        }
+
+        // Another instance may have created the book since it was loaded.
+        let order_book = match self.find(exchange, security).await? {
+            Some(order_book) => order_book,
+            None => {
+                info!(
+                    "Creating order book for {} on {}",
+                    security.symbol, exchange.exchange
+                );
+                let new_orderbook = NewOrderBook::new(
+                    &security.symbol,
+                    &exchange.exchange,
+                    security.security_id,
+                    exchange.exchange_id,
+                );
+                create_orderbook(self.postgres_pool.clone(), new_orderbook).await
+            }
+        };
+
+        if let Err(e) = self.cache(&order_book).await {
+            error!("Failed to save order book to Redis: {}", e);
+        }
+        books.insert(key, order_book.clone());
+        Ok(order_book)
+    }
+
+    async fn find(&self, exchange: &Exchange, security: &Security) -> Result<Option<OrderBook>> {
+        let mut connection = self
+            .postgres_pool
+            .get()
+            .await
+            .map_err(|e| anyhow!("No Postgres connection: {}", e))?;
+        let order_book = order_books::table
+            .filter(order_books::exchange_id.eq(exchange.exchange_id))
+            .filter(order_books::security_id.eq(security.security_id))
+            .order(order_books::created_at.asc())
+            .select(OrderBook::as_select())
+            .first(&mut connection)
+            .await
+            .optional()?;
+        Ok(order_book)
+    }
+
+    async fn cache(&self, order_book: &OrderBook) -> Result<()> {
+        let mut connection = create_redis_connection(&self.redis_pool).await?;
+        let updated_at = order_book
+            .updated_at
+            .map(|updated_at| updated_at.to_string())
+            .unwrap_or_default();
+
+        redis::cmd("HSET")
+            .arg(self.keys.order_book(order_book.order_book_id))
+            .arg("created_at")
+            .arg(order_book.created_at.to_string())
+            .arg("updated_at")
+            .arg(updated_at)
+            .arg("symbol")
+            .arg(&order_book.symbol)
+            .arg("exchange")
+            .arg(&order_book.exchange)
+            .arg("security_id")
+            .arg(order_book.security_id.to_string())
+            .arg("exchange_id")
+            .arg(order_book.exchange_id.to_string())
+            .arg("order_book_id")
+            .arg(order_book.order_book_id.to_string())
+            .arg("buy_order_book_id")
+            .arg(order_book.buy_order_book_id.to_string())
+            .arg("sell_order_book_id")
+            .arg(order_book.sell_order_book_id.to_string())
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,576 @@
+use anyhow::{anyhow, Result};
+use databaseschema::models::OrderBook;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError, Receiver, Sender},
+        oneshot,
+    },
+    time::{timeout_at, Instant},
+};
+use tracing::{error, warn};
+
+use crate::{
+    book::{level3::RestingOrder, BookRegistry},
+    candles::CandleTracker,
+    config::{OverflowPolicy, PipelineSettings},
+    connections::ConnectionRegistry,
+    connector::ExchangeConnector,
+    events::{AccountEvent, MarketEvent, Side},
+    metrics::Metrics,
+    sinks::{BookContext, EventSink},
+};
+
+/// Events applied to a book together, on their way to the sinks.
+struct SinkBatch {
+    context: BookContext,
+    events: Vec<MarketEvent>,
+    dispatched_at: Instant,
+}
+
+/// Account activity on its way to the sinks.
+struct AccountBatch {
+    events: Vec<AccountEvent>,
+    dispatched_at: Instant,
+}
+
+enum SinkCommand {
+    Handle(Arc<SinkBatch>),
+    HandleAccount(Arc<AccountBatch>),
+    /// Hand over everything queued before this command, flush the sink, then
+    /// acknowledge.
+    Flush(oneshot::Sender<Result<()>>),
+}
+
+/// A sink fed by its own task, so one slow sink does not hold up the others.
+struct SinkStage {
+    name: String,
+    sender: Sender<SinkCommand>,
+    /// Books the sink missed events of because a batch was dropped, keyed by
+    /// (exchange, symbol), with the last known state of every order those
+    /// events touched. The sink is resynced from the books with its next batch
+    /// for the symbol.
+    stale: Mutex<HashMap<(String, String), HashMap<String, RestingOrder>>>,
+}
+
+impl SinkStage {
+    fn take_stale(&self, key: &(String, String)) -> Option<HashMap<String, RestingOrder>> {
+        self.stale.lock().ok()?.remove(key)
+    }
+
+    fn mark_stale(&self, key: (String, String), touched: HashMap<String, RestingOrder>) {
+        if let Ok(mut stale) = self.stale.lock() {
+            stale.insert(key, touched);
+        }
+    }
+}
+
+/// Applies normalized events to the in-memory books and hands them to every sink.
+///
+/// Each sink has a bounded queue drained by its own task, so applying a frame
+/// only waits for the sinks when one of them has fallen `sink_capacity` batches
+/// behind, and then only if the overflow policy says to wait.
+pub struct MarketDataProcessor {
+    books: Arc<BookRegistry>,
+    candles: CandleTracker,
+    stages: Vec<SinkStage>,
+    metrics: Arc<Metrics>,
+    connections: Arc<ConnectionRegistry>,
+    settings: PipelineSettings,
+}
+
+impl MarketDataProcessor {
+    pub fn new(
+        books: Arc<BookRegistry>,
+        sinks: Vec<Arc<dyn EventSink>>,
+        metrics: Arc<Metrics>,
+        connections: Arc<ConnectionRegistry>,
+        settings: &PipelineSettings,
+    ) -> Self {
+        let stages = sinks
+            .into_iter()
+            .map(|sink| {
+                let (sender, receiver) = mpsc::channel(settings.sink_capacity.max(1));
+                let name = sink.name().to_string();
+                tokio::spawn(run_sink(sink, receiver, metrics.clone()));
+                SinkStage {
+                    name,
+                    sender,
+                    stale: Mutex::new(HashMap::new()),
+                }
+            })
+            .collect();
+
+        Self {
+            books,
+            candles: CandleTracker::new(),
+            stages,
+            metrics,
+            connections,
+            settings: settings.clone(),
+        }
+    }
+
+    pub fn books(&self) -> &Arc<BookRegistry> {
+        &self.books
+    }
+
+    pub fn candles(&self) -> &CandleTracker {
+        &self.candles
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn connections(&self) -> &Arc<ConnectionRegistry> {
+        &self.connections
+    }
+
+    pub fn settings(&self) -> &PipelineSettings {
+        &self.settings
+    }
+
+    /// Processes events parsed by `connector` and returns the symbols whose book was
+    /// invalidated and needs a fresh snapshot from the venue.
+    pub async fn process(
+        &self,
+        connector: &dyn ExchangeConnector,
+        order_book: &OrderBook,
+        events: Vec<MarketEvent>,
+    ) -> Result<Vec<String>> {
+        if events.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let events = self.candles.track(events);
+        let has_book_events = events.iter().any(MarketEvent::is_book_event);
+        let has_level_events = events.iter().any(MarketEvent::is_level_event);
+        let outcome = self
+            .books
+            .apply(events, &|book| connector.book_checksum(book), &|book| {
+                connector.level_book_checksum(book)
+            })?;
+
+        let total_volume = if has_book_events {
+            self.books
+                .with_book(&order_book.exchange, &order_book.symbol, |book| {
+                    book.total_volume().clone()
+                })
+        } else if has_level_events {
+            self.books
+                .with_level_book(&order_book.exchange, &order_book.symbol, |book| {
+                    book.total_volume()
+                })
+        } else {
+            None
+        };
+
+        if !outcome.events.is_empty() {
+            let context = BookContext {
+                order_book: order_book.clone(),
+                total_volume,
+            };
+            self.dispatch_book(context, outcome.events).await;
+        }
+
+        Ok(outcome.resync)
+    }
+
+    /// Hands activity on our own account to the sinks, through the same queues
+    /// as market data. It concerns no book, so nothing is applied.
+    pub async fn process_account(&self, events: Vec<AccountEvent>) {
+        if events.is_empty() {
+            return;
+        }
+
+        let batch = Arc::new(AccountBatch {
+            events,
+            dispatched_at: Instant::now(),
+        });
+        for stage in self.stages.iter() {
+            self.send(stage, SinkCommand::HandleAccount(batch.clone()))
+                .await;
+        }
+    }
+
+    /// Hands events applied to a book to every sink. A sink that had a batch
+    /// for the book dropped instead gets deletes for the orders it may still
+    /// hold and snapshots of the books, followed by the batch's other events,
+    /// since the snapshots already include the batch's book events.
+    async fn dispatch_book(&self, context: BookContext, events: Vec<MarketEvent>) {
+        let exchange = context.order_book.exchange.clone();
+        let symbol = context.order_book.symbol.clone();
+        let key = (exchange, symbol);
+        let touches_book = events
+            .iter()
+            .any(|event| event.is_book_event() || event.is_level_event());
+        let batch = Arc::new(SinkBatch {
+            context,
+            events,
+            dispatched_at: Instant::now(),
+        });
+
+        for stage in self.stages.iter() {
+            let mut stale = stage.take_stale(&key);
+            if let Some(touched) = stale.as_mut() {
+                record_orders(touched, &batch.events);
+            }
+
+            let resync = stale
+                .as_ref()
+                .and_then(|touched| self.books.resync_events(&key.0, &key.1, touched));
+            let resynced = resync.is_some();
+            let command = match resync {
+                Some(mut events) => {
+                    events.extend(
+                        batch
+                            .events
+                            .iter()
+                            .filter(|event| !event.is_book_event() && !event.is_level_event())
+                            .cloned(),
+                    );
+                    SinkCommand::Handle(Arc::new(SinkBatch {
+                        context: batch.context.clone(),
+                        events,
+                        dispatched_at: batch.dispatched_at,
+                    }))
+                }
+                None => SinkCommand::Handle(batch.clone()),
+            };
+
+            let queued = self.send(stage, command).await;
+            if queued && resynced {
+                self.metrics
+                    .increment("pipeline_books_resynced_total", &[("stage", &stage.name)]);
+                continue;
+            }
+            if !queued && stale.is_none() && touches_book {
+                warn!(
+                    "Sink {} missed book events for {} {}, resyncing it with the next batch",
+                    stage.name, key.0, key.1
+                );
+                let mut touched = HashMap::new();
+                record_orders(&mut touched, &batch.events);
+                stale = Some(touched);
+            }
+            if let Some(touched) = stale {
+                stage.mark_stale(key.clone(), touched);
+            }
+        }
+    }
+
+    /// Queues `command` for `stage` as the overflow policy says, returning
+    /// whether it was queued.
+    async fn send(&self, stage: &SinkStage, command: SinkCommand) -> bool {
+        let queued = match self.settings.overflow {
+            OverflowPolicy::Block => stage.sender.send(command).await.is_ok(),
+            OverflowPolicy::Drop => match stage.sender.try_send(command) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    self.metrics
+                        .increment("pipeline_batches_dropped_total", &[("stage", &stage.name)]);
+                    return false;
+                }
+                Err(TrySendError::Closed(_)) => false,
+            },
+        };
+        if !queued {
+            error!("Sink {} has stopped", stage.name);
+        }
+
+        self.metrics.set_gauge(
+            "pipeline_queue_depth",
+            &[("stage", &stage.name)],
+            (stage.sender.max_capacity() - stage.sender.capacity()) as f64,
+        );
+        queued
+    }
+
+    /// Hands every queued batch to its sink and flushes the sinks, giving up on
+    /// those still busy at `deadline`.
+    pub async fn flush(&self, deadline: Instant) {
+        for stage in self.stages.iter() {
+            let flushed = timeout_at(deadline, async {
+                let (ack, done) = oneshot::channel();
+                stage
+                    .sender
+                    .send(SinkCommand::Flush(ack))
+                    .await
+                    .map_err(|_| anyhow!("Sink has stopped"))?;
+                done.await
+                    .map_err(|_| anyhow!("Sink stopped before flushing"))?
+            })
+            .await;
+
+            match flushed {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("Sink {} failed to flush: {}", stage.name, e),
+                Err(_) => error!("Sink {} did not flush before the deadline", stage.name),
+            }
+        }
+    }
+}
+
+/// Records the last known state of every order `events` touch.
+fn record_orders(touched: &mut HashMap<String, RestingOrder>, events: &[MarketEvent]) {
+    for event in events {
+        match event {
+            MarketEvent::OrderAdded(order)
+            | MarketEvent::OrderModified(order)
+            | MarketEvent::OrderDeleted(order) => {
+                touched.insert(
+                    order.order_id.clone(),
+                    RestingOrder {
+                        order_id: order.order_id.clone(),
+                        side: order.side,
+                        price: order.price.clone(),
+                        quantity: order.quantity.clone(),
+                        timestamp: order.timestamp,
+                    },
+                );
+            }
+            MarketEvent::BookSnapshot(snapshot) => {
+                for (side, orders) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+                    for order in orders {
+                        touched.insert(
+                            order.order_id.clone(),
+                            RestingOrder {
+                                order_id: order.order_id.clone(),
+                                side,
+                                price: order.price.clone(),
+                                quantity: order.quantity.clone(),
+                                timestamp: order.timestamp,
+                            },
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Feeds `sink` its queued batches in order.
+async fn run_sink(
+    sink: Arc<dyn EventSink>,
+    mut receiver: Receiver<SinkCommand>,
+    metrics: Arc<Metrics>,
+) {
+    while let Some(command) = receiver.recv().await {
+        match command {
+            SinkCommand::Handle(batch) => {
+                if let Err(e) = sink.handle(&batch.context, &batch.events).await {
+                    error!("Sink {} failed to handle events: {}", sink.name(), e);
+                }
+                metrics.observe(
+                    "pipeline_stage_latency",
+                    &[("stage", sink.name())],
+                    batch.dispatched_at.elapsed(),
+                );
+            }
+            SinkCommand::HandleAccount(batch) => {
+                if let Err(e) = sink.handle_account(&batch.events).await {
+                    error!(
+                        "Sink {} failed to handle account events: {}",
+                        sink.name(),
+                        e
+                    );
+                }
+                metrics.observe(
+                    "pipeline_stage_latency",
+                    &[("stage", sink.name())],
+                    batch.dispatched_at.elapsed(),
+                );
+            }
+            SinkCommand::Flush(ack) => {
+                let _ = ack.send(sink.flush().await);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{BookOrder, BookSnapshot, OrderEvent, Trade};
+    use async_trait::async_trait;
+    use bigdecimal::BigDecimal;
+    use chrono::Utc;
+    use std::time::Duration;
+    use tokio::sync::{Notify, Semaphore};
+    use uuid::Uuid;
+
+    /// Records the batches it is handed, each only once `gate` lets it through.
+    struct GatedSink {
+        started: Notify,
+        gate: Semaphore,
+        batches: Mutex<Vec<Vec<MarketEvent>>>,
+    }
+
+    #[async_trait]
+    impl EventSink for GatedSink {
+        fn name(&self) -> &str {
+            "gated"
+        }
+
+        async fn handle(&self, _context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+            self.started.notify_one();
+            self.gate.acquire().await?.forget();
+            self.batches.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    fn context() -> BookContext {
+        BookContext {
+            order_book: OrderBook {
+                order_book_id: Uuid::nil(),
+                created_at: Utc::now().naive_utc(),
+                updated_at: None,
+                symbol: "BTC/USD".to_string(),
+                exchange: "kraken".to_string(),
+                security_id: Uuid::nil(),
+                exchange_id: Uuid::nil(),
+                buy_order_book_id: Uuid::nil(),
+                sell_order_book_id: Uuid::nil(),
+                total_volume: decimal("0"),
+            },
+            total_volume: None,
+        }
+    }
+
+    fn order(order_id: &str, price: &str) -> OrderEvent {
+        OrderEvent {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            side: Side::Buy,
+            order_id: order_id.to_string(),
+            price: decimal(price),
+            quantity: decimal("1"),
+            timestamp: Utc::now(),
+            sequence: None,
+            checksum: None,
+        }
+    }
+
+    fn snapshot(order_id: &str, price: &str) -> MarketEvent {
+        MarketEvent::BookSnapshot(BookSnapshot {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            bids: vec![BookOrder {
+                order_id: order_id.to_string(),
+                price: decimal(price),
+                quantity: decimal("1"),
+                timestamp: Utc::now(),
+            }],
+            asks: vec![],
+            sequence: None,
+            checksum: None,
+        })
+    }
+
+    fn trade() -> MarketEvent {
+        MarketEvent::Trade(Trade {
+            exchange: "kraken".to_string(),
+            symbol: "BTC/USD".to_string(),
+            side: Side::Sell,
+            price: decimal("99"),
+            quantity: decimal("1"),
+            order_type: "limit".to_string(),
+            trade_id: 1,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn process(processor: &MarketDataProcessor, events: Vec<MarketEvent>) {
+        let outcome = processor
+            .books()
+            .apply(events, &|_| None, &|_| None)
+            .unwrap();
+        processor.dispatch_book(context(), outcome.events).await;
+    }
+
+    #[tokio::test]
+    async fn resyncs_a_sink_from_the_books_after_dropping_its_batch() {
+        let metrics = Arc::new(Metrics::new());
+        let sink = Arc::new(GatedSink {
+            started: Notify::new(),
+            gate: Semaphore::new(0),
+            batches: Mutex::new(vec![]),
+        });
+        let processor = MarketDataProcessor::new(
+            Arc::new(BookRegistry::new(metrics.clone())),
+            vec![sink.clone()],
+            metrics.clone(),
+            Arc::new(ConnectionRegistry::new()),
+            &PipelineSettings {
+                sink_capacity: 1,
+                overflow: OverflowPolicy::Drop,
+                ..PipelineSettings::default()
+            },
+        );
+
+        // The sink holds the first batch, the second fills its queue and the
+        // third is dropped.
+        process(&processor, vec![snapshot("A", "100")]).await;
+        sink.started.notified().await;
+        process(&processor, vec![MarketEvent::OrderAdded(order("B", "101"))]).await;
+        process(
+            &processor,
+            vec![MarketEvent::OrderDeleted(order("A", "100"))],
+        )
+        .await;
+        assert_eq!(
+            metrics.counter("pipeline_batches_dropped_total", &[("stage", "gated")]),
+            1
+        );
+
+        sink.gate.add_permits(100);
+        processor
+            .flush(Instant::now() + Duration::from_secs(5))
+            .await;
+        process(
+            &processor,
+            vec![MarketEvent::OrderAdded(order("C", "102")), trade()],
+        )
+        .await;
+        processor
+            .flush(Instant::now() + Duration::from_secs(5))
+            .await;
+        process(&processor, vec![MarketEvent::OrderAdded(order("D", "103"))]).await;
+        processor
+            .flush(Instant::now() + Duration::from_secs(5))
+            .await;
+
+        let batches = sink.batches.lock().unwrap();
+        assert_eq!(batches.len(), 4);
+        let resync = &batches[2];
+        assert_eq!(resync.len(), 3);
+        assert!(matches!(&resync[0], MarketEvent::OrderDeleted(order) if order.order_id == "A"));
+        let MarketEvent::BookSnapshot(snapshot) = &resync[1] else {
+            panic!("expected a snapshot, got {:?}", resync[1]);
+        };
+        let bids: Vec<&str> = snapshot
+            .bids
+            .iter()
+            .map(|order| order.order_id.as_str())
+            .collect();
+        assert_eq!(bids, ["C", "B"]);
+        assert!(matches!(resync[2], MarketEvent::Trade(_)));
+        assert!(
+            matches!(&batches[3][..], [MarketEvent::OrderAdded(order)] if order.order_id == "D")
+        );
+        assert_eq!(
+            metrics.counter("pipeline_books_resynced_total", &[("stage", "gated")]),
+            1
+        );
+    }
+}
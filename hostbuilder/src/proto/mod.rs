@@ -0,0 +1,329 @@
+//! Protobuf wire format for market data, generated from `proto/` at build time,
+//! and conversions from the internal event model.
+
+pub mod v1 {
+    include!(concat!(env!("OUT_DIR"), "/marketdata.v1.rs"));
+}
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    book::level3::Level3Book,
+    events::{
+        AccountEvent, AccountOrder, Balance, BookLevel, BookOrder, BookSnapshot, Candle, Fill,
+        Instrument, LedgerEntry, LevelEvent, LevelSnapshot, MarketEvent, OrderEvent, Side,
+        StatusEvent, Ticker, Trade,
+    },
+};
+use v1::{account_event, market_data_event::Event};
+
+impl v1::MarketDataEvent {
+    /// Wraps `event` in the envelope. Heartbeats have no wire representation,
+    /// and account activity goes out in [`v1::AccountEvent`]s.
+    pub fn from_event(event: &MarketEvent) -> Option<Self> {
+        let event = match event {
+            MarketEvent::BookSnapshot(snapshot) => Event::Snapshot(snapshot.into()),
+            MarketEvent::OrderAdded(order) => {
+                Event::Delta(v1::OrderDelta::new(v1::DeltaAction::Add, order))
+            }
+            MarketEvent::OrderModified(order) => {
+                Event::Delta(v1::OrderDelta::new(v1::DeltaAction::Modify, order))
+            }
+            MarketEvent::OrderDeleted(order) => {
+                Event::Delta(v1::OrderDelta::new(v1::DeltaAction::Delete, order))
+            }
+            MarketEvent::LevelSnapshot(snapshot) => Event::LevelSnapshot(snapshot.into()),
+            MarketEvent::LevelUpdated(level) => Event::LevelUpdate(level.into()),
+            MarketEvent::Trade(trade) => Event::Trade(trade.into()),
+            MarketEvent::Ticker(ticker) => Event::Ticker(ticker.as_ref().into()),
+            MarketEvent::Candle(candle) => Event::Candle(candle.as_ref().into()),
+            MarketEvent::CandleClosed(candle) => Event::CandleClosed(candle.as_ref().into()),
+            MarketEvent::Instrument(instrument) => Event::Instrument(instrument.as_ref().into()),
+            MarketEvent::Status(status) => Event::Status(status.into()),
+            MarketEvent::Account(_) | MarketEvent::Heartbeat(_) => return None,
+        };
+        Some(Self { event: Some(event) })
+    }
+}
+
+impl From<&AccountEvent> for v1::AccountEvent {
+    fn from(event: &AccountEvent) -> Self {
+        let event = match event {
+            AccountEvent::Order(order) => account_event::Event::Order(order.as_ref().into()),
+            AccountEvent::Fill(fill) => account_event::Event::Fill(fill.as_ref().into()),
+            AccountEvent::Balance(balance) => {
+                account_event::Event::Balance(balance.as_ref().into())
+            }
+        };
+        Self { event: Some(event) }
+    }
+}
+
+impl From<v1::Bbo> for v1::MarketDataEvent {
+    fn from(bbo: v1::Bbo) -> Self {
+        Self {
+            event: Some(Event::Bbo(bbo)),
+        }
+    }
+}
+
+impl From<Side> for v1::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => v1::Side::Buy,
+            Side::Sell => v1::Side::Sell,
+        }
+    }
+}
+
+impl From<&BookOrder> for v1::BookOrder {
+    fn from(order: &BookOrder) -> Self {
+        Self {
+            order_id: order.order_id.clone(),
+            price: order.price.to_string(),
+            quantity: order.quantity.to_string(),
+            timestamp_ns: timestamp_ns(&order.timestamp),
+        }
+    }
+}
+
+impl From<&BookSnapshot> for v1::BookSnapshot {
+    fn from(snapshot: &BookSnapshot) -> Self {
+        Self {
+            exchange: snapshot.exchange.clone(),
+            symbol: snapshot.symbol.clone(),
+            bids: snapshot.bids.iter().map(Into::into).collect(),
+            asks: snapshot.asks.iter().map(Into::into).collect(),
+            sequence: snapshot.sequence,
+            checksum: snapshot.checksum,
+        }
+    }
+}
+
+impl v1::OrderDelta {
+    pub fn new(action: v1::DeltaAction, order: &OrderEvent) -> Self {
+        Self {
+            exchange: order.exchange.clone(),
+            symbol: order.symbol.clone(),
+            action: action as i32,
+            side: v1::Side::from(order.side) as i32,
+            order_id: order.order_id.clone(),
+            price: order.price.to_string(),
+            quantity: order.quantity.to_string(),
+            timestamp_ns: timestamp_ns(&order.timestamp),
+            sequence: order.sequence,
+            checksum: order.checksum,
+        }
+    }
+}
+
+impl From<&BookLevel> for v1::PriceLevel {
+    fn from(level: &BookLevel) -> Self {
+        Self {
+            price: level.price.to_string(),
+            quantity: level.quantity.to_string(),
+        }
+    }
+}
+
+impl From<&LevelSnapshot> for v1::LevelSnapshot {
+    fn from(snapshot: &LevelSnapshot) -> Self {
+        Self {
+            exchange: snapshot.exchange.clone(),
+            symbol: snapshot.symbol.clone(),
+            bids: snapshot.bids.iter().map(Into::into).collect(),
+            asks: snapshot.asks.iter().map(Into::into).collect(),
+            sequence: snapshot.sequence,
+            checksum: snapshot.checksum,
+        }
+    }
+}
+
+impl From<&LevelEvent> for v1::LevelUpdate {
+    fn from(level: &LevelEvent) -> Self {
+        Self {
+            exchange: level.exchange.clone(),
+            symbol: level.symbol.clone(),
+            side: v1::Side::from(level.side) as i32,
+            price: level.price.to_string(),
+            quantity: level.quantity.to_string(),
+            timestamp_ns: timestamp_ns(&level.timestamp),
+            sequence: level.sequence,
+            checksum: level.checksum,
+        }
+    }
+}
+
+impl From<&Trade> for v1::Trade {
+    fn from(trade: &Trade) -> Self {
+        Self {
+            exchange: trade.exchange.clone(),
+            symbol: trade.symbol.clone(),
+            side: v1::Side::from(trade.side) as i32,
+            price: trade.price.to_string(),
+            quantity: trade.quantity.to_string(),
+            order_type: trade.order_type.clone(),
+            trade_id: trade.trade_id,
+            timestamp_ns: timestamp_ns(&trade.timestamp),
+        }
+    }
+}
+
+impl From<&Ticker> for v1::Ticker {
+    fn from(ticker: &Ticker) -> Self {
+        Self {
+            exchange: ticker.exchange.clone(),
+            symbol: ticker.symbol.clone(),
+            bid_price: ticker.bid.to_string(),
+            bid_quantity: ticker.bid_quantity.to_string(),
+            ask_price: ticker.ask.to_string(),
+            ask_quantity: ticker.ask_quantity.to_string(),
+            last: ticker.last.to_string(),
+            volume: ticker.volume.to_string(),
+            vwap: ticker.vwap.to_string(),
+            high: ticker.high.to_string(),
+            low: ticker.low.to_string(),
+            change: ticker.change.to_string(),
+            change_pct: ticker.change_pct.to_string(),
+            timestamp_ns: timestamp_ns(&ticker.timestamp),
+        }
+    }
+}
+
+impl From<&Candle> for v1::Candle {
+    fn from(candle: &Candle) -> Self {
+        Self {
+            exchange: candle.exchange.clone(),
+            symbol: candle.symbol.clone(),
+            interval_minutes: candle.interval,
+            interval_begin_ns: timestamp_ns(&candle.interval_begin),
+            open: candle.open.to_string(),
+            high: candle.high.to_string(),
+            low: candle.low.to_string(),
+            close: candle.close.to_string(),
+            volume: candle.volume.to_string(),
+            vwap: candle.vwap.to_string(),
+            trades: candle.trades,
+            timestamp_ns: timestamp_ns(&candle.timestamp),
+        }
+    }
+}
+
+impl From<&Instrument> for v1::Instrument {
+    fn from(instrument: &Instrument) -> Self {
+        Self {
+            exchange: instrument.exchange.clone(),
+            symbol: instrument.symbol.clone(),
+            base: instrument.base.clone(),
+            quote: instrument.quote.clone(),
+            status: instrument.status.to_string(),
+            price_precision: instrument.price_precision,
+            quantity_precision: instrument.quantity_precision,
+            tick_size: instrument.tick_size.to_string(),
+            lot_size: instrument.lot_size.to_string(),
+            min_order_quantity: instrument.min_order_quantity.to_string(),
+            min_order_cost: instrument.min_order_cost.as_ref().map(ToString::to_string),
+            marginable: instrument.marginable,
+            timestamp_ns: timestamp_ns(&instrument.timestamp),
+        }
+    }
+}
+
+impl From<&AccountOrder> for v1::AccountOrder {
+    fn from(order: &AccountOrder) -> Self {
+        Self {
+            exchange: order.exchange.clone(),
+            order_id: order.order_id.clone(),
+            client_order_id: order.client_order_id.clone(),
+            symbol: order.symbol.clone(),
+            side: v1::Side::from(order.side) as i32,
+            order_type: order.order_type.clone(),
+            status: order.status.to_string(),
+            quantity: order.quantity.to_string(),
+            limit_price: order.limit_price.as_ref().map(ToString::to_string),
+            filled_quantity: order.filled_quantity.to_string(),
+            average_price: order.average_price.as_ref().map(ToString::to_string),
+            timestamp_ns: timestamp_ns(&order.timestamp),
+        }
+    }
+}
+
+impl From<&Fill> for v1::Fill {
+    fn from(fill: &Fill) -> Self {
+        Self {
+            exchange: fill.exchange.clone(),
+            execution_id: fill.execution_id.clone(),
+            trade_id: fill.trade_id,
+            order_id: fill.order_id.clone(),
+            symbol: fill.symbol.clone(),
+            side: v1::Side::from(fill.side) as i32,
+            price: fill.price.to_string(),
+            quantity: fill.quantity.to_string(),
+            cost: fill.cost.to_string(),
+            fee: fill.fee.to_string(),
+            fee_asset: fill.fee_asset.clone(),
+            liquidity: fill
+                .liquidity
+                .map(|liquidity| liquidity.as_str().to_string()),
+            timestamp_ns: timestamp_ns(&fill.timestamp),
+        }
+    }
+}
+
+impl From<&LedgerEntry> for v1::LedgerEntry {
+    fn from(entry: &LedgerEntry) -> Self {
+        Self {
+            ledger_id: entry.ledger_id.clone(),
+            r#type: entry.entry_type.clone(),
+            amount: entry.amount.to_string(),
+            fee: entry.fee.to_string(),
+        }
+    }
+}
+
+impl From<&Balance> for v1::Balance {
+    fn from(balance: &Balance) -> Self {
+        Self {
+            exchange: balance.exchange.clone(),
+            asset: balance.asset.clone(),
+            balance: balance.balance.to_string(),
+            ledger: balance.ledger.as_ref().map(Into::into),
+            timestamp_ns: timestamp_ns(&balance.timestamp),
+        }
+    }
+}
+
+impl From<&StatusEvent> for v1::Status {
+    fn from(status: &StatusEvent) -> Self {
+        Self {
+            exchange: status.exchange.clone(),
+            system: status.system.clone(),
+            api_version: status.api_version.clone(),
+            connection_id: status.connection_id,
+            version: status.version.clone(),
+        }
+    }
+}
+
+impl From<&Level3Book> for v1::Bbo {
+    fn from(book: &Level3Book) -> Self {
+        let bid = book.best_bid();
+        let ask = book.best_ask();
+        Self {
+            exchange: book.exchange().to_string(),
+            symbol: book.symbol().to_string(),
+            bid_price: bid.as_ref().map(|level| level.price.to_string()),
+            bid_quantity: bid.as_ref().map(|level| level.quantity.to_string()),
+            ask_price: ask.as_ref().map(|level| level.price.to_string()),
+            ask_quantity: ask.as_ref().map(|level| level.quantity.to_string()),
+            timestamp_ns: book
+                .updated_at()
+                .map(|updated_at| timestamp_ns(&updated_at))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn timestamp_ns(timestamp: &DateTime<Utc>) -> i64 {
+    timestamp.timestamp_nanos_opt().unwrap_or_default()
+}
@@ -0,0 +1,279 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::{fs, sync::broadcast::Receiver, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{Config, Exchange, Websocket},
+    connector::ConnectorRegistry,
+    feeds::FeedManager,
+    metrics::Metrics,
+    sinks::broker_sink::BrokerSink,
+};
+
+/// What has to change for the running engine to match a new config. Removals
+/// are applied before additions, so a channel or exchange whose settings changed
+/// is removed and added again.
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    pub removed_exchanges: Vec<String>,
+    pub added_exchanges: Vec<Exchange>,
+    /// (exchange, channel)
+    pub removed_channels: Vec<(String, String)>,
+    pub added_channels: Vec<(String, Websocket)>,
+    /// (exchange, symbol)
+    pub removed_symbols: Vec<(String, String)>,
+    pub added_symbols: Vec<(String, String)>,
+    /// The new topics, if they changed.
+    pub topics: Option<Vec<String>>,
+}
+
+impl ConfigDiff {
+    pub fn between(running: &[Exchange], running_topics: &[String], target: &Config) -> Self {
+        let mut diff = Self::default();
+
+        for exchange in running.iter() {
+            let kept = target
+                .exchanges
+                .iter()
+                .any(|candidate| candidate.exchange == exchange.exchange);
+            if !kept {
+                diff.removed_exchanges.push(exchange.exchange.clone());
+            }
+        }
+
+        for exchange in target.exchanges.iter() {
+            let Some(current) = running
+                .iter()
+                .find(|candidate| candidate.exchange == exchange.exchange)
+            else {
+                diff.added_exchanges.push(exchange.clone());
+                continue;
+            };
+
+            // The token endpoint is only read when the connector is created.
+            if current.websocket_token != exchange.websocket_token {
+                diff.removed_exchanges.push(exchange.exchange.clone());
+                diff.added_exchanges.push(exchange.clone());
+                continue;
+            }
+
+            let name = &exchange.exchange;
+            for websocket in current.websockets() {
+                if !exchange.websockets().any(|target| target == websocket) {
+                    diff.removed_channels
+                        .push((name.clone(), websocket.channel.clone()));
+                }
+            }
+            for websocket in exchange.websockets() {
+                if !current.websockets().any(|running| running == websocket) {
+                    diff.added_channels.push((name.clone(), websocket.clone()));
+                }
+            }
+            for symbol in current.symbols.iter() {
+                if !exchange.symbols.contains(symbol) {
+                    diff.removed_symbols.push((name.clone(), symbol.clone()));
+                }
+            }
+            for symbol in exchange.symbols.iter() {
+                if !current.symbols.contains(symbol) {
+                    diff.added_symbols.push((name.clone(), symbol.clone()));
+                }
+            }
+        }
+
+        if running_topics != target.topics.as_slice() {
+            diff.topics = Some(target.topics.clone());
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed_exchanges.is_empty()
+            && self.added_exchanges.is_empty()
+            && self.removed_channels.is_empty()
+            && self.added_channels.is_empty()
+            && self.removed_symbols.is_empty()
+            && self.added_symbols.is_empty()
+            && self.topics.is_none()
+    }
+}
+
+/// Watches the config file and applies changes to `topics` and `exchanges`
+/// while the engine runs. The file is polled rather than watched for events,
+/// since a mounted configmap is updated by swapping a symlink.
+///
+/// A new config is applied only if it parses and validates; otherwise it is
+/// logged and the running config stays in place. The config is the source of
+/// truth: symbols and channels added through the admin API but missing from it
+/// are removed on the next reload.
+pub struct ConfigReloader {
+    path: String,
+    current: Config,
+    feeds: Arc<FeedManager>,
+    broker: Arc<BrokerSink>,
+    registry: ConnectorRegistry,
+    metrics: Arc<Metrics>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        path: &str,
+        current: Config,
+        feeds: Arc<FeedManager>,
+        broker: Arc<BrokerSink>,
+        registry: ConnectorRegistry,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            path: path.to_string(),
+            current,
+            feeds,
+            broker,
+            registry,
+            metrics,
+        }
+    }
+
+    /// Checks the file every `poll_interval` until `shutdown_rx` fires.
+    pub async fn watch(mut self, mut shutdown_rx: Receiver<()>) {
+        let interval = Duration::from_millis(
+            self.current
+                .data_engine_server_configuration
+                .reload_settings
+                .poll_interval_ms
+                .max(1),
+        );
+        let mut last_seen = fs::read_to_string(&self.path).await.ok();
+
+        loop {
+            tokio::select! {
+                _ = sleep(interval) => {}
+                _ = shutdown_rx.recv() => return,
+            }
+
+            let contents = match fs::read_to_string(&self.path).await {
+                Ok(contents) => contents,
+                Err(e) => {
+                    warn!("Failed to read config {}: {}", self.path, e);
+                    continue;
+                }
+            };
+            if last_seen.as_deref() == Some(contents.as_str()) {
+                continue;
+            }
+            last_seen = Some(contents.clone());
+
+            match self.reload(&contents).await {
+                Ok(()) => self.record("applied"),
+                Err(e) => {
+                    error!(
+                        "Rejected config {}, keeping the running one: {:#}",
+                        self.path, e
+                    );
+                    self.record("rejected");
+                }
+            }
+        }
+    }
+
+    async fn reload(&mut self, contents: &str) -> Result<()> {
+        let config = Config::parse(contents)?;
+        config.validate(&self.registry)?;
+
+        for (section, changed) in [
+            (
+                "data_engine_server_configuration",
+                differs(
+                    &self.current.data_engine_server_configuration,
+                    &config.data_engine_server_configuration,
+                ),
+            ),
+            (
+                "message_broker_server_configuration",
+                differs(
+                    &self.current.message_broker_server_configuration,
+                    &config.message_broker_server_configuration,
+                ),
+            ),
+        ] {
+            if changed {
+                warn!("Changes to {} take effect on restart", section);
+            }
+        }
+
+        let diff = ConfigDiff::between(&self.feeds.exchanges(), &self.current.topics, &config);
+        if diff.is_empty() {
+            info!("Config {} changed, nothing to apply", self.path);
+        } else {
+            info!("Applying config {}: {:?}", self.path, diff);
+            self.apply(diff).await;
+        }
+
+        self.current = config;
+        Ok(())
+    }
+
+    /// Applies each change on its own, so one failing change does not hold up
+    /// the rest.
+    async fn apply(&self, diff: ConfigDiff) {
+        for exchange in diff.removed_exchanges.iter() {
+            if let Err(e) = self.feeds.remove_exchange(exchange).await {
+                self.failed(format!("remove exchange {}: {}", exchange, e));
+            }
+        }
+        for (exchange, channel) in diff.removed_channels.iter() {
+            if let Err(e) = self.feeds.remove_channel(exchange, channel).await {
+                self.failed(format!(
+                    "remove {} channel from {}: {}",
+                    channel, exchange, e
+                ));
+            }
+        }
+        for (exchange, symbol) in diff.removed_symbols.iter() {
+            if let Err(e) = self.feeds.remove_symbol(exchange, symbol).await {
+                self.failed(format!("remove {} from {}: {}", symbol, exchange, e));
+            }
+        }
+        for exchange in diff.added_exchanges.iter() {
+            if let Err(e) = self.feeds.add_exchange(exchange).await {
+                self.failed(format!("add exchange {}: {}", exchange.exchange, e));
+            }
+        }
+        for (exchange, websocket) in diff.added_channels.iter() {
+            if let Err(e) = self.feeds.add_channel(exchange, websocket).await {
+                self.failed(format!(
+                    "add {} channel on {}: {}",
+                    websocket.channel, exchange, e
+                ));
+            }
+        }
+        for (exchange, symbol) in diff.added_symbols.iter() {
+            if let Err(e) = self.feeds.add_symbol(exchange, symbol).await {
+                self.failed(format!("add {} on {}: {}", symbol, exchange, e));
+            }
+        }
+        if let Some(topics) = &diff.topics {
+            self.broker.set_topics(topics);
+        }
+    }
+
+    fn failed(&self, change: String) {
+        error!("Failed to {}", change);
+        self.metrics
+            .increment("config_reload_failures_total", &[("path", &self.path)]);
+    }
+
+    fn record(&self, result: &str) {
+        self.metrics.increment(
+            "config_reloads_total",
+            &[("path", &self.path), ("result", result)],
+        );
+    }
+}
+
+fn differs<T: Serialize>(current: &T, target: &T) -> bool {
+    serde_json::to_value(current).ok() != serde_json::to_value(target).ok()
+}
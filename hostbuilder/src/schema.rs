@@ -0,0 +1,138 @@
+//! Tables owned by the engine itself, created by [`crate::migrations`]. The
+//! shared tables it also writes to live in `databaseschema`.
+
+diesel::table! {
+    data_engine_migrations (version) {
+        version -> Int4,
+        name -> Text,
+        applied_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    price_levels (exchange, symbol, side, price) {
+        exchange -> Text,
+        symbol -> Text,
+        side -> Text,
+        price -> Numeric,
+        quantity -> Numeric,
+        order_book_id -> Uuid,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    tickers (exchange, symbol, timestamp) {
+        exchange -> Text,
+        symbol -> Text,
+        timestamp -> Timestamptz,
+        bid -> Numeric,
+        bid_qty -> Numeric,
+        ask -> Numeric,
+        ask_qty -> Numeric,
+        last -> Numeric,
+        volume -> Numeric,
+        vwap -> Numeric,
+        high -> Numeric,
+        low -> Numeric,
+        change -> Numeric,
+        change_pct -> Numeric,
+        security_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    candles (exchange, symbol, interval, interval_begin) {
+        exchange -> Text,
+        symbol -> Text,
+        interval -> Int4,
+        interval_begin -> Timestamptz,
+        open -> Numeric,
+        high -> Numeric,
+        low -> Numeric,
+        close -> Numeric,
+        volume -> Numeric,
+        vwap -> Numeric,
+        trades -> Int8,
+        closed -> Bool,
+        security_id -> Uuid,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    instruments (exchange, symbol) {
+        exchange -> Text,
+        symbol -> Text,
+        base -> Text,
+        quote -> Text,
+        status -> Text,
+        price_precision -> Int4,
+        quantity_precision -> Int4,
+        tick_size -> Numeric,
+        lot_size -> Numeric,
+        min_order_quantity -> Numeric,
+        min_order_cost -> Nullable<Numeric>,
+        marginable -> Bool,
+        security_id -> Uuid,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    account_orders (exchange, order_id) {
+        exchange -> Text,
+        order_id -> Text,
+        client_order_id -> Nullable<Text>,
+        symbol -> Text,
+        side -> Text,
+        order_type -> Text,
+        status -> Text,
+        quantity -> Numeric,
+        limit_price -> Nullable<Numeric>,
+        filled_quantity -> Numeric,
+        average_price -> Nullable<Numeric>,
+        timestamp -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    account_fills (exchange, execution_id) {
+        exchange -> Text,
+        execution_id -> Text,
+        trade_id -> Nullable<Int8>,
+        order_id -> Text,
+        symbol -> Text,
+        side -> Text,
+        price -> Numeric,
+        quantity -> Numeric,
+        cost -> Numeric,
+        fee -> Numeric,
+        fee_asset -> Nullable<Text>,
+        liquidity -> Nullable<Text>,
+        timestamp -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    account_balances (exchange, asset) {
+        exchange -> Text,
+        asset -> Text,
+        balance -> Numeric,
+        timestamp -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    account_ledger (exchange, ledger_id) {
+        exchange -> Text,
+        ledger_id -> Text,
+        asset -> Text,
+        entry_type -> Text,
+        amount -> Numeric,
+        fee -> Numeric,
+        balance -> Numeric,
+        timestamp -> Timestamptz,
+    }
+}
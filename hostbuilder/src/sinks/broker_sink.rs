@@ -0,0 +1,441 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use prost::Message;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError, Receiver, Sender},
+        oneshot,
+    },
+    time::{timeout_at, Instant},
+};
+use tokio_retry::{
+    strategy::{jitter, ExponentialBackoff},
+    Retry,
+};
+use tracing::{error, warn};
+
+use crate::{
+    broker::{BrokerMessage, BrokerPublisher},
+    config::{OverflowPolicy, PublisherSettings},
+    events::{AccountEvent, MarketEvent},
+    metrics::Metrics,
+    proto::v1::{self, MarketDataEvent},
+    sinks::{BookContext, EventSink},
+};
+
+/// Topic carrying applied Level 3 book events.
+pub const ORDER_TOPIC: &str = "order-data";
+/// Topic carrying applied Level 2 book events.
+pub const BOOK_TOPIC: &str = "book-data";
+/// Topic carrying trades.
+pub const TRADE_TOPIC: &str = "trade-data";
+/// Topic carrying venue tickers.
+pub const TICKER_TOPIC: &str = "ticker-data";
+/// Topic carrying candles and their closes.
+pub const CANDLE_TOPIC: &str = "candle-data";
+/// Topic carrying instrument reference data.
+pub const INSTRUMENT_TOPIC: &str = "instrument-data";
+/// Topic carrying the fills and order states of our own account. The engine
+/// publishes it like any other topic; keeping it away from consumers outside
+/// our trading services is up to the broker's access control.
+pub const EXECUTION_TOPIC: &str = "execution-data";
+/// Topic carrying the balances of our own account. Like [`EXECUTION_TOPIC`],
+/// the broker's access control must restrict who can read it.
+pub const BALANCE_TOPIC: &str = "balance-data";
+/// Every topic the engine can publish to.
+pub const TOPICS: &[&str] = &[
+    ORDER_TOPIC,
+    BOOK_TOPIC,
+    TRADE_TOPIC,
+    TICKER_TOPIC,
+    CANDLE_TOPIC,
+    INSTRUMENT_TOPIC,
+    EXECUTION_TOPIC,
+    BALANCE_TOPIC,
+];
+
+/// Longest wait between attempts to publish a batch the broker rejected.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+enum Command {
+    Publish(BrokerMessage),
+    /// Publish everything queued before this command, then acknowledge.
+    Flush(oneshot::Sender<()>),
+}
+
+/// Publishes book events, trades, tickers, candles and instruments to the
+/// configured broker topics, encoded as `marketdata.v1.MarketDataEvent`
+/// protobuf messages, and our account's activity to the account topics as
+/// `marketdata.v1.AccountEvent`s.
+///
+/// Events are queued in a bounded buffer and sent by a single task that batches
+/// them per topic. A batch that fails to send is retried with backoff, holding
+/// back later batches so events keep their order per symbol; while the broker is
+/// down the buffer fills up and the overflow policy decides whether the feed
+/// waits or new events are dropped. A batch still failing after `max_retries`
+/// attempts is dropped and counted in `broker_batches_dropped_total`.
+///
+/// Every message carries a sequence number per topic and key, taken before it
+/// is queued, so a consumer sees a jump in it wherever messages for its key
+/// were dropped instead of silently missing them.
+pub struct BrokerSink {
+    sender: Sender<Command>,
+    topics: RwLock<Vec<String>>,
+    /// Last sequence number handed out per (topic, key).
+    sequences: Mutex<HashMap<(String, String), u64>>,
+    overflow: OverflowPolicy,
+    metrics: Arc<Metrics>,
+}
+
+impl BrokerSink {
+    pub fn new(
+        publisher: Arc<dyn BrokerPublisher>,
+        topics: &[String],
+        settings: &PublisherSettings,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(settings.buffer_capacity.max(1));
+        tokio::spawn(run_publisher(
+            publisher,
+            receiver,
+            settings.clone(),
+            metrics.clone(),
+        ));
+
+        Self {
+            sender,
+            topics: RwLock::new(topics.to_vec()),
+            sequences: Mutex::new(HashMap::new()),
+            overflow: settings.overflow,
+            metrics,
+        }
+    }
+
+    /// Replaces the topics published to.
+    pub fn set_topics(&self, topics: &[String]) {
+        if let Ok(mut configured) = self.topics.write() {
+            *configured = topics.to_vec();
+        }
+    }
+
+    /// The configured topic `event` belongs on, if any.
+    fn topic(&self, event: &MarketEvent) -> Option<&'static str> {
+        let topic = match event {
+            MarketEvent::OrderAdded(_)
+            | MarketEvent::OrderModified(_)
+            | MarketEvent::OrderDeleted(_)
+            | MarketEvent::BookSnapshot(_) => ORDER_TOPIC,
+            MarketEvent::LevelUpdated(_) | MarketEvent::LevelSnapshot(_) => BOOK_TOPIC,
+            MarketEvent::Trade(_) => TRADE_TOPIC,
+            MarketEvent::Ticker(_) => TICKER_TOPIC,
+            MarketEvent::Candle(_) | MarketEvent::CandleClosed(_) => CANDLE_TOPIC,
+            MarketEvent::Instrument(_) => INSTRUMENT_TOPIC,
+            MarketEvent::Account(_) | MarketEvent::Heartbeat(_) | MarketEvent::Status(_) => {
+                return None
+            }
+        };
+        self.configured(topic)
+    }
+
+    /// `topic`, if it is configured.
+    fn configured(&self, topic: &'static str) -> Option<&'static str> {
+        let topics = self.topics.read().ok()?;
+        topics
+            .iter()
+            .any(|configured| configured.as_str() == topic)
+            .then_some(topic)
+    }
+
+    /// Numbers the next message of `topic` and `key`.
+    fn next_sequence(&self, topic: &str, key: &str) -> u64 {
+        let Ok(mut sequences) = self.sequences.lock() else {
+            return 0;
+        };
+        let sequence = sequences
+            .entry((topic.to_string(), key.to_string()))
+            .or_default();
+        *sequence += 1;
+        *sequence
+    }
+
+    async fn enqueue(&self, message: BrokerMessage) -> Result<()> {
+        match self.overflow {
+            OverflowPolicy::Block => self
+                .sender
+                .send(Command::Publish(message))
+                .await
+                .map_err(|_| anyhow!("Broker publisher has stopped"))?,
+            OverflowPolicy::Drop => match self.sender.try_send(Command::Publish(message)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(Command::Publish(message))) => {
+                    self.metrics.increment(
+                        "broker_messages_dropped_total",
+                        &[("topic", &message.topic)],
+                    );
+                }
+                Err(TrySendError::Full(Command::Flush(_))) => {}
+                Err(TrySendError::Closed(_)) => {
+                    return Err(anyhow!("Broker publisher has stopped"));
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSink for BrokerSink {
+    fn name(&self) -> &str {
+        "broker"
+    }
+
+    async fn handle(&self, _context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+        for event in events {
+            let (Some(topic), Some(payload)) =
+                (self.topic(event), MarketDataEvent::from_event(event))
+            else {
+                continue;
+            };
+
+            let key = format!(
+                "{}:{}",
+                event.exchange(),
+                event.symbol().unwrap_or_default()
+            );
+            let message = BrokerMessage {
+                topic: topic.to_string(),
+                sequence: self.next_sequence(topic, &key),
+                key,
+                payload: payload.encode_to_vec(),
+            };
+            self.enqueue(message).await?;
+        }
+
+        self.metrics.set_gauge(
+            "broker_buffer_depth",
+            &[],
+            (self.sender.max_capacity() - self.sender.capacity()) as f64,
+        );
+        Ok(())
+    }
+
+    /// Fills and order states are keyed by symbol and balances by asset, so
+    /// each keeps its order.
+    async fn handle_account(&self, events: &[AccountEvent]) -> Result<()> {
+        for event in events {
+            let (topic, key) = match event {
+                AccountEvent::Order(order) => (EXECUTION_TOPIC, &order.symbol),
+                AccountEvent::Fill(fill) => (EXECUTION_TOPIC, &fill.symbol),
+                AccountEvent::Balance(balance) => (BALANCE_TOPIC, &balance.asset),
+            };
+            let Some(topic) = self.configured(topic) else {
+                continue;
+            };
+
+            let key = format!("{}:{}", event.exchange(), key);
+            let message = BrokerMessage {
+                topic: topic.to_string(),
+                sequence: self.next_sequence(topic, &key),
+                key,
+                payload: v1::AccountEvent::from(event).encode_to_vec(),
+            };
+            self.enqueue(message).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let (ack, done) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(ack))
+            .await
+            .map_err(|_| anyhow!("Broker publisher has stopped"))?;
+        done.await
+            .map_err(|_| anyhow!("Broker publisher stopped before flushing"))?;
+        Ok(())
+    }
+}
+
+/// Drains the buffer, sending a topic's batch once it is full or its oldest
+/// message has waited `linger_ms`.
+async fn run_publisher(
+    publisher: Arc<dyn BrokerPublisher>,
+    mut receiver: Receiver<Command>,
+    settings: PublisherSettings,
+    metrics: Arc<Metrics>,
+) {
+    let linger = Duration::from_millis(settings.linger_ms);
+    let batch_size = settings.batch_size.max(1);
+    let max_retries = settings.max_retries;
+    let mut batches: HashMap<String, Vec<BrokerMessage>> = HashMap::new();
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let received = match deadline {
+            Some(at) => match timeout_at(at, receiver.recv()).await {
+                Ok(received) => received,
+                Err(_) => {
+                    for (topic, batch) in batches.drain() {
+                        publish(publisher.as_ref(), &metrics, max_retries, &topic, batch).await;
+                    }
+                    deadline = None;
+                    continue;
+                }
+            },
+            None => receiver.recv().await,
+        };
+
+        let message = match received {
+            Some(Command::Publish(message)) => message,
+            Some(Command::Flush(ack)) => {
+                for (topic, batch) in batches.drain() {
+                    publish(publisher.as_ref(), &metrics, max_retries, &topic, batch).await;
+                }
+                deadline = None;
+                let _ = ack.send(());
+                continue;
+            }
+            None => {
+                for (topic, batch) in batches.drain() {
+                    publish(publisher.as_ref(), &metrics, max_retries, &topic, batch).await;
+                }
+                return;
+            }
+        };
+
+        let topic = message.topic.clone();
+        let batch = batches.entry(topic.clone()).or_default();
+        batch.push(message);
+        deadline.get_or_insert_with(|| Instant::now() + linger);
+
+        if batch.len() >= batch_size {
+            if let Some(batch) = batches.remove(&topic) {
+                publish(publisher.as_ref(), &metrics, max_retries, &topic, batch).await;
+            }
+            if batches.is_empty() {
+                deadline = None;
+            }
+        }
+    }
+}
+
+/// Sends `batch`, retrying with backoff up to `max_retries` times before the
+/// batch is dropped.
+async fn publish(
+    publisher: &dyn BrokerPublisher,
+    metrics: &Metrics,
+    max_retries: usize,
+    topic: &str,
+    batch: Vec<BrokerMessage>,
+) {
+    let labels = [("topic", topic)];
+    let strategy = ExponentialBackoff::from_millis(2)
+        .factor(50)
+        .max_delay(MAX_RETRY_DELAY)
+        .map(jitter)
+        .take(max_retries);
+
+    let result = Retry::start(strategy, || async {
+        let result = publisher.publish(topic, &batch).await;
+        if let Err(e) = &result {
+            warn!(
+                "Failed to publish {} messages to {}: {}",
+                batch.len(),
+                topic,
+                e
+            );
+            metrics.increment("broker_publish_failures_total", &labels);
+        }
+        result
+    })
+    .await;
+
+    match result {
+        Ok(()) => {
+            metrics.increment("broker_batches_published_total", &labels);
+            metrics.add(
+                "broker_messages_published_total",
+                &labels,
+                batch.len() as u64,
+            );
+        }
+        Err(e) => {
+            error!(
+                "Dropping {} messages bound for {}: {}",
+                batch.len(),
+                topic,
+                e
+            );
+            metrics.increment("broker_batches_dropped_total", &labels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Balance;
+    use chrono::Utc;
+
+    /// Rejects its first batch and records the messages of every later one.
+    #[derive(Default)]
+    struct FlakyPublisher {
+        attempts: Mutex<usize>,
+        published: Mutex<Vec<BrokerMessage>>,
+    }
+
+    #[async_trait]
+    impl BrokerPublisher for FlakyPublisher {
+        async fn publish(&self, _topic: &str, messages: &[BrokerMessage]) -> Result<()> {
+            let mut attempts = self.attempts.lock().unwrap();
+            *attempts += 1;
+            if *attempts == 1 {
+                return Err(anyhow!("broker unavailable"));
+            }
+            self.published.lock().unwrap().extend_from_slice(messages);
+            Ok(())
+        }
+    }
+
+    fn balance(asset: &str) -> AccountEvent {
+        AccountEvent::Balance(Box::new(Balance {
+            exchange: "kraken".to_string(),
+            asset: asset.to_string(),
+            balance: "1".parse().unwrap(),
+            ledger: None,
+            timestamp: Utc::now(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn a_dropped_batch_leaves_a_gap_in_its_keys_sequence() {
+        let publisher = Arc::new(FlakyPublisher::default());
+        let sink = BrokerSink::new(
+            publisher.clone(),
+            &[BALANCE_TOPIC.to_string()],
+            &PublisherSettings {
+                batch_size: 1,
+                max_retries: 0,
+                ..PublisherSettings::default()
+            },
+            Arc::new(Metrics::new()),
+        );
+
+        for asset in ["USD", "USD", "BTC"] {
+            sink.handle_account(&[balance(asset)]).await.unwrap();
+        }
+        sink.flush().await.unwrap();
+
+        let published = publisher.published.lock().unwrap();
+        let published: Vec<(&str, u64)> = published
+            .iter()
+            .map(|message| (message.key.as_str(), message.sequence))
+            .collect();
+        assert_eq!(published, [("kraken:USD", 2), ("kraken:BTC", 1)]);
+    }
+}
@@ -0,0 +1,44 @@
+pub mod broker_sink;
+pub mod postgres_sink;
+pub mod postgres_writer;
+pub mod redis_keys;
+pub mod redis_sink;
+pub mod redis_stream_sink;
+pub mod trade_history;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use databaseschema::models::OrderBook;
+
+use crate::events::{AccountEvent, MarketEvent};
+
+/// State of the book the events were applied to.
+#[derive(Clone)]
+pub struct BookContext {
+    pub order_book: OrderBook,
+    /// Open volume of the book after the events, or `None` when the batch did not
+    /// touch the book.
+    pub total_volume: Option<BigDecimal>,
+}
+
+/// A destination for normalized market data.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn handle(&self, context: &BookContext, events: &[MarketEvent]) -> Result<()>;
+
+    /// Handles activity on our own account. Sinks that serve market data to
+    /// anyone who asks leave this out, so account activity only reaches the
+    /// sinks that keep it.
+    async fn handle_account(&self, _events: &[AccountEvent]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Waits until everything handed to the sink so far has been written. Sinks
+    /// that write before `handle` returns have nothing to do.
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
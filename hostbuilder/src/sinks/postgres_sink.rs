@@ -0,0 +1,1198 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use databaseschema::{
+    models::{
+        NewModifiedBuyOrder, NewModifiedSellOrder, NewOpenBuyOrder, NewOpenSellOrder, NewTrade,
+        OrderBook,
+    },
+    schema::{
+        modified_buy_orders, modified_sell_orders, open_buy_orders, open_sell_orders, order_books,
+        trades,
+    },
+    CustomAsyncPgConnectionManager,
+};
+use deadpool::managed::Pool;
+use diesel::{
+    upsert::excluded, BoolExpressionMethods, ExpressionMethods, Insertable, QueryDsl, QueryResult,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    config::PostgresSettings,
+    events::{
+        AccountEvent, AccountOrder, Balance, BookLevel, Candle, Fill, Instrument, LedgerEntry,
+        MarketEvent, Side, Ticker,
+    },
+    metrics::Metrics,
+    schema::{
+        account_balances, account_fills, account_ledger, account_orders, candles, instruments,
+        price_levels, tickers,
+    },
+    sinks::{
+        postgres_writer::{Batch, BatchWriter},
+        redis_keys::price_member,
+        BookContext, EventSink,
+    },
+};
+
+/// Rows per `INSERT`, keeping statements well under Postgres' bind parameter limit.
+const INSERT_CHUNK: usize = 1000;
+
+/// Persists orders, price levels, trades, tickers, candles, instruments, order book
+/// volume and our account's activity, each table through its own [`BatchWriter`]
+/// so a slow database never holds up the feed.
+pub struct PostgresSink {
+    open_buy_orders: BatchWriter<OrderBatch<NewOpenBuyOrder>>,
+    open_sell_orders: BatchWriter<OrderBatch<NewOpenSellOrder>>,
+    modified_buy_orders: BatchWriter<OrderBatch<NewModifiedBuyOrder>>,
+    modified_sell_orders: BatchWriter<OrderBatch<NewModifiedSellOrder>>,
+    levels: BatchWriter<LevelBatch>,
+    trades: BatchWriter<TradeBatch>,
+    tickers: BatchWriter<TickerBatch>,
+    candles: BatchWriter<CandleBatch>,
+    instruments: BatchWriter<InstrumentBatch>,
+    account_orders: BatchWriter<AccountOrderBatch>,
+    account_fills: BatchWriter<FillBatch>,
+    account_balances: BatchWriter<BalanceBatch>,
+    account_ledger: BatchWriter<LedgerBatch>,
+    volumes: BatchWriter<VolumeBatch>,
+}
+
+impl PostgresSink {
+    pub fn new(
+        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+        settings: &PostgresSettings,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            open_buy_orders: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            open_sell_orders: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            modified_buy_orders: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            modified_sell_orders: BatchWriter::new(
+                postgres_pool.clone(),
+                settings,
+                metrics.clone(),
+            ),
+            levels: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            trades: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            tickers: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            candles: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            instruments: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            account_orders: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            account_fills: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            account_balances: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            account_ledger: BatchWriter::new(postgres_pool.clone(), settings, metrics.clone()),
+            volumes: BatchWriter::new(postgres_pool, settings, metrics),
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn handle(&self, context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+        let order_book = &context.order_book;
+
+        for event in events {
+            match event {
+                MarketEvent::BookSnapshot(snapshot) => {
+                    for bid in snapshot.bids.iter() {
+                        self.open_buy_orders
+                            .write(OrderWrite::Upsert(NewOpenBuyOrder::new(
+                                &order_book.symbol,
+                                &order_book.exchange,
+                                order_book.security_id,
+                                order_book.exchange_id,
+                                order_book.buy_order_book_id,
+                                &bid.order_id,
+                                &bid.price,
+                                &bid.quantity,
+                            )))
+                            .await?;
+                    }
+                    for ask in snapshot.asks.iter() {
+                        self.open_sell_orders
+                            .write(OrderWrite::Upsert(NewOpenSellOrder::new(
+                                &order_book.symbol,
+                                &order_book.exchange,
+                                order_book.security_id,
+                                order_book.exchange_id,
+                                order_book.sell_order_book_id,
+                                &ask.order_id,
+                                &ask.price,
+                                &ask.quantity,
+                            )))
+                            .await?;
+                    }
+                }
+                MarketEvent::OrderAdded(order) => match order.side {
+                    Side::Buy => {
+                        self.open_buy_orders
+                            .write(OrderWrite::Upsert(NewOpenBuyOrder::new(
+                                &order_book.symbol,
+                                &order_book.exchange,
+                                order_book.security_id,
+                                order_book.exchange_id,
+                                order_book.buy_order_book_id,
+                                &order.order_id,
+                                &order.price,
+                                &order.quantity,
+                            )))
+                            .await?
+                    }
+                    Side::Sell => {
+                        self.open_sell_orders
+                            .write(OrderWrite::Upsert(NewOpenSellOrder::new(
+                                &order_book.symbol,
+                                &order_book.exchange,
+                                order_book.security_id,
+                                order_book.exchange_id,
+                                order_book.sell_order_book_id,
+                                &order.order_id,
+                                &order.price,
+                                &order.quantity,
+                            )))
+                            .await?
+                    }
+                },
+                // A modify replaces the order's open or previous modified row.
+                MarketEvent::OrderModified(order) => match order.side {
+                    Side::Buy => {
+                        self.open_buy_orders
+                            .write(OrderWrite::Delete(order.order_id.clone()))
+                            .await?;
+                        self.modified_buy_orders
+                            .write(OrderWrite::Upsert(NewModifiedBuyOrder::new(
+                                &order_book.symbol,
+                                &order_book.exchange,
+                                order_book.security_id,
+                                order_book.exchange_id,
+                                order_book.buy_order_book_id,
+                                &order.order_id,
+                                &order.price,
+                                &order.quantity,
+                            )))
+                            .await?;
+                    }
+                    Side::Sell => {
+                        self.open_sell_orders
+                            .write(OrderWrite::Delete(order.order_id.clone()))
+                            .await?;
+                        self.modified_sell_orders
+                            .write(OrderWrite::Upsert(NewModifiedSellOrder::new(
+                                &order_book.symbol,
+                                &order_book.exchange,
+                                order_book.security_id,
+                                order_book.exchange_id,
+                                order_book.sell_order_book_id,
+                                &order.order_id,
+                                &order.price,
+                                &order.quantity,
+                            )))
+                            .await?;
+                    }
+                },
+                MarketEvent::OrderDeleted(order) => match order.side {
+                    Side::Buy => {
+                        self.open_buy_orders
+                            .write(OrderWrite::Delete(order.order_id.clone()))
+                            .await?;
+                        self.modified_buy_orders
+                            .write(OrderWrite::Delete(order.order_id.clone()))
+                            .await?;
+                    }
+                    Side::Sell => {
+                        self.open_sell_orders
+                            .write(OrderWrite::Delete(order.order_id.clone()))
+                            .await?;
+                        self.modified_sell_orders
+                            .write(OrderWrite::Delete(order.order_id.clone()))
+                            .await?;
+                    }
+                },
+                // A snapshot also clears levels left behind by an earlier run.
+                MarketEvent::LevelSnapshot(snapshot) => {
+                    self.levels
+                        .write(LevelWrite::Clear(
+                            snapshot.exchange.clone(),
+                            snapshot.symbol.clone(),
+                        ))
+                        .await?;
+                    for (side, levels) in
+                        [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)]
+                    {
+                        for level in levels {
+                            self.levels
+                                .write(LevelWrite::Set(NewPriceLevel::new(order_book, side, level)))
+                                .await?;
+                        }
+                    }
+                }
+                MarketEvent::LevelUpdated(level) => {
+                    self.levels
+                        .write(LevelWrite::Set(NewPriceLevel::new(
+                            order_book,
+                            level.side,
+                            &BookLevel {
+                                price: level.price.clone(),
+                                quantity: level.quantity.clone(),
+                            },
+                        )))
+                        .await?;
+                }
+                MarketEvent::Trade(trade) => {
+                    self.trades
+                        .write(NewTrade::new(
+                            &trade.symbol,
+                            &order_book.exchange,
+                            order_book.security_id,
+                            order_book.exchange_id,
+                            trade.side.as_str(),
+                            &trade.price,
+                            &trade.quantity,
+                        ))
+                        .await?
+                }
+                MarketEvent::Ticker(ticker) => {
+                    self.tickers
+                        .write(NewTicker::new(order_book, ticker))
+                        .await?
+                }
+                MarketEvent::Candle(candle) => {
+                    self.candles
+                        .write(NewCandle::new(order_book, candle, false))
+                        .await?
+                }
+                MarketEvent::CandleClosed(candle) => {
+                    self.candles
+                        .write(NewCandle::new(order_book, candle, true))
+                        .await?
+                }
+                MarketEvent::Instrument(instrument) => {
+                    self.instruments
+                        .write(NewInstrument::new(order_book, instrument))
+                        .await?
+                }
+                MarketEvent::Account(_) | MarketEvent::Heartbeat(_) | MarketEvent::Status(_) => {}
+            }
+        }
+
+        if let Some(total_volume) = &context.total_volume {
+            self.volumes
+                .write((order_book.order_book_id, total_volume.clone()))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_account(&self, events: &[AccountEvent]) -> Result<()> {
+        for event in events {
+            match event {
+                AccountEvent::Order(order) => {
+                    self.account_orders
+                        .write(NewAccountOrder::new(order))
+                        .await?
+                }
+                AccountEvent::Fill(fill) => self.account_fills.write(NewFill::new(fill)).await?,
+                AccountEvent::Balance(balance) => {
+                    self.account_balances
+                        .write(NewBalance::new(balance))
+                        .await?;
+                    if let Some(entry) = &balance.ledger {
+                        self.account_ledger
+                            .write(NewLedgerEntry::new(balance, entry))
+                            .await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let (
+            open_buy,
+            open_sell,
+            modified_buy,
+            modified_sell,
+            levels,
+            trades,
+            tickers,
+            candles,
+            instruments,
+            account_orders,
+            account_fills,
+            account_balances,
+            account_ledger,
+            volumes,
+        ) = tokio::join!(
+            self.open_buy_orders.flush(),
+            self.open_sell_orders.flush(),
+            self.modified_buy_orders.flush(),
+            self.modified_sell_orders.flush(),
+            self.levels.flush(),
+            self.trades.flush(),
+            self.tickers.flush(),
+            self.candles.flush(),
+            self.instruments.flush(),
+            self.account_orders.flush(),
+            self.account_fills.flush(),
+            self.account_balances.flush(),
+            self.account_ledger.flush(),
+            self.volumes.flush(),
+        );
+        open_buy?;
+        open_sell?;
+        modified_buy?;
+        modified_sell?;
+        levels?;
+        trades?;
+        tickers?;
+        candles?;
+        instruments?;
+        account_orders?;
+        account_fills?;
+        account_balances?;
+        account_ledger?;
+        volumes?;
+        Ok(())
+    }
+}
+
+/// A row of one of the order tables, keyed by the venue's order id.
+#[async_trait]
+pub trait OrderRow: Clone + Send + Sync + 'static {
+    const TABLE: &'static str;
+
+    fn unique_id(&self) -> &str;
+
+    /// Deletes the rows of `ids`, then inserts `rows`.
+    async fn replace(
+        connection: &mut AsyncPgConnection,
+        ids: Vec<String>,
+        rows: Vec<Self>,
+    ) -> QueryResult<()>;
+}
+
+macro_rules! order_row {
+    ($row:ty, $table:ident) => {
+        #[async_trait]
+        impl OrderRow for $row {
+            const TABLE: &'static str = stringify!($table);
+
+            fn unique_id(&self) -> &str {
+                &self.unique_id
+            }
+
+            async fn replace(
+                connection: &mut AsyncPgConnection,
+                ids: Vec<String>,
+                rows: Vec<Self>,
+            ) -> QueryResult<()> {
+                diesel::delete($table::table.filter($table::unique_id.eq_any(ids)))
+                    .execute(connection)
+                    .await?;
+                for chunk in rows.chunks(INSERT_CHUNK) {
+                    diesel::insert_into($table::table)
+                        .values(chunk)
+                        .execute(connection)
+                        .await?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+order_row!(NewOpenBuyOrder, open_buy_orders);
+order_row!(NewOpenSellOrder, open_sell_orders);
+order_row!(NewModifiedBuyOrder, modified_buy_orders);
+order_row!(NewModifiedSellOrder, modified_sell_orders);
+
+pub enum OrderWrite<R> {
+    Upsert(R),
+    Delete(String),
+}
+
+/// Final state of every order written in the window: the row it ends up as, or
+/// `None` once deleted. Flushing deletes the existing row of each order and
+/// inserts the surviving ones, so replaying the same writes is harmless.
+pub struct OrderBatch<R> {
+    rows: HashMap<String, Option<R>>,
+}
+
+impl<R> Default for OrderBatch<R> {
+    fn default() -> Self {
+        Self {
+            rows: HashMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl<R: OrderRow> Batch for OrderBatch<R> {
+    type Write = OrderWrite<R>;
+
+    const TABLE: &'static str = R::TABLE;
+
+    fn push(&mut self, write: OrderWrite<R>) {
+        match write {
+            OrderWrite::Upsert(row) => self.rows.insert(row.unique_id().to_string(), Some(row)),
+            OrderWrite::Delete(unique_id) => self.rows.insert(unique_id, None),
+        };
+    }
+
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        let ids = self.rows.keys().cloned().collect();
+        let rows = self.rows.values().flatten().cloned().collect();
+        R::replace(connection, ids, rows).await
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = price_levels)]
+pub struct NewPriceLevel {
+    pub exchange: String,
+    pub symbol: String,
+    pub side: String,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub order_book_id: Uuid,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NewPriceLevel {
+    pub fn new(order_book: &OrderBook, side: Side, level: &BookLevel) -> Self {
+        Self {
+            exchange: order_book.exchange.clone(),
+            symbol: order_book.symbol.clone(),
+            side: side.as_str().to_string(),
+            price: level.price.clone(),
+            quantity: level.quantity.clone(),
+            order_book_id: match side {
+                Side::Buy => order_book.buy_order_book_id,
+                Side::Sell => order_book.sell_order_book_id,
+            },
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+pub enum LevelWrite {
+    /// Removes every level of (exchange, symbol).
+    Clear(String, String),
+    /// Sets a level's quantity; zero removes it.
+    Set(NewPriceLevel),
+}
+
+/// Final state of every price level written in the window, after the books
+/// cleared in it. Flushing clears those books first, so a level set after a
+/// clear survives it.
+#[derive(Default)]
+pub struct LevelBatch {
+    cleared: Vec<(String, String)>,
+    /// Keyed by exchange, symbol, side and normalized price.
+    levels: HashMap<(String, String, String, String), NewPriceLevel>,
+}
+
+#[async_trait]
+impl Batch for LevelBatch {
+    type Write = LevelWrite;
+
+    const TABLE: &'static str = "price_levels";
+
+    fn push(&mut self, write: LevelWrite) {
+        match write {
+            LevelWrite::Clear(exchange, symbol) => {
+                self.levels
+                    .retain(|(e, s, _, _), _| *e != exchange || *s != symbol);
+                self.cleared.push((exchange, symbol));
+            }
+            LevelWrite::Set(level) => {
+                let key = (
+                    level.exchange.clone(),
+                    level.symbol.clone(),
+                    level.side.clone(),
+                    price_member(&level.price),
+                );
+                self.levels.insert(key, level);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cleared.len() + self.levels.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        for (exchange, symbol) in self.cleared.iter() {
+            diesel::delete(
+                price_levels::table
+                    .filter(price_levels::exchange.eq(exchange))
+                    .filter(price_levels::symbol.eq(symbol)),
+            )
+            .execute(connection)
+            .await?;
+        }
+
+        let (removed, set): (Vec<&NewPriceLevel>, Vec<&NewPriceLevel>) = self
+            .levels
+            .values()
+            .partition(|level| level.quantity.is_zero());
+        for level in removed {
+            diesel::delete(
+                price_levels::table
+                    .filter(price_levels::exchange.eq(&level.exchange))
+                    .filter(price_levels::symbol.eq(&level.symbol))
+                    .filter(price_levels::side.eq(&level.side))
+                    .filter(price_levels::price.eq(&level.price)),
+            )
+            .execute(connection)
+            .await?;
+        }
+
+        let set: Vec<NewPriceLevel> = set.into_iter().cloned().collect();
+        for chunk in set.chunks(INSERT_CHUNK) {
+            diesel::insert_into(price_levels::table)
+                .values(chunk)
+                .on_conflict((
+                    price_levels::exchange,
+                    price_levels::symbol,
+                    price_levels::side,
+                    price_levels::price,
+                ))
+                .do_update()
+                .set((
+                    price_levels::quantity.eq(excluded(price_levels::quantity)),
+                    price_levels::order_book_id.eq(excluded(price_levels::order_book_id)),
+                    price_levels::updated_at.eq(excluded(price_levels::updated_at)),
+                ))
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct TradeBatch {
+    trades: Vec<NewTrade>,
+}
+
+#[async_trait]
+impl Batch for TradeBatch {
+    type Write = NewTrade;
+
+    const TABLE: &'static str = "trades";
+
+    fn push(&mut self, trade: NewTrade) {
+        self.trades.push(trade);
+    }
+
+    fn len(&self) -> usize {
+        self.trades.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        for chunk in self.trades.chunks(INSERT_CHUNK) {
+            diesel::insert_into(trades::table)
+                .values(chunk)
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = tickers)]
+pub struct NewTicker {
+    pub exchange: String,
+    pub symbol: String,
+    pub timestamp: DateTime<Utc>,
+    pub bid: BigDecimal,
+    pub bid_qty: BigDecimal,
+    pub ask: BigDecimal,
+    pub ask_qty: BigDecimal,
+    pub last: BigDecimal,
+    pub volume: BigDecimal,
+    pub vwap: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub change: BigDecimal,
+    pub change_pct: BigDecimal,
+    pub security_id: Uuid,
+}
+
+impl NewTicker {
+    pub fn new(order_book: &OrderBook, ticker: &Ticker) -> Self {
+        Self {
+            exchange: order_book.exchange.clone(),
+            symbol: order_book.symbol.clone(),
+            timestamp: ticker.timestamp,
+            bid: ticker.bid.clone(),
+            bid_qty: ticker.bid_quantity.clone(),
+            ask: ticker.ask.clone(),
+            ask_qty: ticker.ask_quantity.clone(),
+            last: ticker.last.clone(),
+            volume: ticker.volume.clone(),
+            vwap: ticker.vwap.clone(),
+            high: ticker.high.clone(),
+            low: ticker.low.clone(),
+            change: ticker.change.clone(),
+            change_pct: ticker.change_pct.clone(),
+            security_id: order_book.security_id,
+        }
+    }
+}
+
+/// Tickers received in the window, appended as a time series. A ticker already
+/// stored for its timestamp is kept, so replaying the same writes is harmless.
+#[derive(Default)]
+pub struct TickerBatch {
+    tickers: Vec<NewTicker>,
+}
+
+#[async_trait]
+impl Batch for TickerBatch {
+    type Write = NewTicker;
+
+    const TABLE: &'static str = "tickers";
+
+    fn push(&mut self, ticker: NewTicker) {
+        self.tickers.push(ticker);
+    }
+
+    fn len(&self) -> usize {
+        self.tickers.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        for chunk in self.tickers.chunks(INSERT_CHUNK) {
+            diesel::insert_into(tickers::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = candles)]
+pub struct NewCandle {
+    pub exchange: String,
+    pub symbol: String,
+    pub interval: i32,
+    pub interval_begin: DateTime<Utc>,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    pub volume: BigDecimal,
+    pub vwap: BigDecimal,
+    pub trades: i64,
+    pub closed: bool,
+    pub security_id: Uuid,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NewCandle {
+    pub fn new(order_book: &OrderBook, candle: &Candle, closed: bool) -> Self {
+        Self {
+            exchange: order_book.exchange.clone(),
+            symbol: order_book.symbol.clone(),
+            interval: candle.interval as i32,
+            interval_begin: candle.interval_begin,
+            open: candle.open.clone(),
+            high: candle.high.clone(),
+            low: candle.low.clone(),
+            close: candle.close.clone(),
+            volume: candle.volume.clone(),
+            vwap: candle.vwap.clone(),
+            trades: candle.trades as i64,
+            closed,
+            security_id: order_book.security_id,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Latest state of every candle written in the window, upserted so each
+/// interval keeps a single row. A candle stays closed once it was written
+/// closed.
+#[derive(Default)]
+pub struct CandleBatch {
+    /// Keyed by exchange, symbol, interval and interval begin.
+    candles: HashMap<(String, String, i32, DateTime<Utc>), NewCandle>,
+}
+
+#[async_trait]
+impl Batch for CandleBatch {
+    type Write = NewCandle;
+
+    const TABLE: &'static str = "candles";
+
+    fn push(&mut self, mut candle: NewCandle) {
+        let key = (
+            candle.exchange.clone(),
+            candle.symbol.clone(),
+            candle.interval,
+            candle.interval_begin,
+        );
+        if let Some(previous) = self.candles.get(&key) {
+            candle.closed |= previous.closed;
+        }
+        self.candles.insert(key, candle);
+    }
+
+    fn len(&self) -> usize {
+        self.candles.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        let rows: Vec<NewCandle> = self.candles.values().cloned().collect();
+        for chunk in rows.chunks(INSERT_CHUNK) {
+            diesel::insert_into(candles::table)
+                .values(chunk)
+                .on_conflict((
+                    candles::exchange,
+                    candles::symbol,
+                    candles::interval,
+                    candles::interval_begin,
+                ))
+                .do_update()
+                .set((
+                    candles::open.eq(excluded(candles::open)),
+                    candles::high.eq(excluded(candles::high)),
+                    candles::low.eq(excluded(candles::low)),
+                    candles::close.eq(excluded(candles::close)),
+                    candles::volume.eq(excluded(candles::volume)),
+                    candles::vwap.eq(excluded(candles::vwap)),
+                    candles::trades.eq(excluded(candles::trades)),
+                    candles::closed.eq(candles::closed.or(excluded(candles::closed))),
+                    candles::updated_at.eq(excluded(candles::updated_at)),
+                ))
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = instruments)]
+pub struct NewInstrument {
+    pub exchange: String,
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+    pub status: String,
+    pub price_precision: i32,
+    pub quantity_precision: i32,
+    pub tick_size: BigDecimal,
+    pub lot_size: BigDecimal,
+    pub min_order_quantity: BigDecimal,
+    pub min_order_cost: Option<BigDecimal>,
+    pub marginable: bool,
+    pub security_id: Uuid,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NewInstrument {
+    pub fn new(order_book: &OrderBook, instrument: &Instrument) -> Self {
+        Self {
+            exchange: order_book.exchange.clone(),
+            symbol: order_book.symbol.clone(),
+            base: instrument.base.clone(),
+            quote: instrument.quote.clone(),
+            status: instrument.status.to_string(),
+            price_precision: instrument.price_precision as i32,
+            quantity_precision: instrument.quantity_precision as i32,
+            tick_size: instrument.tick_size.clone(),
+            lot_size: instrument.lot_size.clone(),
+            min_order_quantity: instrument.min_order_quantity.clone(),
+            min_order_cost: instrument.min_order_cost.clone(),
+            marginable: instrument.marginable,
+            security_id: order_book.security_id,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Latest trading rules of every symbol written in the window, upserted so
+/// each symbol keeps a single row.
+#[derive(Default)]
+pub struct InstrumentBatch {
+    /// Keyed by exchange and symbol.
+    instruments: HashMap<(String, String), NewInstrument>,
+}
+
+#[async_trait]
+impl Batch for InstrumentBatch {
+    type Write = NewInstrument;
+
+    const TABLE: &'static str = "instruments";
+
+    fn push(&mut self, instrument: NewInstrument) {
+        let key = (instrument.exchange.clone(), instrument.symbol.clone());
+        self.instruments.insert(key, instrument);
+    }
+
+    fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        let rows: Vec<NewInstrument> = self.instruments.values().cloned().collect();
+        for chunk in rows.chunks(INSERT_CHUNK) {
+            diesel::insert_into(instruments::table)
+                .values(chunk)
+                .on_conflict((instruments::exchange, instruments::symbol))
+                .do_update()
+                .set((
+                    instruments::base.eq(excluded(instruments::base)),
+                    instruments::quote.eq(excluded(instruments::quote)),
+                    instruments::status.eq(excluded(instruments::status)),
+                    instruments::price_precision.eq(excluded(instruments::price_precision)),
+                    instruments::quantity_precision.eq(excluded(instruments::quantity_precision)),
+                    instruments::tick_size.eq(excluded(instruments::tick_size)),
+                    instruments::lot_size.eq(excluded(instruments::lot_size)),
+                    instruments::min_order_quantity.eq(excluded(instruments::min_order_quantity)),
+                    instruments::min_order_cost.eq(excluded(instruments::min_order_cost)),
+                    instruments::marginable.eq(excluded(instruments::marginable)),
+                    instruments::security_id.eq(excluded(instruments::security_id)),
+                    instruments::updated_at.eq(excluded(instruments::updated_at)),
+                ))
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = account_orders)]
+pub struct NewAccountOrder {
+    pub exchange: String,
+    pub order_id: String,
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    pub status: String,
+    pub quantity: BigDecimal,
+    pub limit_price: Option<BigDecimal>,
+    pub filled_quantity: BigDecimal,
+    pub average_price: Option<BigDecimal>,
+    pub timestamp: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NewAccountOrder {
+    pub fn new(order: &AccountOrder) -> Self {
+        Self {
+            exchange: order.exchange.clone(),
+            order_id: order.order_id.clone(),
+            client_order_id: order.client_order_id.clone(),
+            symbol: order.symbol.clone(),
+            side: order.side.as_str().to_string(),
+            order_type: order.order_type.clone(),
+            status: order.status.to_string(),
+            quantity: order.quantity.clone(),
+            limit_price: order.limit_price.clone(),
+            filled_quantity: order.filled_quantity.clone(),
+            average_price: order.average_price.clone(),
+            timestamp: order.timestamp,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Latest state of every order written in the window, upserted so each order
+/// keeps a single row.
+#[derive(Default)]
+pub struct AccountOrderBatch {
+    /// Keyed by exchange and order id.
+    orders: HashMap<(String, String), NewAccountOrder>,
+}
+
+#[async_trait]
+impl Batch for AccountOrderBatch {
+    type Write = NewAccountOrder;
+
+    const TABLE: &'static str = "account_orders";
+
+    fn push(&mut self, order: NewAccountOrder) {
+        let key = (order.exchange.clone(), order.order_id.clone());
+        self.orders.insert(key, order);
+    }
+
+    fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        let rows: Vec<NewAccountOrder> = self.orders.values().cloned().collect();
+        for chunk in rows.chunks(INSERT_CHUNK) {
+            diesel::insert_into(account_orders::table)
+                .values(chunk)
+                .on_conflict((account_orders::exchange, account_orders::order_id))
+                .do_update()
+                .set((
+                    account_orders::client_order_id.eq(excluded(account_orders::client_order_id)),
+                    account_orders::status.eq(excluded(account_orders::status)),
+                    account_orders::quantity.eq(excluded(account_orders::quantity)),
+                    account_orders::limit_price.eq(excluded(account_orders::limit_price)),
+                    account_orders::filled_quantity.eq(excluded(account_orders::filled_quantity)),
+                    account_orders::average_price.eq(excluded(account_orders::average_price)),
+                    account_orders::timestamp.eq(excluded(account_orders::timestamp)),
+                    account_orders::updated_at.eq(excluded(account_orders::updated_at)),
+                ))
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = account_fills)]
+pub struct NewFill {
+    pub exchange: String,
+    pub execution_id: String,
+    pub trade_id: Option<i64>,
+    pub order_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub cost: BigDecimal,
+    pub fee: BigDecimal,
+    pub fee_asset: Option<String>,
+    pub liquidity: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NewFill {
+    pub fn new(fill: &Fill) -> Self {
+        Self {
+            exchange: fill.exchange.clone(),
+            execution_id: fill.execution_id.clone(),
+            trade_id: fill.trade_id.map(|trade_id| trade_id as i64),
+            order_id: fill.order_id.clone(),
+            symbol: fill.symbol.clone(),
+            side: fill.side.as_str().to_string(),
+            price: fill.price.clone(),
+            quantity: fill.quantity.clone(),
+            cost: fill.cost.clone(),
+            fee: fill.fee.clone(),
+            fee_asset: fill.fee_asset.clone(),
+            liquidity: fill
+                .liquidity
+                .map(|liquidity| liquidity.as_str().to_string()),
+            timestamp: fill.timestamp,
+        }
+    }
+}
+
+/// Fills received in the window. A fill already stored is kept, so those the
+/// venue repeats after a reconnect are only recorded once.
+#[derive(Default)]
+pub struct FillBatch {
+    fills: Vec<NewFill>,
+}
+
+#[async_trait]
+impl Batch for FillBatch {
+    type Write = NewFill;
+
+    const TABLE: &'static str = "account_fills";
+
+    fn push(&mut self, fill: NewFill) {
+        self.fills.push(fill);
+    }
+
+    fn len(&self) -> usize {
+        self.fills.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        for chunk in self.fills.chunks(INSERT_CHUNK) {
+            diesel::insert_into(account_fills::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = account_balances)]
+pub struct NewBalance {
+    pub exchange: String,
+    pub asset: String,
+    pub balance: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NewBalance {
+    pub fn new(balance: &Balance) -> Self {
+        Self {
+            exchange: balance.exchange.clone(),
+            asset: balance.asset.clone(),
+            balance: balance.balance.clone(),
+            timestamp: balance.timestamp,
+        }
+    }
+}
+
+/// Latest balance of every asset written in the window.
+#[derive(Default)]
+pub struct BalanceBatch {
+    /// Keyed by exchange and asset.
+    balances: HashMap<(String, String), NewBalance>,
+}
+
+#[async_trait]
+impl Batch for BalanceBatch {
+    type Write = NewBalance;
+
+    const TABLE: &'static str = "account_balances";
+
+    fn push(&mut self, balance: NewBalance) {
+        let key = (balance.exchange.clone(), balance.asset.clone());
+        self.balances.insert(key, balance);
+    }
+
+    fn len(&self) -> usize {
+        self.balances.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        let rows: Vec<NewBalance> = self.balances.values().cloned().collect();
+        for chunk in rows.chunks(INSERT_CHUNK) {
+            diesel::insert_into(account_balances::table)
+                .values(chunk)
+                .on_conflict((account_balances::exchange, account_balances::asset))
+                .do_update()
+                .set((
+                    account_balances::balance.eq(excluded(account_balances::balance)),
+                    account_balances::timestamp.eq(excluded(account_balances::timestamp)),
+                ))
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = account_ledger)]
+pub struct NewLedgerEntry {
+    pub exchange: String,
+    pub ledger_id: String,
+    pub asset: String,
+    pub entry_type: String,
+    pub amount: BigDecimal,
+    pub fee: BigDecimal,
+    pub balance: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl NewLedgerEntry {
+    pub fn new(balance: &Balance, entry: &LedgerEntry) -> Self {
+        Self {
+            exchange: balance.exchange.clone(),
+            ledger_id: entry.ledger_id.clone(),
+            asset: balance.asset.clone(),
+            entry_type: entry.entry_type.clone(),
+            amount: entry.amount.clone(),
+            fee: entry.fee.clone(),
+            balance: balance.balance.clone(),
+            timestamp: balance.timestamp,
+        }
+    }
+}
+
+/// Ledger entries received in the window, appended once each.
+#[derive(Default)]
+pub struct LedgerBatch {
+    entries: Vec<NewLedgerEntry>,
+}
+
+#[async_trait]
+impl Batch for LedgerBatch {
+    type Write = NewLedgerEntry;
+
+    const TABLE: &'static str = "account_ledger";
+
+    fn push(&mut self, entry: NewLedgerEntry) {
+        self.entries.push(entry);
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        for chunk in self.entries.chunks(INSERT_CHUNK) {
+            diesel::insert_into(account_ledger::table)
+                .values(chunk)
+                .on_conflict_do_nothing()
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Latest open volume of every book written in the window.
+#[derive(Default)]
+pub struct VolumeBatch {
+    volumes: HashMap<Uuid, BigDecimal>,
+}
+
+#[async_trait]
+impl Batch for VolumeBatch {
+    type Write = (Uuid, BigDecimal);
+
+    const TABLE: &'static str = "order_books";
+
+    fn push(&mut self, (order_book_id, total_volume): (Uuid, BigDecimal)) {
+        self.volumes.insert(order_book_id, total_volume);
+    }
+
+    fn len(&self) -> usize {
+        self.volumes.len()
+    }
+
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()> {
+        let now = Utc::now().naive_utc();
+        for (order_book_id, total_volume) in self.volumes.iter() {
+            diesel::update(order_books::table.find(order_book_id))
+                .set((
+                    order_books::total_volume.eq(total_volume),
+                    order_books::updated_at.eq(now),
+                ))
+                .execute(connection)
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use databaseschema::CustomAsyncPgConnectionManager;
+use deadpool::managed::Pool;
+use diesel::{
+    result::{DatabaseErrorKind, Error as DieselError},
+    QueryResult,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        oneshot,
+    },
+    time::{timeout_at, Instant},
+};
+use tokio_retry::{
+    strategy::{jitter, ExponentialBackoff},
+    RetryIf,
+};
+use tracing::{error, warn};
+
+use crate::{config::PostgresSettings, metrics::Metrics};
+
+/// Longest wait between attempts at a batch that failed transiently.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A table's pending writes, coalesced until they are flushed.
+#[async_trait]
+pub trait Batch: Default + Send + Sync + 'static {
+    type Write: Send + 'static;
+
+    /// Table name used in logs and metric labels.
+    const TABLE: &'static str;
+
+    fn push(&mut self, write: Self::Write);
+
+    /// Rows the batch will write.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the batch. Runs inside a transaction, so either every row lands or
+    /// none does.
+    async fn execute(&self, connection: &mut AsyncPgConnection) -> QueryResult<()>;
+}
+
+enum Command<W> {
+    Write(W),
+    /// Write everything queued before this command, then acknowledge.
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Error)]
+enum WriteError {
+    #[error("no connection: {0}")]
+    Pool(String),
+    #[error(transparent)]
+    Query(#[from] DieselError),
+}
+
+/// Writes one table off the feed's path. Writes are queued in a bounded buffer and
+/// a single task coalesces them into batches, flushed once `batch_size` rows are
+/// pending or the oldest has waited `linger_ms`, each in one transaction. A batch
+/// that fails transiently is retried with backoff; one that still fails, or fails
+/// for any other reason, is logged, counted and dropped. While the database is
+/// behind, the buffer fills up and `write` waits for room.
+pub struct BatchWriter<B: Batch> {
+    sender: Sender<Command<B::Write>>,
+    batch: PhantomData<B>,
+}
+
+impl<B: Batch> BatchWriter<B> {
+    pub fn new(
+        postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+        settings: &PostgresSettings,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(settings.queue_capacity.max(1));
+        tokio::spawn(run::<B>(postgres_pool, receiver, settings.clone(), metrics));
+
+        Self {
+            sender,
+            batch: PhantomData,
+        }
+    }
+
+    pub async fn write(&self, write: B::Write) -> Result<()> {
+        self.sender
+            .send(Command::Write(write))
+            .await
+            .map_err(|_| anyhow!("Writer for {} has stopped", B::TABLE))
+    }
+
+    /// Waits until every write queued so far has been flushed.
+    pub async fn flush(&self) -> Result<()> {
+        let (ack, done) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(ack))
+            .await
+            .map_err(|_| anyhow!("Writer for {} has stopped", B::TABLE))?;
+        done.await
+            .map_err(|_| anyhow!("Writer for {} stopped before flushing", B::TABLE))
+    }
+}
+
+async fn run<B: Batch>(
+    postgres_pool: Arc<Pool<CustomAsyncPgConnectionManager>>,
+    mut receiver: Receiver<Command<B::Write>>,
+    settings: PostgresSettings,
+    metrics: Arc<Metrics>,
+) {
+    let labels = [("table", B::TABLE)];
+    let linger = Duration::from_millis(settings.linger_ms);
+    let batch_size = settings.batch_size.max(1);
+    let mut batch = B::default();
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let received = match deadline {
+            Some(at) => match timeout_at(at, receiver.recv()).await {
+                Ok(received) => received,
+                Err(_) => {
+                    let pending = std::mem::take(&mut batch);
+                    write_batch(&postgres_pool, &settings, &metrics, pending).await;
+                    deadline = None;
+                    continue;
+                }
+            },
+            None => receiver.recv().await,
+        };
+        metrics.set_gauge("postgres_queue_depth", &labels, receiver.len() as f64);
+
+        match received {
+            Some(Command::Write(write)) => {
+                batch.push(write);
+                deadline.get_or_insert_with(|| Instant::now() + linger);
+                if batch.len() >= batch_size {
+                    let pending = std::mem::take(&mut batch);
+                    write_batch(&postgres_pool, &settings, &metrics, pending).await;
+                    deadline = None;
+                }
+            }
+            Some(Command::Flush(ack)) => {
+                let pending = std::mem::take(&mut batch);
+                write_batch(&postgres_pool, &settings, &metrics, pending).await;
+                deadline = None;
+                let _ = ack.send(());
+            }
+            None => {
+                write_batch(&postgres_pool, &settings, &metrics, batch).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Writes `batch` in one transaction, retrying transient failures.
+async fn write_batch<B: Batch>(
+    postgres_pool: &Pool<CustomAsyncPgConnectionManager>,
+    settings: &PostgresSettings,
+    metrics: &Metrics,
+    batch: B,
+) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let labels = [("table", B::TABLE)];
+    let started = Instant::now();
+    let strategy = ExponentialBackoff::from_millis(2)
+        .factor(50)
+        .max_delay(MAX_RETRY_DELAY)
+        .map(jitter)
+        .take(settings.max_retries);
+
+    let result = RetryIf::start(
+        strategy,
+        || async {
+            let result = execute(postgres_pool, &batch).await;
+            if let Err(e) = &result {
+                warn!(
+                    "Failed to write {} rows to {}: {}",
+                    batch.len(),
+                    B::TABLE,
+                    e
+                );
+                metrics.increment("postgres_write_failures_total", &labels);
+            }
+            result
+        },
+        is_transient,
+    )
+    .await;
+
+    let elapsed = started.elapsed();
+    metrics.set_gauge(
+        "postgres_flush_latency_milliseconds",
+        &labels,
+        elapsed.as_secs_f64() * 1000.0,
+    );
+    metrics.add(
+        "postgres_flush_milliseconds_total",
+        &labels,
+        elapsed.as_millis() as u64,
+    );
+
+    match result {
+        Ok(()) => {
+            metrics.increment("postgres_flushes_total", &labels);
+            metrics.add("postgres_rows_written_total", &labels, batch.len() as u64);
+        }
+        Err(e) => {
+            error!(
+                "Dropping {} rows bound for {}: {}",
+                batch.len(),
+                B::TABLE,
+                e
+            );
+            metrics.add("postgres_rows_dropped_total", &labels, batch.len() as u64);
+        }
+    }
+}
+
+async fn execute<B: Batch>(
+    postgres_pool: &Pool<CustomAsyncPgConnectionManager>,
+    batch: &B,
+) -> Result<(), WriteError> {
+    let mut connection = postgres_pool
+        .get()
+        .await
+        .map_err(|e| WriteError::Pool(e.to_string()))?;
+    connection
+        .transaction::<_, DieselError, _>(|connection| batch.execute(connection).scope_boxed())
+        .await?;
+    Ok(())
+}
+
+/// Whether retrying the batch may succeed: the connection was lost or the
+/// transaction lost a serialization conflict, as opposed to the rows themselves
+/// being rejected.
+fn is_transient(error: &WriteError) -> bool {
+    match error {
+        WriteError::Pool(_) => true,
+        WriteError::Query(DieselError::DatabaseError(kind, _)) => matches!(
+            kind,
+            DatabaseErrorKind::SerializationFailure
+                | DatabaseErrorKind::ClosedConnection
+                | DatabaseErrorKind::UnableToSendCommand
+        ),
+        WriteError::Query(DieselError::BrokenTransactionManager) => true,
+        WriteError::Query(_) => false,
+    }
+}
@@ -0,0 +1,231 @@
+use bigdecimal::BigDecimal;
+use uuid::Uuid;
+
+use crate::events::Side;
+
+/// Version of the key layout described on [`RedisKeys`], stored under
+/// `{namespace}:schema_version`. The flat layout with keys such as
+/// `buy_order:{id}` and no version key is version 1, and version 2 put book
+/// keys under `{namespace}:{exchange}:{symbol}` without a hash tag. Both kept
+/// the reference data outside the namespace, as `exchange:{id}`,
+/// `security:{id}` and `order_book:{id}`.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Removes an order hash and its entry in the price level index. Every key the
+/// script touches is passed in `KEYS`, as Redis Cluster requires, so the caller
+/// must know where the order rests.
+///
+/// `KEYS[1]` is the order hash, `KEYS[2]` the set of its price level and
+/// `KEYS[3]` the level index of its side; `ARGV[1]` is the order id and
+/// `ARGV[2]` its price level.
+pub const REMOVE_ORDER_SCRIPT: &str = r#"
+redis.call('SREM', KEYS[2], ARGV[1])
+if redis.call('SCARD', KEYS[2]) == 0 then
+    redis.call('ZREM', KEYS[3], ARGV[2])
+end
+return redis.call('DEL', KEYS[1])
+"#;
+
+/// Redis key layout. Everything the engine writes lives under its namespace and,
+/// for market data, under the book's exchange and symbol:
+///
+/// - `{namespace}:schema_version`: version of this layout
+/// - `{namespace}:exchange:{exchange_id}`: hash describing an exchange
+/// - `{namespace}:security:{security_id}`: hash describing a security
+/// - `{namespace}:order_book:{order_book_id}`: hash describing an order book,
+///   for readers that know it by id
+/// - `{book}:summary`: hash describing the book and its open volume
+/// - `{book}:order:{order_id}`: hash per resting order
+/// - `{book}:levels:{side}`: sorted set of the side's prices, scored by price
+/// - `{book}:level:{side}:{price}`: set of the order ids resting at a price
+/// - `{book}:depth:{side}`: hash of the Level 2 book's quantity per price
+/// - `{book}:trade:{trade_id}`: hash per trade
+/// - `{book}:ticker`: hash of the latest ticker
+/// - `{book}:candle:{interval}`: hash of the latest candle of an interval in
+///   minutes
+/// - `{book}:instrument`: hash of the symbol's trading rules
+/// - `{book}:events`: stream of the book's events and trades in arrival order
+///
+/// where `{book}` is the hash tag `{{namespace}:{exchange}:{symbol}}` and
+/// `{side}` is `buy` or `sell`. The hash tag puts every key of a book in the
+/// same Redis Cluster slot, so a batch can write them in one `MULTI` and delete
+/// several of them in one `DEL`.
+#[derive(Clone, Debug)]
+pub struct RedisKeys {
+    namespace: String,
+}
+
+impl RedisKeys {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn schema_version(&self) -> String {
+        format!("{}:schema_version", self.namespace)
+    }
+
+    pub fn exchange(&self, exchange_id: Uuid) -> String {
+        format!("{}:exchange:{}", self.namespace, exchange_id)
+    }
+
+    pub fn security(&self, security_id: Uuid) -> String {
+        format!("{}:security:{}", self.namespace, security_id)
+    }
+
+    pub fn order_book(&self, order_book_id: Uuid) -> String {
+        format!("{}:order_book:{}", self.namespace, order_book_id)
+    }
+
+    /// The current name of a reference data key in the layout of versions 1
+    /// and 2, or `None` if `key` is not one.
+    pub fn from_flat_reference(&self, key: &str) -> Option<String> {
+        let (kind, id) = key.split_once(':')?;
+        let id = id.parse().ok()?;
+        match kind {
+            "exchange" => Some(self.exchange(id)),
+            "security" => Some(self.security(id)),
+            "order_book" => Some(self.order_book(id)),
+            _ => None,
+        }
+    }
+
+    /// Patterns that together match every key in the namespace.
+    pub fn patterns(&self) -> [String; 2] {
+        [
+            format!("{}:*", self.namespace),
+            format!("{{{}:*", self.namespace),
+        ]
+    }
+
+    /// Prefix of every key belonging to a book, a hash tag as described on
+    /// [`RedisKeys`].
+    pub fn book(&self, exchange: &str, symbol: &str) -> String {
+        format!("{{{}:{}:{}}}", self.namespace, exchange, symbol)
+    }
+
+    /// The current name of a book key in the version 2 layout, or `None` if
+    /// `key` is not one.
+    pub fn from_version2(&self, key: &str) -> Option<String> {
+        let rest = key.strip_prefix(&self.namespace)?.strip_prefix(':')?;
+        let mut parts = rest.splitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(exchange), Some(symbol), Some(name)) if !name.is_empty() => {
+                Some(format!("{}:{}", self.book(exchange, symbol), name))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn summary(&self, exchange: &str, symbol: &str) -> String {
+        format!("{}:summary", self.book(exchange, symbol))
+    }
+
+    pub fn order(&self, exchange: &str, symbol: &str, order_id: &str) -> String {
+        format!("{}:order:{}", self.book(exchange, symbol), order_id)
+    }
+
+    pub fn levels(&self, exchange: &str, symbol: &str, side: Side) -> String {
+        format!("{}:levels:{}", self.book(exchange, symbol), side.as_str())
+    }
+
+    pub fn level(&self, exchange: &str, symbol: &str, side: Side, price: &BigDecimal) -> String {
+        format!(
+            "{}:level:{}:{}",
+            self.book(exchange, symbol),
+            side.as_str(),
+            price_member(price)
+        )
+    }
+
+    pub fn depth(&self, exchange: &str, symbol: &str, side: Side) -> String {
+        format!("{}:depth:{}", self.book(exchange, symbol), side.as_str())
+    }
+
+    pub fn events(&self, exchange: &str, symbol: &str) -> String {
+        format!("{}:events", self.book(exchange, symbol))
+    }
+
+    pub fn trade(&self, exchange: &str, symbol: &str, trade_id: u64) -> String {
+        format!("{}:trade:{}", self.book(exchange, symbol), trade_id)
+    }
+
+    pub fn ticker(&self, exchange: &str, symbol: &str) -> String {
+        format!("{}:ticker", self.book(exchange, symbol))
+    }
+
+    pub fn candle(&self, exchange: &str, symbol: &str, interval: u32) -> String {
+        format!("{}:candle:{}", self.book(exchange, symbol), interval)
+    }
+
+    pub fn instrument(&self, exchange: &str, symbol: &str) -> String {
+        format!("{}:instrument", self.book(exchange, symbol))
+    }
+}
+
+/// Canonical form of a price in keys and level members, so `100.10` and `100.1`
+/// land on the same level.
+pub fn price_member(price: &BigDecimal) -> String {
+    price.normalized().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn book_keys_share_a_hash_tag() {
+        let keys = RedisKeys::new("data-engine");
+
+        assert_eq!(
+            keys.order("kraken", "BTC/USD", "O1"),
+            "{data-engine:kraken:BTC/USD}:order:O1"
+        );
+        assert_eq!(
+            keys.level("kraken", "BTC/USD", Side::Sell, &"100.10".parse().unwrap()),
+            "{data-engine:kraken:BTC/USD}:level:sell:100.1"
+        );
+    }
+
+    #[test]
+    fn version2_book_keys_are_renamed_under_the_hash_tag() {
+        let keys = RedisKeys::new("data-engine");
+
+        assert_eq!(
+            keys.from_version2("data-engine:kraken:BTC/USD:level:buy:100.1")
+                .as_deref(),
+            Some("{data-engine:kraken:BTC/USD}:level:buy:100.1")
+        );
+        assert_eq!(
+            keys.from_version2("data-engine:kraken:BTC/USD:summary")
+                .as_deref(),
+            Some(keys.summary("kraken", "BTC/USD").as_str())
+        );
+        assert_eq!(keys.from_version2("data-engine:schema_version"), None);
+        assert_eq!(keys.from_version2("other:kraken:BTC/USD:summary"), None);
+        let id = Uuid::nil();
+        assert_eq!(keys.from_version2(&keys.order_book(id)), None);
+    }
+
+    #[test]
+    fn flat_reference_keys_move_into_the_namespace() {
+        let keys = RedisKeys::new("data-engine");
+        let id = Uuid::nil();
+
+        assert_eq!(
+            keys.from_flat_reference(&format!("order_book:{}", id)),
+            Some(format!("data-engine:order_book:{}", id))
+        );
+        assert_eq!(
+            keys.from_flat_reference(&format!("exchange:{}", id)),
+            Some(keys.exchange(id))
+        );
+        assert_eq!(keys.from_flat_reference("order_book:not-an-id"), None);
+        assert_eq!(keys.from_flat_reference(&format!("trade:{}", id)), None);
+    }
+}
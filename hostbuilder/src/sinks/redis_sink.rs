@@ -0,0 +1,738 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use databaseschema::models::OrderBook;
+use deadpool::managed::Pool;
+use deadpool_redis::{Connection, Manager};
+use redis::Pipeline;
+use redis_utils::create_redis_connection;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+use crate::{
+    config::Exchange,
+    events::{Candle, Instrument, MarketEvent, Side, Ticker, Trade},
+    sinks::{
+        redis_keys::{price_member, RedisKeys, REMOVE_ORDER_SCRIPT, SCHEMA_VERSION},
+        BookContext, EventSink,
+    },
+};
+
+/// Keys scanned and written per round trip.
+const SCAN_COUNT: usize = 1000;
+
+/// Side and price level of each order a batch has to find in Redis, by order id.
+type Resting = HashMap<String, (Side, BigDecimal)>;
+
+/// Mirrors every book into Redis under the engine's namespace: a hash per order,
+/// indexed by price level, plus the book summary and trades. See [`RedisKeys`]
+/// for the layout.
+pub struct RedisSink {
+    redis_pool: Arc<Pool<Manager, Connection>>,
+    keys: RedisKeys,
+}
+
+impl RedisSink {
+    pub fn new(redis_pool: Arc<Pool<Manager, Connection>>, keys: RedisKeys) -> Self {
+        Self { redis_pool, keys }
+    }
+
+    /// Brings the database up to [`SCHEMA_VERSION`]. Version 2 book keys are
+    /// moved under their book's hash tag and version 1 data as described on
+    /// [`Self::migrate_from_version1`]. Either version kept the exchange,
+    /// security and order book keys outside the namespace; those move into it.
+    pub async fn migrate(&self, exchanges: &[Exchange]) -> Result<()> {
+        let mut connection = create_redis_connection(&self.redis_pool).await?;
+        let version: Option<u32> = redis::cmd("GET")
+            .arg(self.keys.schema_version())
+            .query_async(&mut connection)
+            .await?;
+
+        match version {
+            Some(version) if version == SCHEMA_VERSION => return Ok(()),
+            Some(version) if version > SCHEMA_VERSION => {
+                return Err(anyhow!(
+                    "Redis schema version {} is newer than the supported version {}",
+                    version,
+                    SCHEMA_VERSION
+                ));
+            }
+            _ => {}
+        }
+        info!(
+            "Migrating Redis keys to schema version {} under {}",
+            SCHEMA_VERSION,
+            self.keys.namespace()
+        );
+
+        match version {
+            Some(2) => {
+                let moved = self.migrate_from_version2(&mut connection).await?;
+                info!("Migrated Redis keys: {} book keys moved", moved);
+            }
+            _ => {
+                let (moved, dropped) = self
+                    .migrate_from_version1(&mut connection, exchanges)
+                    .await?;
+                info!(
+                    "Migrated Redis keys: {} trades moved, {} orders dropped",
+                    moved, dropped
+                );
+            }
+        }
+
+        let mut moved = 0;
+        for pattern in ["exchange:*", "security:*", "order_book:*"] {
+            let keys: Vec<(String, String)> = scan(&mut connection, pattern)
+                .await?
+                .into_iter()
+                .filter_map(|key| {
+                    let target = self.keys.from_flat_reference(&key)?;
+                    Some((key, target))
+                })
+                .collect();
+            moved += move_keys(&mut connection, &keys).await?;
+        }
+        info!(
+            "Migrated Redis keys: {} reference data keys moved into the namespace",
+            moved
+        );
+
+        redis::cmd("SET")
+            .arg(self.keys.schema_version())
+            .arg(SCHEMA_VERSION)
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+        Ok(())
+    }
+
+    /// Moves trades in the flat version 1 layout under their book; the exchange,
+    /// which version 1 did not record, is taken from the configured exchange
+    /// listing the symbol. Orders are dropped rather than moved since every book
+    /// is rebuilt from the venue's next snapshot. Returns the number of trades
+    /// moved and orders dropped.
+    async fn migrate_from_version1(
+        &self,
+        connection: &mut Connection,
+        exchanges: &[Exchange],
+    ) -> Result<(usize, usize)> {
+        let mut moved = 0;
+        for chunk in scan(connection, "trade:*").await?.chunks(SCAN_COUNT) {
+            let mut pipe = redis::pipe();
+            for key in chunk {
+                pipe.cmd("HGETALL").arg(key);
+            }
+            let trades: Vec<HashMap<String, String>> = pipe.query_async(connection).await?;
+
+            // Not atomic: the trades of a chunk belong to different books, and
+            // so to different Redis Cluster slots.
+            let mut pipe = redis::pipe();
+            for (key, mut trade) in chunk.iter().zip(trades) {
+                let (Some(symbol), Some(trade_id)) = (
+                    trade.get("symbol").cloned(),
+                    trade.get("trade_id").and_then(|id| id.parse::<u64>().ok()),
+                ) else {
+                    warn!("Dropping malformed trade {}", key);
+                    pipe.cmd("DEL").arg(key).ignore();
+                    continue;
+                };
+                let mut listed = exchanges
+                    .iter()
+                    .filter(|exchange| exchange.symbols.contains(&symbol));
+                let exchange = match (listed.next(), listed.next()) {
+                    (Some(exchange), None) => exchange.exchange.clone(),
+                    _ => {
+                        warn!("Dropping trade {}: cannot tell its exchange", key);
+                        pipe.cmd("DEL").arg(key).ignore();
+                        continue;
+                    }
+                };
+
+                trade.insert("exchange".to_string(), exchange.clone());
+                let fields: Vec<(String, String)> = trade.into_iter().collect();
+                pipe.cmd("HSET")
+                    .arg(self.keys.trade(&exchange, &symbol, trade_id))
+                    .arg(fields)
+                    .ignore();
+                pipe.cmd("DEL").arg(key).ignore();
+                moved += 1;
+            }
+            pipe.query_async::<_, ()>(connection).await?;
+        }
+
+        let mut dropped = 0;
+        for pattern in ["buy_order:*", "sell_order:*"] {
+            for chunk in scan(connection, pattern).await?.chunks(SCAN_COUNT) {
+                redis::cmd("UNLINK")
+                    .arg(chunk)
+                    .query_async::<_, ()>(connection)
+                    .await?;
+                dropped += chunk.len();
+            }
+        }
+
+        // `order_book:{id}` stays the reference data cache; only the volume the
+        // sink used to add to it moves to the book summary.
+        for chunk in scan(connection, "order_book:*").await?.chunks(SCAN_COUNT) {
+            let mut pipe = redis::pipe();
+            for key in chunk {
+                pipe.cmd("HDEL").arg(key).arg("total_volume").ignore();
+            }
+            pipe.query_async::<_, ()>(connection).await?;
+        }
+
+        Ok((moved, dropped))
+    }
+
+    /// Moves every version 2 book key under its book's hash tag, returning how
+    /// many were moved.
+    async fn migrate_from_version2(&self, connection: &mut Connection) -> Result<usize> {
+        let keys: Vec<(String, String)> = scan(connection, &format!("{}:*", self.keys.namespace()))
+            .await?
+            .into_iter()
+            .filter_map(|key| {
+                let target = self.keys.from_version2(&key)?;
+                Some((key, target))
+            })
+            .collect();
+        move_keys(connection, &keys).await
+    }
+
+    /// Expires every key in the engine's namespace after `ttl`, leaving the rest
+    /// of the database alone. Returns the number of keys expired.
+    pub async fn expire_keys(&self, ttl: Duration) -> Result<usize> {
+        let mut connection = create_redis_connection(&self.redis_pool).await?;
+        let mut keys = vec![];
+        for pattern in self.keys.patterns() {
+            keys.extend(scan(&mut connection, &pattern).await?);
+        }
+
+        for chunk in keys.chunks(SCAN_COUNT) {
+            let mut pipe = redis::pipe();
+            for key in chunk {
+                pipe.cmd("EXPIRE").arg(key).arg(ttl.as_secs()).ignore();
+            }
+            pipe.query_async::<_, ()>(&mut connection).await?;
+        }
+
+        Ok(keys.len())
+    }
+
+    /// Where the orders `events` modify or delete rest before the batch, read
+    /// from Redis so the pipeline can name every key it touches.
+    async fn resting_orders(
+        &self,
+        connection: &mut Connection,
+        order_book: &OrderBook,
+        events: &[MarketEvent],
+    ) -> Result<Resting> {
+        let (exchange, symbol) = (&order_book.exchange, &order_book.symbol);
+        let mut order_ids: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::OrderModified(order) | MarketEvent::OrderDeleted(order) => {
+                    Some(order.order_id.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        order_ids.sort_unstable();
+        order_ids.dedup();
+        if order_ids.is_empty() {
+            return Ok(Resting::new());
+        }
+
+        let mut pipe = redis::pipe();
+        for order_id in order_ids.iter() {
+            pipe.cmd("HMGET")
+                .arg(self.keys.order(exchange, symbol, order_id))
+                .arg("side")
+                .arg("price_level");
+        }
+        let placements: Vec<(Option<String>, Option<String>)> =
+            pipe.query_async(connection).await?;
+
+        Ok(order_ids
+            .into_iter()
+            .zip(placements)
+            .filter_map(|(order_id, placement)| {
+                let side = match placement.0.as_deref() {
+                    Some("buy") => Side::Buy,
+                    Some("sell") => Side::Sell,
+                    _ => return None,
+                };
+                let price = placement.1?.parse().ok()?;
+                Some((order_id.to_string(), (side, price)))
+            })
+            .collect())
+    }
+
+    /// Every key holding the orders and price levels of `order_book`, read from
+    /// Redis so a snapshot can delete them by name, including orders that
+    /// vanished while the engine was down.
+    async fn book_keys(
+        &self,
+        connection: &mut Connection,
+        order_book: &OrderBook,
+    ) -> Result<Vec<String>> {
+        let (exchange, symbol) = (&order_book.exchange, &order_book.symbol);
+        let sides = [Side::Buy, Side::Sell];
+
+        let mut pipe = redis::pipe();
+        for side in sides {
+            pipe.cmd("ZRANGE")
+                .arg(self.keys.levels(exchange, symbol, side))
+                .arg(0)
+                .arg(-1);
+        }
+        let prices: Vec<Vec<String>> = pipe.query_async(connection).await?;
+
+        let mut levels = vec![];
+        for (side, prices) in sides.into_iter().zip(prices) {
+            for price in prices.iter().filter_map(|price| price.parse().ok()) {
+                levels.push(self.keys.level(exchange, symbol, side, &price));
+            }
+        }
+
+        let mut pipe = redis::pipe();
+        for level in levels.iter() {
+            pipe.cmd("SMEMBERS").arg(level);
+        }
+        let members: Vec<Vec<String>> = pipe.query_async(connection).await?;
+
+        let mut keys: Vec<String> = sides
+            .into_iter()
+            .map(|side| self.keys.levels(exchange, symbol, side))
+            .collect();
+        keys.extend(
+            members
+                .iter()
+                .flatten()
+                .map(|order_id| self.keys.order(exchange, symbol, order_id)),
+        );
+        keys.extend(levels);
+        Ok(keys)
+    }
+
+    fn set_order(
+        &self,
+        pipe: &mut Pipeline,
+        order_book: &OrderBook,
+        side: Side,
+        order_id: &str,
+        price: &BigDecimal,
+        quantity: &BigDecimal,
+    ) {
+        let (exchange, symbol) = (&order_book.exchange, &order_book.symbol);
+        let book_id = match side {
+            Side::Buy => order_book.buy_order_book_id,
+            Side::Sell => order_book.sell_order_book_id,
+        };
+
+        pipe.cmd("HSET")
+            .arg(self.keys.order(exchange, symbol, order_id))
+            .arg("symbol")
+            .arg(symbol)
+            .arg("exchange")
+            .arg(exchange)
+            .arg("security_id")
+            .arg(order_book.security_id.to_string())
+            .arg("exchange_id")
+            .arg(order_book.exchange_id.to_string())
+            .arg("order_book_id")
+            .arg(book_id.to_string())
+            .arg("unique_id")
+            .arg(order_id)
+            .arg("side")
+            .arg(side.as_str())
+            .arg("price_level")
+            .arg(price_member(price))
+            .arg("quantity")
+            .arg(quantity.to_string())
+            .ignore();
+        pipe.cmd("SADD")
+            .arg(self.keys.level(exchange, symbol, side, price))
+            .arg(order_id)
+            .ignore();
+        pipe.cmd("ZADD")
+            .arg(self.keys.levels(exchange, symbol, side))
+            .arg(price.to_f64().unwrap_or_default())
+            .arg(price_member(price))
+            .ignore();
+    }
+
+    /// Removes an order from where `resting` says it rests, so a later
+    /// `set_order` can move it to another price.
+    fn remove_order(
+        &self,
+        pipe: &mut Pipeline,
+        resting: &mut Resting,
+        order_book: &OrderBook,
+        order_id: &str,
+    ) {
+        let (exchange, symbol) = (&order_book.exchange, &order_book.symbol);
+        let order = self.keys.order(exchange, symbol, order_id);
+        let Some((side, price)) = resting.remove(order_id) else {
+            pipe.cmd("DEL").arg(order).ignore();
+            return;
+        };
+        pipe.cmd("EVAL")
+            .arg(REMOVE_ORDER_SCRIPT)
+            .arg(3)
+            .arg(order)
+            .arg(self.keys.level(exchange, symbol, side, &price))
+            .arg(self.keys.levels(exchange, symbol, side))
+            .arg(order_id)
+            .arg(price_member(&price))
+            .ignore();
+    }
+
+    fn set_trade(&self, pipe: &mut Pipeline, trade: &Trade) {
+        pipe.cmd("HSET")
+            .arg(
+                self.keys
+                    .trade(&trade.exchange, &trade.symbol, trade.trade_id),
+            )
+            .arg("symbol")
+            .arg(&trade.symbol)
+            .arg("exchange")
+            .arg(&trade.exchange)
+            .arg("side")
+            .arg(trade.side.as_str())
+            .arg("price")
+            .arg(trade.price.to_string())
+            .arg("qty")
+            .arg(trade.quantity.to_string())
+            .arg("ord_type")
+            .arg(&trade.order_type)
+            .arg("trade_id")
+            .arg(trade.trade_id)
+            .arg("timestamp")
+            .arg(trade.timestamp.to_rfc3339())
+            .ignore();
+    }
+
+    /// Overwrites the symbol's ticker hash with the latest values.
+    fn set_ticker(&self, pipe: &mut Pipeline, ticker: &Ticker) {
+        pipe.cmd("HSET")
+            .arg(self.keys.ticker(&ticker.exchange, &ticker.symbol))
+            .arg("symbol")
+            .arg(&ticker.symbol)
+            .arg("exchange")
+            .arg(&ticker.exchange)
+            .arg("bid")
+            .arg(ticker.bid.to_string())
+            .arg("bid_qty")
+            .arg(ticker.bid_quantity.to_string())
+            .arg("ask")
+            .arg(ticker.ask.to_string())
+            .arg("ask_qty")
+            .arg(ticker.ask_quantity.to_string())
+            .arg("last")
+            .arg(ticker.last.to_string())
+            .arg("volume")
+            .arg(ticker.volume.to_string())
+            .arg("vwap")
+            .arg(ticker.vwap.to_string())
+            .arg("high")
+            .arg(ticker.high.to_string())
+            .arg("low")
+            .arg(ticker.low.to_string())
+            .arg("change")
+            .arg(ticker.change.to_string())
+            .arg("change_pct")
+            .arg(ticker.change_pct.to_string())
+            .arg("timestamp")
+            .arg(ticker.timestamp.to_rfc3339())
+            .ignore();
+    }
+
+    /// Overwrites the hash of the candle's interval, so it holds the candle
+    /// being built, or the last one closed until the next starts.
+    fn set_candle(&self, pipe: &mut Pipeline, candle: &Candle, closed: bool) {
+        pipe.cmd("HSET")
+            .arg(
+                self.keys
+                    .candle(&candle.exchange, &candle.symbol, candle.interval),
+            )
+            .arg("symbol")
+            .arg(&candle.symbol)
+            .arg("exchange")
+            .arg(&candle.exchange)
+            .arg("interval")
+            .arg(candle.interval)
+            .arg("interval_begin")
+            .arg(candle.interval_begin.to_rfc3339())
+            .arg("open")
+            .arg(candle.open.to_string())
+            .arg("high")
+            .arg(candle.high.to_string())
+            .arg("low")
+            .arg(candle.low.to_string())
+            .arg("close")
+            .arg(candle.close.to_string())
+            .arg("volume")
+            .arg(candle.volume.to_string())
+            .arg("vwap")
+            .arg(candle.vwap.to_string())
+            .arg("trades")
+            .arg(candle.trades)
+            .arg("closed")
+            .arg(closed)
+            .arg("timestamp")
+            .arg(candle.timestamp.to_rfc3339())
+            .ignore();
+    }
+
+    /// Overwrites the hash of the symbol's trading rules. `min_order_cost` is
+    /// empty when the venue sets none.
+    fn set_instrument(&self, pipe: &mut Pipeline, instrument: &Instrument) {
+        let min_order_cost = instrument
+            .min_order_cost
+            .as_ref()
+            .map(|cost| cost.to_string())
+            .unwrap_or_default();
+
+        pipe.cmd("HSET")
+            .arg(
+                self.keys
+                    .instrument(&instrument.exchange, &instrument.symbol),
+            )
+            .arg("symbol")
+            .arg(&instrument.symbol)
+            .arg("exchange")
+            .arg(&instrument.exchange)
+            .arg("base")
+            .arg(&instrument.base)
+            .arg("quote")
+            .arg(&instrument.quote)
+            .arg("status")
+            .arg(instrument.status.as_str())
+            .arg("price_precision")
+            .arg(instrument.price_precision)
+            .arg("quantity_precision")
+            .arg(instrument.quantity_precision)
+            .arg("tick_size")
+            .arg(instrument.tick_size.to_string())
+            .arg("lot_size")
+            .arg(instrument.lot_size.to_string())
+            .arg("min_order_quantity")
+            .arg(instrument.min_order_quantity.to_string())
+            .arg("min_order_cost")
+            .arg(min_order_cost)
+            .arg("marginable")
+            .arg(instrument.marginable)
+            .arg("timestamp")
+            .arg(instrument.timestamp.to_rfc3339())
+            .ignore();
+    }
+
+    fn set_summary(&self, pipe: &mut Pipeline, order_book: &OrderBook, total_volume: &BigDecimal) {
+        let updated_at = order_book
+            .updated_at
+            .map(|updated_at| updated_at.to_string())
+            .unwrap_or_default();
+
+        pipe.cmd("HSET")
+            .arg(self.keys.summary(&order_book.exchange, &order_book.symbol))
+            .arg("order_book_id")
+            .arg(order_book.order_book_id.to_string())
+            .arg("created_at")
+            .arg(order_book.created_at.to_string())
+            .arg("updated_at")
+            .arg(updated_at)
+            .arg("symbol")
+            .arg(&order_book.symbol)
+            .arg("exchange")
+            .arg(&order_book.exchange)
+            .arg("security_id")
+            .arg(order_book.security_id.to_string())
+            .arg("exchange_id")
+            .arg(order_book.exchange_id.to_string())
+            .arg("buy_order_book_id")
+            .arg(order_book.buy_order_book_id.to_string())
+            .arg("sell_order_book_id")
+            .arg(order_book.sell_order_book_id.to_string())
+            .arg("total_volume")
+            .arg(total_volume.to_string())
+            .ignore();
+    }
+}
+
+#[async_trait]
+impl EventSink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn handle(&self, context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+        let order_book = &context.order_book;
+        let (exchange, symbol) = (&order_book.exchange, &order_book.symbol);
+        let mut connection = create_redis_connection(&self.redis_pool).await?;
+        let mut resting = self
+            .resting_orders(&mut connection, order_book, events)
+            .await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+
+        for event in events {
+            match event {
+                MarketEvent::BookSnapshot(snapshot) => {
+                    // The orders placed earlier in the batch are not in Redis yet.
+                    let mut keys = self.book_keys(&mut connection, order_book).await?;
+                    for (order_id, (side, price)) in resting.drain() {
+                        keys.push(self.keys.order(exchange, symbol, &order_id));
+                        keys.push(self.keys.level(exchange, symbol, side, &price));
+                        keys.push(self.keys.levels(exchange, symbol, side));
+                    }
+                    if !keys.is_empty() {
+                        pipe.cmd("DEL").arg(keys).ignore();
+                    }
+                    for (side, orders) in
+                        [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)]
+                    {
+                        for order in orders {
+                            self.set_order(
+                                &mut pipe,
+                                order_book,
+                                side,
+                                &order.order_id,
+                                &order.price,
+                                &order.quantity,
+                            );
+                            resting.insert(order.order_id.clone(), (side, order.price.clone()));
+                        }
+                    }
+                }
+                MarketEvent::OrderAdded(order) => {
+                    self.set_order(
+                        &mut pipe,
+                        order_book,
+                        order.side,
+                        &order.order_id,
+                        &order.price,
+                        &order.quantity,
+                    );
+                    resting.insert(order.order_id.clone(), (order.side, order.price.clone()));
+                }
+                MarketEvent::OrderModified(order) => {
+                    self.remove_order(&mut pipe, &mut resting, order_book, &order.order_id);
+                    self.set_order(
+                        &mut pipe,
+                        order_book,
+                        order.side,
+                        &order.order_id,
+                        &order.price,
+                        &order.quantity,
+                    );
+                    resting.insert(order.order_id.clone(), (order.side, order.price.clone()));
+                }
+                MarketEvent::OrderDeleted(order) => {
+                    self.remove_order(&mut pipe, &mut resting, order_book, &order.order_id);
+                }
+                MarketEvent::LevelSnapshot(snapshot) => {
+                    for (side, levels) in
+                        [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)]
+                    {
+                        let key = self.keys.depth(&snapshot.exchange, &snapshot.symbol, side);
+                        pipe.cmd("DEL").arg(&key).ignore();
+                        for level in levels {
+                            pipe.cmd("HSET")
+                                .arg(&key)
+                                .arg(price_member(&level.price))
+                                .arg(level.quantity.to_string())
+                                .ignore();
+                        }
+                    }
+                }
+                MarketEvent::LevelUpdated(level) => {
+                    let key = self.keys.depth(&level.exchange, &level.symbol, level.side);
+                    if level.quantity.is_zero() {
+                        pipe.cmd("HDEL")
+                            .arg(key)
+                            .arg(price_member(&level.price))
+                            .ignore();
+                    } else {
+                        pipe.cmd("HSET")
+                            .arg(key)
+                            .arg(price_member(&level.price))
+                            .arg(level.quantity.to_string())
+                            .ignore();
+                    }
+                }
+                MarketEvent::Trade(trade) => self.set_trade(&mut pipe, trade),
+                MarketEvent::Ticker(ticker) => self.set_ticker(&mut pipe, ticker),
+                MarketEvent::Candle(candle) => self.set_candle(&mut pipe, candle, false),
+                MarketEvent::CandleClosed(candle) => self.set_candle(&mut pipe, candle, true),
+                MarketEvent::Instrument(instrument) => self.set_instrument(&mut pipe, instrument),
+                MarketEvent::Account(_) | MarketEvent::Heartbeat(_) | MarketEvent::Status(_) => {}
+            }
+        }
+
+        if let Some(total_volume) = &context.total_volume {
+            self.set_summary(&mut pipe, order_book, total_volume);
+        }
+
+        pipe.query_async::<_, ()>(&mut connection).await?;
+        Ok(())
+    }
+}
+
+/// Moves each key of `keys` to its new name, returning how many existed. Keys
+/// are copied with `DUMP` and `RESTORE` rather than renamed, since Redis
+/// Cluster refuses a `RENAME` across slots.
+async fn move_keys(connection: &mut Connection, keys: &[(String, String)]) -> Result<usize> {
+    let mut moved = 0;
+    for chunk in keys.chunks(SCAN_COUNT) {
+        let mut pipe = redis::pipe();
+        for (key, _) in chunk {
+            pipe.cmd("DUMP").arg(key);
+        }
+        let dumps: Vec<Option<Vec<u8>>> = pipe.query_async(connection).await?;
+
+        let mut pipe = redis::pipe();
+        for (key, _) in chunk {
+            pipe.cmd("PTTL").arg(key);
+        }
+        let ttls: Vec<i64> = pipe.query_async(connection).await?;
+
+        let mut pipe = redis::pipe();
+        for (((key, target), dump), ttl) in chunk.iter().zip(dumps).zip(ttls) {
+            if let Some(dump) = dump {
+                pipe.cmd("RESTORE")
+                    .arg(target)
+                    .arg(ttl.max(0))
+                    .arg(dump)
+                    .arg("REPLACE")
+                    .ignore();
+                moved += 1;
+            }
+            pipe.cmd("UNLINK").arg(key).ignore();
+        }
+        pipe.query_async::<_, ()>(connection).await?;
+    }
+    Ok(moved)
+}
+
+/// Every key matching `pattern`, gathered with `SCAN` so Redis is never blocked.
+async fn scan(connection: &mut Connection, pattern: &str) -> Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut cursor: u64 = 0;
+
+    loop {
+        let (next, mut batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(connection)
+            .await?;
+        keys.append(&mut batch);
+
+        if next == 0 {
+            return Ok(keys);
+        }
+        cursor = next;
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use deadpool::managed::Pool;
+use deadpool_redis::{Connection, Manager};
+use redis_utils::create_redis_connection;
+use std::sync::Arc;
+
+use crate::{
+    events::MarketEvent,
+    sinks::{redis_keys::RedisKeys, BookContext, EventSink},
+};
+
+/// Appends every book event and trade to its symbol's Redis Stream, in the order
+/// the engine applied them. Entry IDs are Redis' millisecond timestamps, so a
+/// consumer can resume after the last ID it saw or replay a time window with
+/// `XRANGE`. Each entry holds the event `type`, the book `sequence` for book
+/// events, and the `event` itself as JSON.
+pub struct RedisStreamSink {
+    redis_pool: Arc<Pool<Manager, Connection>>,
+    keys: RedisKeys,
+    max_len: usize,
+}
+
+impl RedisStreamSink {
+    pub fn new(
+        redis_pool: Arc<Pool<Manager, Connection>>,
+        keys: RedisKeys,
+        max_len: usize,
+    ) -> Self {
+        Self {
+            redis_pool,
+            keys,
+            max_len,
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for RedisStreamSink {
+    fn name(&self) -> &str {
+        "redis_stream"
+    }
+
+    async fn handle(&self, _context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+        let mut pipe = redis::pipe();
+        let mut appended = 0;
+
+        for event in events {
+            let Some(symbol) = event.symbol() else {
+                continue;
+            };
+            let sequence = match event {
+                MarketEvent::OrderAdded(order)
+                | MarketEvent::OrderModified(order)
+                | MarketEvent::OrderDeleted(order) => order.sequence,
+                MarketEvent::BookSnapshot(snapshot) => snapshot.sequence,
+                MarketEvent::LevelUpdated(level) => level.sequence,
+                MarketEvent::LevelSnapshot(snapshot) => snapshot.sequence,
+                _ => None,
+            };
+
+            let command = pipe
+                .cmd("XADD")
+                .arg(self.keys.events(event.exchange(), symbol))
+                .arg("MAXLEN")
+                .arg("~")
+                .arg(self.max_len)
+                .arg("*")
+                .arg("type")
+                .arg(event.kind());
+            if let Some(sequence) = sequence {
+                command.arg("sequence").arg(sequence);
+            }
+            command
+                .arg("event")
+                .arg(serde_json::to_string(event)?)
+                .ignore();
+            appended += 1;
+        }
+
+        if appended == 0 {
+            return Ok(());
+        }
+
+        let mut connection = create_redis_connection(&self.redis_pool).await?;
+        pipe.query_async::<_, ()>(&mut connection).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::RwLock,
+};
+
+use crate::{
+    events::{MarketEvent, Trade},
+    sinks::{BookContext, EventSink},
+};
+
+/// Trades kept per symbol.
+pub const MAX_TRADES: usize = 1000;
+
+/// Keeps the most recent trades of every symbol in memory for the query API.
+#[derive(Default)]
+pub struct TradeHistory {
+    trades: RwLock<HashMap<(String, String), VecDeque<Trade>>>,
+}
+
+impl TradeHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Up to `limit` of the latest trades for `exchange`/`symbol`, newest first.
+    pub fn recent(&self, exchange: &str, symbol: &str, limit: usize) -> Vec<Trade> {
+        self.trades
+            .read()
+            .ok()
+            .and_then(|trades| {
+                trades
+                    .get(&(exchange.to_string(), symbol.to_string()))
+                    .map(|trades| trades.iter().rev().take(limit).cloned().collect())
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl EventSink for TradeHistory {
+    fn name(&self) -> &str {
+        "trade_history"
+    }
+
+    async fn handle(&self, _context: &BookContext, events: &[MarketEvent]) -> Result<()> {
+        let Ok(mut history) = self.trades.write() else {
+            return Ok(());
+        };
+
+        for event in events {
+            if let MarketEvent::Trade(trade) = event {
+                let trades = history
+                    .entry((trade.exchange.clone(), trade.symbol.clone()))
+                    .or_default();
+                if trades.len() == MAX_TRADES {
+                    trades.pop_front();
+                }
+                trades.push_back(trade.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,29 @@
-use hostbuilder::{HostedObject, HostedObjectTrait};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use hostbuilder::{config::Config, connector::ConnectorRegistry, HostedObject, HostedObjectTrait};
+use std::env;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    if let Some(arg) = args.next() {
+        if arg == "--check-config" {
+            return check_config(args.next());
+        }
+    }
+
     let mut engine = HostedObject::new().await?;
     engine.run().await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Validates the config at `path`, or at `CONFIG_PATH`, with the environment
+/// overrides applied, and reports every problem found.
+fn check_config(path: Option<String>) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => env::var("CONFIG_PATH").context("Pass a config path or set CONFIG_PATH")?,
+    };
+    Config::check(&path, &ConnectorRegistry::default())?;
+    println!("{} is valid", path);
+    Ok(())
+}